@@ -0,0 +1,236 @@
+//! Minimal OpenID Connect provider for trying out SSO locally.
+//!
+//! Every authorization request is approved immediately for a single fixed user.
+//!
+//!   cargo run --example mock_oidc_idp
+//!
+//! then start the API with
+//!   OIDC_ISSUER=http://localhost:9000
+//!   OIDC_CLIENT_ID=async-rust
+//!   OIDC_REDIRECT_URI=http://localhost:3000/api/v1/auth/oidc/callback
+//! and open http://localhost:3000/api/v1/auth/oidc/login in a browser.
+//!
+//! `MOCK_IDP_PORT`, `MOCK_IDP_SUBJECT`, `MOCK_IDP_EMAIL` and `MOCK_IDP_NAME`
+//! override the defaults. The OIDC service tests also mount `router` in-process.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Form, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, EncodingKey, Header, Algorithm};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use rsa::{pkcs1::EncodeRsaPrivateKey, pkcs8::LineEnding, traits::PublicKeyParts, RsaPrivateKey};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const KEY_ID: &str = "mock-idp-1";
+
+struct IssuedCode {
+    client_id: String,
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: String,
+}
+
+struct Idp {
+    issuer: String,
+    encoding: EncodingKey,
+    jwk: Value,
+    codes: Mutex<HashMap<String, IssuedCode>>,
+}
+
+type SharedIdp = Arc<Idp>;
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: String,
+    code_verifier: String,
+}
+
+fn random_string(len: usize) -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn oauth_error(error: &str, description: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": error, "error_description": description })),
+    )
+        .into_response()
+}
+
+/// GET /.well-known/openid-configuration
+async fn discovery(State(idp): State<SharedIdp>) -> Json<Value> {
+    Json(json!({
+        "issuer": idp.issuer,
+        "authorization_endpoint": format!("{}/authorize", idp.issuer),
+        "token_endpoint": format!("{}/token", idp.issuer),
+        "jwks_uri": format!("{}/jwks", idp.issuer),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+/// GET /authorize
+/// Approves straight away and sends the browser back with a code.
+async fn authorize(State(idp): State<SharedIdp>, Query(query): Query<AuthorizeQuery>) -> Response {
+    if query.response_type != "code" {
+        return oauth_error("unsupported_response_type", "only response_type=code is supported");
+    }
+    let Some(code_challenge) = query.code_challenge else {
+        return oauth_error("invalid_request", "PKCE code_challenge is required");
+    };
+    if query.code_challenge_method.as_deref() != Some("S256") {
+        return oauth_error("invalid_request", "code_challenge_method must be S256");
+    }
+
+    let code = random_string(32);
+    idp.codes.lock().unwrap().insert(
+        code.clone(),
+        IssuedCode {
+            client_id: query.client_id,
+            redirect_uri: query.redirect_uri.clone(),
+            nonce: query.nonce,
+            code_challenge,
+        },
+    );
+
+    let mut params = vec![("code", code)];
+    if let Some(state) = query.state {
+        params.push(("state", state));
+    }
+    match reqwest::Url::parse_with_params(&query.redirect_uri, &params) {
+        Ok(url) => Redirect::to(url.as_str()).into_response(),
+        Err(_) => oauth_error("invalid_request", "invalid redirect_uri"),
+    }
+}
+
+/// POST /token
+async fn token(State(idp): State<SharedIdp>, Form(form): Form<TokenForm>) -> Response {
+    if form.grant_type != "authorization_code" {
+        return oauth_error("unsupported_grant_type", "only authorization_code is supported");
+    }
+    let Some(issued) = idp.codes.lock().unwrap().remove(&form.code) else {
+        return oauth_error("invalid_grant", "unknown or already used code");
+    };
+    if issued.client_id != form.client_id || issued.redirect_uri != form.redirect_uri {
+        return oauth_error("invalid_grant", "client_id or redirect_uri mismatch");
+    }
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form.code_verifier.as_bytes()));
+    if challenge != issued.code_challenge {
+        return oauth_error("invalid_grant", "PKCE verification failed");
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = json!({
+        "iss": idp.issuer,
+        "sub": env::var("MOCK_IDP_SUBJECT").unwrap_or("mock-user-1".to_string()),
+        "aud": issued.client_id,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": issued.nonce,
+        "email": env::var("MOCK_IDP_EMAIL").unwrap_or("sso.user@example.com".to_string()),
+        "email_verified": true,
+        "name": env::var("MOCK_IDP_NAME").unwrap_or("SSO User".to_string()),
+    });
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_ID.to_string());
+
+    match encode(&header, &claims, &idp.encoding) {
+        Ok(id_token) => Json(json!({
+            "access_token": random_string(32),
+            "token_type": "Bearer",
+            "expires_in": 300,
+            "id_token": id_token,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": "server_error", "error_description": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /jwks
+async fn jwks(State(idp): State<SharedIdp>) -> Json<Value> {
+    Json(json!({ "keys": [idp.jwk] }))
+}
+
+/// The provider's routes, issuing tokens as `issuer` with a freshly generated key
+pub fn router(issuer: String) -> Router {
+    let private_key = RsaPrivateKey::new(&mut OsRng, 2048).expect("Failed to generate RSA key");
+    let pem = private_key
+        .to_pkcs1_pem(LineEnding::LF)
+        .expect("Failed to encode RSA key");
+    let encoding = EncodingKey::from_rsa_pem(pem.as_bytes()).expect("Invalid RSA key");
+
+    let jwk = json!({
+        "kty": "RSA",
+        "use": "sig",
+        "alg": "RS256",
+        "kid": KEY_ID,
+        "n": URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+        "e": URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+    });
+
+    let idp = Arc::new(Idp {
+        issuer,
+        encoding,
+        jwk,
+        codes: Mutex::new(HashMap::new()),
+    });
+
+    Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/authorize", get(authorize))
+        .route("/token", post(token))
+        .route("/jwks", get(jwks))
+        .with_state(idp)
+}
+
+#[tokio::main]
+async fn main() {
+    let port: u16 = env::var("MOCK_IDP_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(9000);
+
+    let app = router(format!("http://localhost:{}", port));
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
+        .await
+        .expect("Failed to bind mock IdP port");
+    println!("Mock OIDC provider listening on http://localhost:{}", port);
+    axum::serve(listener, app).await.unwrap();
+}
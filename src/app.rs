@@ -1,18 +1,132 @@
-use axum::Router;
+use axum::{Extension, Router};
 
 use crate::db;
-use crate::routes::{user_routes, vehicle_routes};
+use crate::mailer;
+use crate::routes::{
+    api_key_routes, document_routes, file_routes, fuel_routes, jwks_routes, maintenance_routes, oidc_routes, role_routes,
+    service_record_routes, transfer_routes, user_routes, vehicle_routes,
+};
+use crate::services::{
+    document_service, file_service, maintenance_service, role_service, user_service, vehicle_service,
+};
+use crate::storage;
+use crate::utils::{jwt, signed_url};
 
 pub async fn build_app() -> Router {
+    jwt::init_from_env().expect("Invalid JWT key configuration");
+    signed_url::init_from_env().expect("Invalid signed URL configuration");
+
     // let task_db = db::connect_task_collection().await;
     let user_db = db::connect_user_collection().await;
     let vehicle_db = db::connect_vehicle_collection().await;
+    let (converted, unconvertible) = vehicle_service::migrate_string_years(&vehicle_db)
+        .await
+        .expect("Failed to migrate vehicle years");
+    if converted > 0 {
+        println!("Converted {} vehicle year(s) to numbers", converted);
+    }
+    if unconvertible > 0 {
+        eprintln!("{} vehicle(s) have an unreadable year; see their legacy_year field", unconvertible);
+    }
+    let file_store = storage::file_store_from_env();
+    let session_db = db::connect_session_collection().await;
+    let user_token_db = db::connect_user_token_collection().await;
+    let role_db = db::connect_role_collection().await;
+    role_service::seed_builtin_roles(&role_db)
+        .await
+        .expect("Failed to seed built-in roles");
+    let role_audit_db = db::connect_role_audit_collection().await;
+    user_service::bootstrap_admin(&user_db, &role_audit_db)
+        .await
+        .expect("Failed to bootstrap admin account");
+    let api_key_db = db::connect_api_key_collection().await;
+    let oidc_state_db = db::connect_oidc_state_collection().await;
+    let service_record_db = db::connect_service_record_collection().await;
+    let fuel_log_db = db::connect_fuel_log_collection().await;
+    let maintenance_rule_db = db::connect_maintenance_rule_collection().await;
+    let maintenance_due_db = db::connect_maintenance_due_collection().await;
+    maintenance_service::spawn_maintenance_evaluator(
+        maintenance_rule_db.clone(),
+        maintenance_due_db.clone(),
+        vehicle_db.clone(),
+        service_record_db.clone(),
+        fuel_log_db.clone(),
+    );
+    let vehicle_document_db = db::connect_vehicle_document_collection().await;
+    let migrated = file_service::migrate_upload_paths(
+        &vehicle_db,
+        &user_db,
+        &service_record_db,
+        &vehicle_document_db,
+    )
+    .await
+    .expect("Failed to migrate upload paths");
+    if migrated > 0 {
+        println!("Converted upload paths to storage keys on {} record(s)", migrated);
+    }
+    vehicle_service::spawn_vehicle_purge(
+        vehicle_db.clone(),
+        vehicle_service::VehicleChildDbs {
+            services: service_record_db.clone(),
+            fuel: fuel_log_db.clone(),
+            maintenance_rules: maintenance_rule_db.clone(),
+            maintenance_due: maintenance_due_db.clone(),
+            documents: vehicle_document_db.clone(),
+        },
+        file_store.clone(),
+    );
+    let vehicle_transfer_db = db::connect_vehicle_transfer_collection().await;
+    let mailer = mailer::mailer_from_env();
+    document_service::spawn_document_expiry_check(
+        vehicle_document_db.clone(),
+        vehicle_db.clone(),
+        user_db.clone(),
+        mailer.clone(),
+    );
 
     // let task_router = task_routes::create_task_routes(task_db);
-    let user_router = user_routes::user_routes(user_db);
-    let vehicle_router = vehicle_routes::vehicle_routes(vehicle_db);
+    let user_router = user_routes::user_routes(user_db.clone());
+    let vehicle_router = vehicle_routes::vehicle_routes(vehicle_db.clone());
+    let role_router = role_routes::role_routes(role_db.clone());
+    let api_key_router = api_key_routes::api_key_routes(api_key_db.clone());
+    let oidc_router = oidc_routes::oidc_routes(user_db.clone());
+    let file_owner_dbs = file_service::FileOwnerDbs {
+        vehicles: vehicle_db.clone(),
+        users: user_db.clone(),
+        services: service_record_db.clone(),
+        documents: vehicle_document_db.clone(),
+    };
+    let service_record_router = service_record_routes::service_record_routes(service_record_db);
+    let fuel_router = fuel_routes::fuel_routes(fuel_log_db);
+    let maintenance_router = maintenance_routes::maintenance_routes(maintenance_rule_db);
+    let document_router = document_routes::document_routes(vehicle_document_db);
+    let transfer_router = transfer_routes::transfer_routes(vehicle_transfer_db);
+    let file_router = file_routes::file_routes(file_store.clone());
 
     Router::new()
+        .merge(jwks_routes::jwks_routes())
         .nest("/api/v1", user_router)
         .nest("/api/v1", vehicle_router)
+        .nest("/api/v1", role_router)
+        .nest("/api/v1", api_key_router)
+        .nest("/api/v1", oidc_router)
+        .nest("/api/v1", service_record_router)
+        .nest("/api/v1", fuel_router)
+        .nest("/api/v1", maintenance_router)
+        .nest("/api/v1", document_router)
+        .nest("/api/v1", transfer_router)
+        .nest("/api/v1", file_router)
+        // Read by the AuthUser / RequirePermission extractors on every protected route
+        .layer(Extension(session_db))
+        .layer(Extension(role_db))
+        .layer(Extension(api_key_db))
+        .layer(Extension(user_db))
+        .layer(Extension(role_audit_db))
+        .layer(Extension(user_token_db))
+        .layer(Extension(oidc_state_db))
+        .layer(Extension(vehicle_db))
+        .layer(Extension(maintenance_due_db))
+        .layer(Extension(mailer))
+        .layer(Extension(file_store))
+        .layer(Extension(file_owner_dbs))
 }
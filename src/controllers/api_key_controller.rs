@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    db::ApiKeyDb,
+    middlewares::auth_middleware::AuthUser,
+    models::api_key_model::{ApiKeyResponse, CreateApiKey, UpdateApiKey},
+    services::api_key_service::{create_api_key, delete_api_key, list_api_keys, update_api_key},
+};
+
+/// Keys may only be managed from an interactive session, so a leaked key
+/// can't mint itself new ones.
+fn require_session(auth: &AuthUser) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if auth.session_id.is_none() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "API keys cannot manage API keys" })),
+        ));
+    }
+    Ok(())
+}
+
+/// POST /api-keys
+/// The full key is returned only in this response.
+pub async fn create_api_key_handler(
+    State(db): State<ApiKeyDb>,
+    auth: AuthUser,
+    Json(payload): Json<CreateApiKey>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match create_api_key(&db, &auth.user_id, payload).await {
+        Ok((record, key)) => (
+            StatusCode::CREATED,
+            Json(json!({
+                "message": "API key created; store it now, it won't be shown again",
+                "key": key,
+                "api_key": ApiKeyResponse::from(record),
+            })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /api-keys
+pub async fn list_api_keys_handler(
+    State(db): State<ApiKeyDb>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match list_api_keys(&db, &auth.user_id).await {
+        Ok(keys) => {
+            let keys: Vec<ApiKeyResponse> = keys.into_iter().map(ApiKeyResponse::from).collect();
+            (StatusCode::OK, Json(json!({ "api_keys": keys })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        ),
+    }
+}
+
+/// PUT /api-keys/:id
+pub async fn update_api_key_handler(
+    State(db): State<ApiKeyDb>,
+    auth: AuthUser,
+    AxPath(id): AxPath<String>,
+    Json(payload): Json<UpdateApiKey>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match update_api_key(&db, &auth.user_id, &id, payload).await {
+        Ok(key) => (
+            StatusCode::OK,
+            Json(json!({ "message": "API key updated", "api_key": ApiKeyResponse::from(key) })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// DELETE /api-keys/:id
+pub async fn delete_api_key_handler(
+    State(db): State<ApiKeyDb>,
+    auth: AuthUser,
+    AxPath(id): AxPath<String>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match delete_api_key(&db, &auth.user_id, &id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "message": "API key deleted" }))),
+        Err(e) => (StatusCode::NOT_FOUND, Json(json!({ "error": e }))),
+    }
+}
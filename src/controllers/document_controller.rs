@@ -0,0 +1,239 @@
+use axum::{
+    extract::{Extension, Multipart, Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::vehicle_controller::{owner_scope, vehicle_error_response},
+    db::{VehicleDb, VehicleDocumentDb},
+    middlewares::{
+        auth_middleware::RequirePermission,
+        upload_middleware::{save_upload, upload_error_response, UploadBudget, UploadKind},
+    },
+    models::{
+        document_model::VehicleDocumentForm,
+        permission_model::{VehicleReadAny, VehicleReadOwn, VehicleUpdateAny, VehicleUpdateOwn},
+    },
+    services::{
+        document_service::{
+            create_document, delete_document, get_document, list_documents, update_document,
+        },
+        vehicle_service::VehicleError,
+    },
+    storage::{discard, SharedFileStore},
+};
+
+fn not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Vehicle or document not found" })),
+    )
+}
+
+/// Read the document form: text fields plus at most one `file`; a later file
+/// replaces an earlier one
+async fn read_document_form(
+    store: &SharedFileStore,
+    mut multipart: Multipart,
+) -> Result<(VehicleDocumentForm, Option<String>), (StatusCode, Json<serde_json::Value>)> {
+    let mut form = VehicleDocumentForm::default();
+    let mut file: Option<String> = None;
+    let mut budget = UploadBudget::from_env();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                discard_file(store, file).await;
+                return Err(upload_error_response(e.into()));
+            }
+        };
+        let field_name = field.name().unwrap_or("").trim().to_lowercase();
+        match field_name.as_str() {
+            "file" => match save_upload(store, field, UploadKind::VehicleDocument, &mut budget).await {
+                Ok(key) => {
+                    discard_file(store, file.replace(key)).await;
+                }
+                Err(e) => {
+                    discard_file(store, file).await;
+                    return Err(upload_error_response(e));
+                }
+            },
+            name => {
+                let slot = match name {
+                    "kind" => &mut form.kind,
+                    "issuer" => &mut form.issuer,
+                    "number" => &mut form.number,
+                    "issued_on" => &mut form.issued_on,
+                    "expires_on" => &mut form.expires_on,
+                    _ => continue,
+                };
+                if let Ok(text) = field.text().await {
+                    *slot = Some(text);
+                }
+            }
+        }
+    }
+
+    Ok((form, file))
+}
+
+async fn discard_file(store: &SharedFileStore, file: Option<String>) {
+    if let Some(key) = file {
+        discard(store, &key).await;
+    }
+}
+
+/// POST /vehicle/:id/documents
+/// Accepts multipart/form-data:
+/// - kind (insurance | registration | inspection | other)
+/// - issuer, number (text, optional)
+/// - issued_on, expires_on (YYYY-MM-DD or RFC 3339, optional)
+/// - file (scan or PDF, optional)
+pub async fn create_document_handler(
+    State(db): State<VehicleDocumentDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let (form, file) = match read_document_form(&store, multipart).await {
+        Ok(parsed) => parsed,
+        Err(rejection) => return rejection,
+    };
+    let mut payload = match form.into_create() {
+        Ok(payload) => payload,
+        Err(fields) => {
+            discard_file(&store, file).await;
+            return vehicle_error_response(VehicleError::Invalid(fields));
+        }
+    };
+
+    payload.file = file.clone();
+
+    match create_document(&db, &vehicles, &vehicle_id, owner, payload).await {
+        Ok(Some(document)) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Document added", "document": document })),
+        ),
+        Ok(None) => {
+            discard_file(&store, file).await;
+            not_found()
+        }
+        Err(e) => {
+            discard_file(&store, file).await;
+            (StatusCode::BAD_REQUEST, Json(json!({ "error": e })))
+        }
+    }
+}
+
+/// GET /vehicle/:id/documents
+pub async fn list_documents_handler(
+    State(db): State<VehicleDocumentDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match list_documents(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(documents)) => (StatusCode::OK, Json(json!({ "documents": documents }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/documents/:document_id
+pub async fn get_document_handler(
+    State(db): State<VehicleDocumentDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath((vehicle_id, document_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match get_document(&db, &vehicles, &vehicle_id, &document_id, owner).await {
+        Ok(Some(document)) => (StatusCode::OK, Json(json!({ "document": document }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// PUT /vehicle/:id/documents/:document_id
+/// Same fields as create, all optional; an uploaded file replaces the current one.
+pub async fn update_document_handler(
+    State(db): State<VehicleDocumentDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, document_id)): AxPath<(String, String)>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let (form, file) = match read_document_form(&store, multipart).await {
+        Ok(parsed) => parsed,
+        Err(rejection) => return rejection,
+    };
+    let mut payload = match form.into_update() {
+        Ok(payload) => payload,
+        Err(fields) => {
+            discard_file(&store, file).await;
+            return vehicle_error_response(VehicleError::Invalid(fields));
+        }
+    };
+
+    payload.file = file.clone();
+
+    match update_document(&db, &vehicles, &store, &vehicle_id, &document_id, owner, payload).await {
+        Ok(Some(document)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Document updated", "document": document })),
+        ),
+        Ok(None) => {
+            discard_file(&store, file).await;
+            not_found()
+        }
+        Err(e) => {
+            discard_file(&store, file).await;
+            (StatusCode::BAD_REQUEST, Json(json!({ "error": e })))
+        }
+    }
+}
+
+/// DELETE /vehicle/:id/documents/:document_id
+pub async fn delete_document_handler(
+    State(db): State<VehicleDocumentDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, document_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match delete_document(&db, &vehicles, &store, &vehicle_id, &document_id, owner).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Document deleted" }))),
+        Ok(false) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
@@ -0,0 +1,395 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Path as AxPath, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    middlewares::auth_middleware::{AuthUser, RequirePermission},
+    models::{
+        file_model::{Disposition, FileListQuery, SignFileRequest, SignedFileQuery},
+        permission_model::{FileList, Permission, PermissionSet, UserUpdateAny, VehicleReadAny, VehicleReadOwn},
+    },
+    services::file_service::{check_signed_url, file_owner, signed_file_url, FileOwner, FileOwnerDbs},
+    storage::{is_valid_key, ByteRange, ObjectMeta, SharedFileStore},
+};
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": "File not found" }))).into_response()
+}
+
+fn invalid_key() -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": "Invalid file key" }))).into_response()
+}
+
+/// Let the caller through if they own the record the file belongs to, or hold
+/// the matching `:any` permission. Everything else is a 404, like a missing file.
+async fn authorize_file(
+    dbs: &FileOwnerDbs,
+    user: &AuthUser,
+    permissions: &PermissionSet,
+    key: &str,
+) -> Result<(), Response> {
+    if !is_valid_key(key) {
+        return Err(invalid_key());
+    }
+
+    let owner = match file_owner(dbs, key).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return Err(not_found()),
+        Err(e) => {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))).into_response())
+        }
+    };
+    let allowed = match owner {
+        FileOwner::Vehicle(owner) => {
+            permissions.allows(VehicleReadAny::NAME) || user.user_object_id() == Ok(owner)
+        }
+        FileOwner::User(owner) => {
+            permissions.allows(UserUpdateAny::NAME) || user.user_object_id() == Ok(owner)
+        }
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(not_found())
+    }
+}
+
+/// GET /files?prefix=
+/// Admin view of what is in the store, including files no record points at any more
+pub async fn list_files_handler(
+    State(store): State<SharedFileStore>,
+    _: RequirePermission<FileList>,
+    Query(query): Query<FileListQuery>,
+) -> Response {
+    if query.prefix.starts_with('/') || query.prefix.split('/').any(|part| part == "..") {
+        return invalid_key();
+    }
+
+    match store.list(&query.prefix).await {
+        Ok(files) => (StatusCode::OK, Json(json!({ "files": files }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))).into_response(),
+    }
+}
+
+/// GET /files/*key
+/// Streams a stored file to the owner of the vehicle (or user, for profile
+/// images) it belongs to; `vehicle:read:any` / `user:update:any` can fetch
+/// anyone's. Supports a single-range `Range` header and `If-None-Match`.
+/// Files the caller may not see are a 404, like missing ones.
+pub async fn download_file_handler(
+    State(store): State<SharedFileStore>,
+    Extension(dbs): Extension<FileOwnerDbs>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(key): AxPath<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = authorize_file(&dbs, &user, &permissions, &key).await {
+        return rejection;
+    }
+
+    stream_file(&store, &key, &headers, None).await
+}
+
+/// POST /signed-urls
+/// Body: { "key": "...", "disposition": "inline" | "attachment", "expires_in": seconds }.
+/// Returns a link to one file that works without a token until it expires;
+/// the caller needs the same access as for `GET /files/*key`.
+pub async fn sign_file_handler(
+    Extension(dbs): Extension<FileOwnerDbs>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    Json(payload): Json<SignFileRequest>,
+) -> Response {
+    if let Err(rejection) = authorize_file(&dbs, &user, &permissions, &payload.key).await {
+        return rejection;
+    }
+
+    match signed_file_url(&payload.key, payload.disposition, payload.expires_in) {
+        Ok(signed) => (StatusCode::OK, Json(json!(signed))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    }
+}
+
+/// GET /public/files/*key?expires=...&disposition=...&signature=...
+/// No authentication: the signature proves the link was issued for this key,
+/// expiry and disposition. Files whose record is gone stop being served.
+pub async fn public_file_handler(
+    State(store): State<SharedFileStore>,
+    Extension(dbs): Extension<FileOwnerDbs>,
+    AxPath(key): AxPath<String>,
+    Query(query): Query<SignedFileQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !is_valid_key(&key) {
+        return invalid_key();
+    }
+    if !check_signed_url(&key, query.expires, query.disposition, &query.signature) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Invalid or expired link" })),
+        )
+            .into_response();
+    }
+
+    match file_owner(&dbs, &key).await {
+        Ok(Some(_)) => stream_file(&store, &key, &headers, query.disposition).await,
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))).into_response(),
+    }
+}
+
+/// Answer a download of `key`: `304` when `If-None-Match` matches, `206` for a
+/// satisfiable range, `416` for one past the end, otherwise the whole file
+pub async fn stream_file(
+    store: &SharedFileStore,
+    key: &str,
+    headers: &HeaderMap,
+    disposition: Option<Disposition>,
+) -> Response {
+    let meta = match store.stat(key).await {
+        Ok(Some(meta)) => meta,
+        Ok(None) => return not_found(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))).into_response(),
+    };
+
+    let mut response_headers = HeaderMap::new();
+    if let Some(etag) = meta.etag.as_deref().and_then(|e| HeaderValue::from_str(e).ok()) {
+        response_headers.insert(header::ETAG, etag);
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+
+    if etag_matches(headers, meta.etag.as_deref()) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+
+    let range = match requested_range(headers, meta.size) {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(range) => Some(range),
+        RangeRequest::Unsatisfiable => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", meta.size)) {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            return (StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response();
+        }
+    };
+
+    let object = match store.get(key, range).await {
+        Ok(Some(object)) => object,
+        Ok(None) => return not_found(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))).into_response(),
+    };
+
+    insert_content_headers(&mut response_headers, &object.meta);
+    if let Some(disposition) = disposition {
+        response_headers.insert(header::CONTENT_DISPOSITION, content_disposition(disposition, key));
+    }
+    let status = match range {
+        Some(range) => {
+            let content_range = format!("bytes {}-{}/{}", range.start, range.end, meta.size);
+            if let Ok(value) = HeaderValue::from_str(&content_range) {
+                response_headers.insert(header::CONTENT_RANGE, value);
+            }
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(range.end - range.start + 1));
+            StatusCode::PARTIAL_CONTENT
+        }
+        None => {
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(meta.size));
+            StatusCode::OK
+        }
+    };
+
+    (status, response_headers, Body::from_stream(object.body)).into_response()
+}
+
+/// Content type (recorded, or guessed from the key's extension), modification
+/// date, and headers that stop browsers second-guessing the type
+fn insert_content_headers(headers: &mut HeaderMap, meta: &ObjectMeta) {
+    let content_type = meta
+        .content_type
+        .clone()
+        .or_else(|| mime_guess::from_path(&meta.key).first_raw().map(str::to_string))
+        .and_then(|t| HeaderValue::from_str(&t).ok())
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    headers.insert(header::CONTENT_TYPE, content_type);
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+
+    if let Some(modified) = meta.last_modified {
+        let value = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+}
+
+/// `attachment` carries the name the file was uploaded under, without the
+/// unique prefix added when it was stored
+fn content_disposition(disposition: Disposition, key: &str) -> HeaderValue {
+    let name = key.rsplit('/').next().unwrap_or(key);
+    let name = name
+        .split_once('_')
+        .filter(|(id, _)| Uuid::parse_str(id).is_ok())
+        .map_or(name, |(_, original)| original);
+
+    match disposition {
+        Disposition::Inline => HeaderValue::from_static("inline"),
+        Disposition::Attachment => HeaderValue::from_str(&format!("attachment; filename=\"{}\"", name))
+            .unwrap_or(HeaderValue::from_static("attachment")),
+    }
+}
+
+/// `If-None-Match` names the current ETag (weak comparison) or is `*`
+fn etag_matches(headers: &HeaderMap, etag: Option<&str>) -> bool {
+    let Some(etag) = etag else { return false };
+    let Some(value) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+    value.trim() == "*" || value.split(',').any(|candidate| opaque(candidate) == opaque(etag))
+}
+
+#[derive(Debug, PartialEq)]
+enum RangeRequest {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// Read a `Range: bytes=...` header against a file of `size` bytes. Headers
+/// we don't understand, including multi-range requests, get the whole file.
+fn requested_range(headers: &HeaderMap, size: u64) -> RangeRequest {
+    let Some(value) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return RangeRequest::Full;
+    };
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    // `bytes=-N`: the last N bytes
+    if start.is_empty() {
+        return match end.parse::<u64>() {
+            Ok(0) => RangeRequest::Unsatisfiable,
+            Ok(_) if size == 0 => RangeRequest::Unsatisfiable,
+            Ok(suffix) => RangeRequest::Partial(ByteRange {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            }),
+            Err(_) => RangeRequest::Full,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return RangeRequest::Full;
+    };
+    let end = if end.is_empty() {
+        None
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => Some(end),
+            _ => return RangeRequest::Full,
+        }
+    };
+    if start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial(ByteRange {
+        start,
+        end: end.map_or(size - 1, |end| end.min(size - 1)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    fn range(value: &'static str, size: u64) -> RangeRequest {
+        requested_range(&with(header::RANGE, value), size)
+    }
+
+    fn partial(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+
+    #[test]
+    fn suffix_ranges_count_back_from_the_end() {
+        assert_eq!(range("bytes=-10", 100), partial(90, 99));
+        // Longer than the file: the whole file
+        assert_eq!(range("bytes=-500", 100), partial(0, 99));
+        assert_eq!(range("bytes=-0", 100), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn open_ended_ranges_run_to_the_end() {
+        assert_eq!(range("bytes=10-", 100), partial(10, 99));
+        assert_eq!(range("bytes=0-", 100), partial(0, 99));
+        assert_eq!(range("bytes=99-", 100), partial(99, 99));
+    }
+
+    #[test]
+    fn end_past_the_size_is_clamped() {
+        assert_eq!(range("bytes=0-9", 100), partial(0, 9));
+        assert_eq!(range("bytes=50-1000", 100), partial(50, 99));
+    }
+
+    #[test]
+    fn start_at_or_past_the_size_is_unsatisfiable() {
+        assert_eq!(range("bytes=100-", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=150-200", 100), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn empty_files_have_no_satisfiable_range() {
+        assert_eq!(range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=-5", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(requested_range(&HeaderMap::new(), 0), RangeRequest::Full);
+    }
+
+    #[test]
+    fn unsupported_ranges_fall_back_to_the_whole_file() {
+        assert_eq!(range("bytes=0-1,5-6", 100), RangeRequest::Full);
+        assert_eq!(range("items=0-1", 100), RangeRequest::Full);
+        assert_eq!(range("bytes=5-2", 100), RangeRequest::Full);
+        assert_eq!(range("bytes=a-b", 100), RangeRequest::Full);
+        assert_eq!(range("bytes=10", 100), RangeRequest::Full);
+        assert_eq!(requested_range(&HeaderMap::new(), 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let etag = Some("\"abc\"");
+        assert!(etag_matches(&with(header::IF_NONE_MATCH, "\"abc\""), etag));
+        assert!(etag_matches(&with(header::IF_NONE_MATCH, "W/\"abc\""), etag));
+        assert!(etag_matches(&with(header::IF_NONE_MATCH, "\"xyz\", W/\"abc\""), etag));
+        assert!(etag_matches(&with(header::IF_NONE_MATCH, "\"abc\""), Some("W/\"abc\"")));
+        assert!(!etag_matches(&with(header::IF_NONE_MATCH, "\"xyz\""), etag));
+        assert!(!etag_matches(&HeaderMap::new(), etag));
+    }
+
+    #[test]
+    fn if_none_match_star_matches_any_existing_etag() {
+        assert!(etag_matches(&with(header::IF_NONE_MATCH, "*"), Some("\"abc\"")));
+        // Nothing to compare against
+        assert!(!etag_matches(&with(header::IF_NONE_MATCH, "*"), None));
+    }
+}
@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Extension, Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::vehicle_controller::{owner_scope, vehicle_error_response},
+    db::{FuelLogDb, VehicleDb},
+    middlewares::auth_middleware::RequirePermission,
+    models::{
+        fuel_model::CreateFuelEntry,
+        permission_model::{VehicleReadAny, VehicleReadOwn, VehicleUpdateAny, VehicleUpdateOwn},
+    },
+    services::{
+        fuel_service::{add_fuel_entry, delete_fuel_entry, fuel_stats, list_fuel_entries},
+        vehicle_service::VehicleError,
+    },
+};
+
+fn not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Vehicle or fuel entry not found" })),
+    )
+}
+
+/// POST /vehicle/:id/fuel
+/// JSON body: odometer, volume, total_cost, optional date, distance_unit (km|mi),
+/// volume_unit (litres|gallons), full_tank (default true) and notes.
+pub async fn add_fuel_entry_handler(
+    State(db): State<FuelLogDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+    Json(payload): Json<CreateFuelEntry>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let entry = match payload.validate() {
+        Ok(entry) => entry,
+        Err(fields) => return vehicle_error_response(VehicleError::Invalid(fields)),
+    };
+
+    match add_fuel_entry(&db, &vehicles, &vehicle_id, owner, entry).await {
+        Ok(Some(entry)) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Fuel entry added", "fuel_entry": entry })),
+        ),
+        Ok(None) => not_found(),
+        Err(e) => vehicle_error_response(e),
+    }
+}
+
+/// GET /vehicle/:id/fuel
+pub async fn list_fuel_entries_handler(
+    State(db): State<FuelLogDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match list_fuel_entries(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(entries)) => (StatusCode::OK, Json(json!({ "fuel_entries": entries }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/fuel/stats
+/// L/100km, MPG (US), cost per km / mile and per-month totals.
+pub async fn fuel_stats_handler(
+    State(db): State<FuelLogDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match fuel_stats(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(stats)) => (StatusCode::OK, Json(json!({ "stats": stats }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// DELETE /vehicle/:id/fuel/:entry_id
+pub async fn delete_fuel_entry_handler(
+    State(db): State<FuelLogDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, entry_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match delete_fuel_entry(&db, &vehicles, &vehicle_id, &entry_id, owner).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Fuel entry deleted" }))),
+        Ok(false) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
@@ -0,0 +1,16 @@
+use axum::{
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::utils::jwt;
+
+/// GET /.well-known/jwks.json
+/// Public keys other services use to verify our access tokens.
+pub async fn jwks_handler() -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(jwt::jwks()),
+    )
+}
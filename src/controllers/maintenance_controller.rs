@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Extension, Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::vehicle_controller::{owner_scope, vehicle_error_response},
+    db::{MaintenanceDueDb, MaintenanceRuleDb, VehicleDb},
+    middlewares::auth_middleware::RequirePermission,
+    models::{
+        maintenance_model::{CreateMaintenanceRule, CreateMaintenanceTemplate},
+        permission_model::{
+            MaintenanceTemplateManage, VehicleReadAny, VehicleReadOwn, VehicleUpdateAny,
+            VehicleUpdateOwn,
+        },
+    },
+    services::{
+        maintenance_service::{
+            create_template, create_vehicle_rule, delete_template, delete_vehicle_rule,
+            due_for_vehicle, list_templates, list_vehicle_rules, reminders_for_user,
+        },
+        vehicle_service::VehicleError,
+    },
+};
+
+fn not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Vehicle or maintenance rule not found" })),
+    )
+}
+
+/// POST /vehicle/:id/maintenance/rules
+/// JSON body: task, category (service category that marks it done),
+/// interval_km and/or interval_months.
+pub async fn create_vehicle_rule_handler(
+    State(db): State<MaintenanceRuleDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+    Json(payload): Json<CreateMaintenanceRule>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    if let Err(fields) = payload.validate() {
+        return vehicle_error_response(VehicleError::Invalid(fields));
+    }
+
+    match create_vehicle_rule(&db, &vehicles, &vehicle_id, owner, payload).await {
+        Ok(Some(rule)) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Maintenance rule created", "rule": rule })),
+        ),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/maintenance/rules
+/// The vehicle's own rules plus the templates for its make/model.
+pub async fn list_vehicle_rules_handler(
+    State(db): State<MaintenanceRuleDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match list_vehicle_rules(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(rules)) => (StatusCode::OK, Json(json!({ "rules": rules }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// DELETE /vehicle/:id/maintenance/rules/:rule_id
+pub async fn delete_vehicle_rule_handler(
+    State(db): State<MaintenanceRuleDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, rule_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match delete_vehicle_rule(&db, &vehicles, &vehicle_id, &rule_id, owner).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Maintenance rule deleted" }))),
+        Ok(false) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/maintenance/due
+/// Tasks due soon or overdue, as of the last evaluation run.
+pub async fn vehicle_due_handler(
+    Extension(due): Extension<MaintenanceDueDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match due_for_vehicle(&due, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(items)) => (StatusCode::OK, Json(json!({ "due": items }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /me/reminders
+/// Due-soon and overdue tasks across all of the caller's vehicles.
+pub async fn my_reminders_handler(
+    Extension(due): Extension<MaintenanceDueDb>,
+    RequirePermission { user, .. }: RequirePermission<VehicleReadOwn>,
+) -> impl IntoResponse {
+    let user_id = match user.user_object_id() {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    };
+
+    match reminders_for_user(&due, user_id).await {
+        Ok(items) => (StatusCode::OK, Json(json!({ "reminders": items }))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /maintenance/templates
+/// JSON body: make, optional model, plus the rule fields.
+pub async fn create_template_handler(
+    State(db): State<MaintenanceRuleDb>,
+    _: RequirePermission<MaintenanceTemplateManage>,
+    Json(payload): Json<CreateMaintenanceTemplate>,
+) -> impl IntoResponse {
+    if let Err(fields) = payload.validate() {
+        return vehicle_error_response(VehicleError::Invalid(fields));
+    }
+
+    match create_template(&db, payload).await {
+        Ok(template) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Maintenance template created", "template": template })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /maintenance/templates
+pub async fn list_templates_handler(
+    State(db): State<MaintenanceRuleDb>,
+    _: RequirePermission<MaintenanceTemplateManage>,
+) -> impl IntoResponse {
+    match list_templates(&db).await {
+        Ok(templates) => (StatusCode::OK, Json(json!({ "templates": templates }))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// DELETE /maintenance/templates/:id
+pub async fn delete_template_handler(
+    State(db): State<MaintenanceRuleDb>,
+    _: RequirePermission<MaintenanceTemplateManage>,
+    AxPath(id): AxPath<String>,
+) -> impl IntoResponse {
+    match delete_template(&db, &id).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Maintenance template deleted" }))),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Maintenance template not found" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
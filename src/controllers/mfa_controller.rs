@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::user_controller::login_error_response,
+    db::{SessionDb, UserDb},
+    middlewares::auth_middleware::AuthUser,
+    models::mfa_model::{DisableMfaRequest, MfaLoginRequest, TotpCodeRequest},
+    services::{
+        mfa_service::{begin_totp_setup, confirm_totp_setup, disable_totp},
+        user_service::complete_mfa_login,
+    },
+};
+
+/// POST /login/2fa
+/// Second login step: exchanges the "mfa pending" token and a TOTP/recovery code for tokens.
+pub async fn mfa_login_handler(
+    State(db): State<UserDb>,
+    Extension(sessions): Extension<SessionDb>,
+    Json(payload): Json<MfaLoginRequest>,
+) -> Response {
+    match complete_mfa_login(&db, &sessions, &payload.mfa_token, &payload.code).await {
+        Ok(token_struct) => (StatusCode::OK, Json(json!({ "token": token_struct }))).into_response(),
+        Err(e) => login_error_response(e, StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Two-factor settings may only be changed from an interactive session, so a
+/// leaked API key can't lock its owner out of their account.
+fn require_session(auth: &AuthUser) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if auth.session_id.is_none() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "API keys cannot change two-factor authentication settings" })),
+        ));
+    }
+    Ok(())
+}
+
+/// POST /2fa/setup
+/// Returns a new secret and otpauth URI; 2FA stays off until confirmed.
+pub async fn totp_setup_handler(
+    State(db): State<UserDb>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match begin_totp_setup(&db, &auth.user_id).await {
+        Ok(setup) => (StatusCode::OK, Json(json!(setup))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /2fa/confirm
+pub async fn totp_confirm_handler(
+    State(db): State<UserDb>,
+    auth: AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match confirm_totp_setup(&db, &auth.user_id, &payload.code).await {
+        Ok(recovery_codes) => (
+            StatusCode::OK,
+            Json(json!({
+                "message": "Two-factor authentication enabled",
+                "recovery_codes": recovery_codes,
+            })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /2fa/disable
+pub async fn totp_disable_handler(
+    State(db): State<UserDb>,
+    auth: AuthUser,
+    Json(payload): Json<DisableMfaRequest>,
+) -> impl IntoResponse {
+    if let Err(rejection) = require_session(&auth) {
+        return rejection;
+    }
+
+    match disable_totp(&db, &auth.user_id, &payload.password, &payload.code).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Two-factor authentication disabled" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
@@ -1,2 +1,13 @@
+pub mod api_key_controller;
+pub mod document_controller;
+pub mod file_controller;
+pub mod fuel_controller;
+pub mod jwks_controller;
+pub mod maintenance_controller;
+pub mod mfa_controller;
+pub mod oidc_controller;
+pub mod role_controller;
+pub mod service_record_controller;
+pub mod transfer_controller;
 pub mod user_controller;
 pub mod vehicle_controller;
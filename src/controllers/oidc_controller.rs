@@ -0,0 +1,126 @@
+use axum::{
+    extract::{Extension, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_extra::{headers::Cookie, TypedHeader};
+use reqwest::Url;
+use serde_json::json;
+
+use crate::{
+    controllers::user_controller::login_error_response,
+    db::{OidcStateDb, SessionDb, UserDb},
+    models::oidc_model::OidcCallbackQuery,
+    services::{
+        oidc_service::{begin_oidc_login, complete_oidc_login, OidcConfig, PENDING_LOGIN_TTL_MINUTES},
+        user_service::LoginOutcome,
+    },
+};
+
+fn oidc_disabled() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Single sign-on is not configured" })),
+    )
+        .into_response()
+}
+
+/// Binds a pending login to the browser that started it
+const STATE_COOKIE: &str = "oidc_state";
+
+/// HttpOnly cookie scoped to the callback path. `SameSite=Lax` still sends it
+/// on the provider's top-level redirect back to us.
+fn state_cookie(config: &OidcConfig, value: &str, max_age: i64) -> String {
+    let callback = Url::parse(&config.redirect_uri).ok();
+    let path = callback.as_ref().map_or("/", |url| url.path());
+    let secure = if callback.as_ref().is_some_and(|url| url.scheme() == "https") {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        STATE_COOKIE, value, path, max_age, secure
+    )
+}
+
+/// GET /auth/oidc/login
+/// Redirects the browser to the identity provider.
+pub async fn oidc_login_handler(Extension(states): Extension<OidcStateDb>) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return oidc_disabled();
+    };
+
+    match begin_oidc_login(&config, &states).await {
+        Ok(redirect) => (
+            [(
+                header::SET_COOKIE,
+                state_cookie(&config, &redirect.binding, PENDING_LOGIN_TTL_MINUTES * 60),
+            )],
+            Redirect::to(&redirect.url),
+        )
+            .into_response(),
+        Err(e) => {
+            eprintln!("begin_oidc_login error: {}", e);
+            (StatusCode::BAD_GATEWAY, Json(json!({ "error": e }))).into_response()
+        }
+    }
+}
+
+/// GET /auth/oidc/callback
+/// Provider redirect target; responds exactly like POST /login.
+pub async fn oidc_callback_handler(
+    State(db): State<UserDb>,
+    Extension(states): Extension<OidcStateDb>,
+    Extension(sessions): Extension<SessionDb>,
+    cookies: Option<TypedHeader<Cookie>>,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return oidc_disabled();
+    };
+
+    let response = callback_response(&config, &db, &states, &sessions, cookies, query).await;
+    // The cookie is single-use whatever the outcome
+    let clear_cookie = [(header::SET_COOKIE, state_cookie(&config, "", 0))];
+    (clear_cookie, response).into_response()
+}
+
+async fn callback_response(
+    config: &OidcConfig,
+    db: &UserDb,
+    states: &OidcStateDb,
+    sessions: &SessionDb,
+    cookies: Option<TypedHeader<Cookie>>,
+    query: OidcCallbackQuery,
+) -> Response {
+    if let Some(error) = query.error {
+        let message = query.error_description.unwrap_or(error);
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": message }))).into_response();
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Missing code or state" })),
+        )
+            .into_response();
+    };
+
+    let binding = cookies.as_ref().and_then(|TypedHeader(c)| c.get(STATE_COOKIE));
+    match complete_oidc_login(config, db, states, sessions, &code, &state, binding).await {
+        Ok(LoginOutcome::Authenticated(token_struct)) => {
+            Json(json!({ "token": token_struct })).into_response()
+        }
+        Ok(LoginOutcome::MfaRequired { mfa_token, expires_in }) => Json(json!({
+            "mfa_required": true,
+            "mfa_token": mfa_token,
+            "expires_in": expires_in,
+        }))
+        .into_response(),
+        Err(e) => {
+            eprintln!("complete_oidc_login error: {}", e);
+            login_error_response(e, StatusCode::UNAUTHORIZED)
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use axum::{
+    extract::{Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    db::RoleDb,
+    middlewares::auth_middleware::RequirePermission,
+    models::{
+        permission_model::{RoleManage, ALL_PERMISSIONS, WILDCARD},
+        role_model::{CreateRole, UpdateRole},
+    },
+    services::role_service::{create_role, delete_role, list_roles, update_role_permissions},
+};
+
+/// GET /permissions
+pub async fn list_permissions_handler(_: RequirePermission<RoleManage>) -> impl IntoResponse {
+    let mut permissions = vec![WILDCARD];
+    permissions.extend_from_slice(ALL_PERMISSIONS);
+    Json(json!({ "permissions": permissions }))
+}
+
+/// GET /roles
+pub async fn list_roles_handler(
+    State(db): State<RoleDb>,
+    _: RequirePermission<RoleManage>,
+) -> impl IntoResponse {
+    match list_roles(&db).await {
+        Ok(roles) => (StatusCode::OK, Json(json!({ "roles": roles }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        ),
+    }
+}
+
+/// POST /roles
+pub async fn create_role_handler(
+    State(db): State<RoleDb>,
+    RequirePermission { permissions, .. }: RequirePermission<RoleManage>,
+    Json(payload): Json<CreateRole>,
+) -> impl IntoResponse {
+    match create_role(&db, &permissions, payload).await {
+        Ok(role) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Role created", "role": role })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// PUT /roles/:name
+pub async fn update_role_handler(
+    State(db): State<RoleDb>,
+    RequirePermission { permissions, .. }: RequirePermission<RoleManage>,
+    AxPath(name): AxPath<String>,
+    Json(payload): Json<UpdateRole>,
+) -> impl IntoResponse {
+    match update_role_permissions(&db, &permissions, &name, payload.permissions).await {
+        Ok(role) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Role updated", "role": role })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// DELETE /roles/:name
+pub async fn delete_role_handler(
+    State(db): State<RoleDb>,
+    _: RequirePermission<RoleManage>,
+    AxPath(name): AxPath<String>,
+) -> impl IntoResponse {
+    match delete_role(&db, &name).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "message": "Role deleted" }))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
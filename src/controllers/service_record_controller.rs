@@ -0,0 +1,259 @@
+use axum::{
+    extract::{Extension, Multipart, Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::vehicle_controller::{owner_scope, vehicle_error_response},
+    db::{ServiceRecordDb, VehicleDb},
+    middlewares::{
+        auth_middleware::RequirePermission,
+        upload_middleware::{save_upload, upload_error_response, UploadBudget, UploadKind},
+    },
+    models::{
+        permission_model::{VehicleReadAny, VehicleReadOwn, VehicleUpdateAny, VehicleUpdateOwn},
+        service_record_model::ServiceRecordForm,
+    },
+    services::{
+        service_record_service::{
+            create_service_record, delete_service_record, get_service_record, list_service_records,
+            service_cost_totals, update_service_record,
+        },
+        vehicle_service::VehicleError,
+    },
+    storage::{discard, SharedFileStore},
+};
+
+fn not_found() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Vehicle or service record not found" })),
+    )
+}
+
+/// Read the service record form: text fields plus any number of `receipts` files
+async fn read_service_form(
+    store: &SharedFileStore,
+    mut multipart: Multipart,
+) -> Result<(ServiceRecordForm, Vec<String>), (StatusCode, Json<serde_json::Value>)> {
+    let mut form = ServiceRecordForm::default();
+    let mut receipts: Vec<String> = vec![];
+    let mut budget = UploadBudget::from_env();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                discard_receipts(store, &receipts).await;
+                return Err(upload_error_response(e.into()));
+            }
+        };
+        let field_name = field.name().unwrap_or("").trim().to_lowercase();
+        match field_name.as_str() {
+            "receipts" | "receipts[]" | "receipt" => {
+                match save_upload(store, field, UploadKind::Receipt, &mut budget).await {
+                    Ok(key) => receipts.push(key),
+                    Err(e) => {
+                        discard_receipts(store, &receipts).await;
+                        return Err(upload_error_response(e));
+                    }
+                }
+            }
+            name => {
+                let slot = match name {
+                    "date" => &mut form.date,
+                    "odometer" => &mut form.odometer,
+                    "category" => &mut form.category,
+                    "cost" => &mut form.cost,
+                    "shop" => &mut form.shop,
+                    "notes" => &mut form.notes,
+                    _ => continue,
+                };
+                if let Ok(text) = field.text().await {
+                    *slot = Some(text);
+                }
+            }
+        }
+    }
+
+    Ok((form, receipts))
+}
+
+async fn discard_receipts(store: &SharedFileStore, receipts: &[String]) {
+    for key in receipts {
+        discard(store, key).await;
+    }
+}
+
+/// POST /vehicle/:id/services
+/// Accepts multipart/form-data:
+/// - date (YYYY-MM-DD or RFC 3339)
+/// - odometer (whole number)
+/// - category (oil_change | tires | brakes | inspection | repair | other)
+/// - cost (number)
+/// - shop, notes (text, optional)
+/// - receipts[] (file(s), optional)
+pub async fn create_service_record_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let (form, receipts) = match read_service_form(&store, multipart).await {
+        Ok(parsed) => parsed,
+        Err(rejection) => return rejection,
+    };
+    let payload = match form.into_create() {
+        Ok(payload) => payload,
+        Err(fields) => {
+            discard_receipts(&store, &receipts).await;
+            return vehicle_error_response(VehicleError::Invalid(fields));
+        }
+    };
+
+    match create_service_record(&db, &vehicles, &vehicle_id, owner, payload, receipts.clone()).await {
+        Ok(Some(record)) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Service record created", "service_record": record })),
+        ),
+        Ok(None) => {
+            discard_receipts(&store, &receipts).await;
+            not_found()
+        }
+        Err(e) => {
+            discard_receipts(&store, &receipts).await;
+            (StatusCode::BAD_REQUEST, Json(json!({ "error": e })))
+        }
+    }
+}
+
+/// GET /vehicle/:id/services
+pub async fn list_service_records_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match list_service_records(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(records)) => (StatusCode::OK, Json(json!({ "service_records": records }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/services/totals
+pub async fn service_cost_totals_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match service_cost_totals(&db, &vehicles, &vehicle_id, owner).await {
+        Ok(Some(totals)) => (StatusCode::OK, Json(json!({ "totals": totals }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicle/:id/services/:record_id
+pub async fn get_service_record_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath((vehicle_id, record_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match get_service_record(&db, &vehicles, &vehicle_id, &record_id, owner).await {
+        Ok(Some(record)) => (StatusCode::OK, Json(json!({ "service_record": record }))),
+        Ok(None) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// PUT /vehicle/:id/services/:record_id
+/// Same fields as create, all optional; uploaded receipts are added to the existing ones.
+pub async fn update_service_record_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, record_id)): AxPath<(String, String)>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let (form, receipts) = match read_service_form(&store, multipart).await {
+        Ok(parsed) => parsed,
+        Err(rejection) => return rejection,
+    };
+    let payload = match form.into_update() {
+        Ok(payload) => payload,
+        Err(fields) => {
+            discard_receipts(&store, &receipts).await;
+            return vehicle_error_response(VehicleError::Invalid(fields));
+        }
+    };
+
+    match update_service_record(&db, &vehicles, &vehicle_id, &record_id, owner, payload, receipts.clone())
+        .await
+    {
+        Ok(Some(record)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Service record updated", "service_record": record })),
+        ),
+        Ok(None) => {
+            discard_receipts(&store, &receipts).await;
+            not_found()
+        }
+        Err(e) => {
+            discard_receipts(&store, &receipts).await;
+            (StatusCode::BAD_REQUEST, Json(json!({ "error": e })))
+        }
+    }
+}
+
+/// DELETE /vehicle/:id/services/:record_id
+pub async fn delete_service_record_handler(
+    State(db): State<ServiceRecordDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath((vehicle_id, record_id)): AxPath<(String, String)>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match delete_service_record(&db, &vehicles, &store, &vehicle_id, &record_id, owner).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Service record deleted" }))),
+        Ok(false) => not_found(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
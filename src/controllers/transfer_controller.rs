@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Extension, Path as AxPath, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    controllers::vehicle_controller::{owner_scope, vehicle_error_response},
+    db::{UserDb, VehicleDb, VehicleTransferDb},
+    mailer::SharedMailer,
+    middlewares::auth_middleware::RequirePermission,
+    models::{
+        permission_model::{VehicleCreateOwn, VehicleReadOwn, VehicleUpdateAny, VehicleUpdateOwn},
+        transfer_model::{CreateTransfer, TransferSummary},
+    },
+    services::transfer_service::{
+        accept_transfer, cancel_transfer, initiate_transfer, pending_transfers, reject_transfer,
+    },
+};
+
+fn no_pending_transfer() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "No pending transfer found" })),
+    )
+}
+
+/// POST /vehicle/:id/transfer
+/// JSON body: email of the account to hand the vehicle to.
+pub async fn initiate_transfer_handler(
+    State(db): State<VehicleTransferDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    Extension(users): Extension<UserDb>,
+    Extension(mailer): Extension<SharedMailer>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(vehicle_id): AxPath<String>,
+    Json(payload): Json<CreateTransfer>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match initiate_transfer(&db, &vehicles, &users, &mailer, &vehicle_id, owner, &payload.email).await {
+        Ok(Some(transfer)) => (
+            StatusCode::CREATED,
+            Json(json!({ "message": "Transfer started", "transfer": TransferSummary::from(transfer) })),
+        ),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({ "error": "Vehicle not found" }))),
+        Err(e) => vehicle_error_response(e),
+    }
+}
+
+/// GET /transfers
+/// Pending transfers the caller has received (`incoming`) or started (`outgoing`).
+pub async fn list_transfers_handler(
+    State(db): State<VehicleTransferDb>,
+    RequirePermission { user, .. }: RequirePermission<VehicleReadOwn>,
+) -> impl IntoResponse {
+    let user_id = match user.user_object_id() {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    };
+
+    match pending_transfers(&db, user_id).await {
+        Ok(inbox) => (StatusCode::OK, Json(json!({ "transfers": inbox }))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /transfers/:id/accept
+pub async fn accept_transfer_handler(
+    State(db): State<VehicleTransferDb>,
+    Extension(vehicles): Extension<VehicleDb>,
+    RequirePermission { user, .. }: RequirePermission<VehicleCreateOwn>,
+    AxPath(transfer_id): AxPath<String>,
+) -> impl IntoResponse {
+    let user_id = match user.user_object_id() {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    };
+
+    match accept_transfer(&db, &vehicles, &transfer_id, user_id).await {
+        Ok(Some(vehicle)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Transfer accepted", "vehicle": vehicle })),
+        ),
+        Ok(None) => no_pending_transfer(),
+        Err(e) => (StatusCode::CONFLICT, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /transfers/:id/reject
+pub async fn reject_transfer_handler(
+    State(db): State<VehicleTransferDb>,
+    RequirePermission { user, .. }: RequirePermission<VehicleCreateOwn>,
+    AxPath(transfer_id): AxPath<String>,
+) -> impl IntoResponse {
+    let user_id = match user.user_object_id() {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    };
+
+    match reject_transfer(&db, &transfer_id, user_id).await {
+        Ok(Some(transfer)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Transfer rejected", "transfer": TransferSummary::from(transfer) })),
+        ),
+        Ok(None) => no_pending_transfer(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /transfers/:id/cancel
+pub async fn cancel_transfer_handler(
+    State(db): State<VehicleTransferDb>,
+    RequirePermission { user, .. }: RequirePermission<VehicleUpdateOwn>,
+    AxPath(transfer_id): AxPath<String>,
+) -> impl IntoResponse {
+    let user_id = match user.user_object_id() {
+        Ok(id) => id,
+        Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    };
+
+    match cancel_transfer(&db, &transfer_id, user_id).await {
+        Ok(Some(transfer)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Transfer cancelled", "transfer": TransferSummary::from(transfer) })),
+        ),
+        Ok(None) => no_pending_transfer(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
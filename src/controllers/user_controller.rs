@@ -1,20 +1,36 @@
-// use std::path::Path;
-use uuid::Uuid;
-
 use axum::{
-    extract::{Multipart, Path as AxPath, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Extension, Multipart, Path as AxPath, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
     Json,
 };
 use serde_json::json;
-use tokio::io::AsyncWriteExt;
 
 use crate::{
-    db::UserDb,
-    middlewares::auth_middleware::{AuthUser,require_role},
-    models::user_model::{LoginUser, RegisterUser, UserRole},
-    services::user_service::{login_user, register_user, update_user},
+    db::{RoleAuditDb, RoleDb, SessionDb, UserDb, UserTokenDb},
+    mailer::SharedMailer,
+    middlewares::{
+        auth_middleware::{AuthUser, RequirePermission},
+        upload_middleware::{save_upload, upload_error_response, UploadBudget, UploadKind},
+    },
+    models::{
+        permission_model::{Permission, UserRoleAssign, UserUpdateAny, UserUpdateOwn},
+        role_model::AssignRoleRequest,
+        session_model::{LogoutQuery, RefreshRequest},
+        user_model::{LoginUser, RegisterUser},
+        user_token_model::{
+            ForgotPasswordRequest, ResendVerificationRequest, ResetPasswordRequest,
+            VerifyEmailQuery,
+        },
+    },
+    services::{
+        session_service::{revoke_session, revoke_user_sessions},
+        user_service::{
+            assign_role, login_user, refresh_tokens, AccountError, LoginError, LoginOutcome, register_user, request_password_reset,
+            resend_verification_email, reset_password, update_user, verify_email, RoleActor,
+        },
+    },
+    storage::{discard, SharedFileStore},
 };
 
 /// POST /register
@@ -25,72 +41,60 @@ use crate::{
 ///  - profileImage (file, optional)
 pub async fn register_handler(
     State(db): State<UserDb>,
+    Extension(tokens): Extension<UserTokenDb>,
+    Extension(mailer): Extension<SharedMailer>,
+    Extension(store): Extension<SharedFileStore>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
     let mut name = String::new();
     let mut email = String::new();
     let mut password = String::new();
-    let mut role: Option<UserRole> = None;
     let mut profile_image_path: Option<String> = None;
+    let mut budget = UploadBudget::from_env();
 
-    println!("Parsing multipart form...");
-
-    while let Ok(Some(field)) = multipart.next_field().await {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                discard_profile_image(&store, profile_image_path).await;
+                return upload_error_response(e.into());
+            }
+        };
         let field_name_raw = field.name().unwrap_or("unknown").to_string();
         let field_name = field_name_raw.trim().to_lowercase();
 
-        println!("Received field: '{}'", field_name);
-
         match field_name.as_str() {
             "name" => {
                 if let Ok(text) = field.text().await {
                     name = text.trim().to_string();
-                    println!("Parsed name: {}", name);
                 }
             }
             "email" => {
                 if let Ok(text) = field.text().await {
                     email = text.trim().to_string();
-                    println!("Parsed email: {}", email);
                 }
             }
             "password" => {
                 if let Ok(text) = field.text().await {
                     password = text.trim().to_string();
-                    println!("Parsed password: {}", password);
-                }
-            }
-            "role" => {
-                if let Ok(text) = field.text().await {
-                    let parsed = match text.to_lowercase().as_str() {
-                        "admin" => Some(UserRole::Admin),
-                        "user" => Some(UserRole::User),
-                        _ => None,
-                    };
-                    role = parsed.or(Some(UserRole::User));
                 }
             }
             "profile_image" => {
-                let upload_dir = "./uploads";
-                tokio::fs::create_dir_all(upload_dir).await.unwrap();
-
-                let file_name = field.file_name().unwrap_or("file").to_string();
-                let file_path = format!("{}/{}_{}", upload_dir, Uuid::new_v4(), file_name);
-
-                let bytes = field.bytes().await.unwrap();
-                let mut file = tokio::fs::File::create(&file_path).await.unwrap();
-                file.write_all(&bytes).await.unwrap();
-
-                profile_image_path = Some(file_path.clone());
-                println!(" Saved image to {}", file_path);
+                match save_upload(&store, field, UploadKind::ProfileImage, &mut budget).await {
+                    Ok(key) => discard_profile_image(&store, profile_image_path.replace(key)).await,
+                    Err(e) => {
+                        discard_profile_image(&store, profile_image_path).await;
+                        return upload_error_response(e);
+                    }
+                }
             }
-            _ => println!("Ignoring unknown field: {}", field_name),
+            _ => {}
         }
     }
 
-    println!(" name={} email={} password={}", name, email, password);
-
     if name.is_empty() || email.is_empty() || password.is_empty() {
+        discard_profile_image(&store, profile_image_path).await;
         return (
             StatusCode::BAD_REQUEST,
             Json(json!({ "error": "Missing required fields: name/email/password" })),
@@ -101,61 +105,259 @@ pub async fn register_handler(
         name,
         email,
         password,
-        role,
     };
-    match register_user(&db, payload, profile_image_path).await {
+    match register_user(&db, &tokens, &mailer, payload, profile_image_path.clone()).await {
         Ok(user) => (
             StatusCode::CREATED,
-            Json(json!({ "message": "User created", "user": user })),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": e })),
+            Json(json!({
+                "message": "User created, check your inbox to verify your email",
+                "user": {
+                    "id": user.id.map(|i| i.to_hex()),
+                    "name": user.name,
+                    "email": user.email,
+                    "role": user.role,
+                    "profile_image": user.profile_image,
+                    "email_verified": user.email_verified,
+                }
+            })),
         ),
+        Err(e) => {
+            discard_profile_image(&store, profile_image_path).await;
+            (account_error_status(&e), Json(json!({ "error": e.to_string() })))
+        }
+    }
+}
+
+/// Bad input is the client's fault (400), a taken address is a conflict (409)
+fn account_error_status(err: &AccountError) -> StatusCode {
+    match err {
+        AccountError::InvalidEmail => StatusCode::BAD_REQUEST,
+        AccountError::EmailTaken => StatusCode::CONFLICT,
+        AccountError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn discard_profile_image(store: &SharedFileStore, key: Option<String>) {
+    if let Some(key) = key {
+        discard(store, &key).await;
     }
 }
 
 /// POST /login
 pub async fn login_handler(
     State(db): State<UserDb>,
+    Extension(sessions): Extension<SessionDb>,
     Json(payload): Json<LoginUser>,
-) -> Json<serde_json::Value> {
-    match login_user(&db, payload).await {
-        Ok(token_struct) => Json(json!({ "token": token_struct })),
+) -> Response {
+    match login_user(&db, &sessions, payload).await {
+        Ok(LoginOutcome::Authenticated(token_struct)) => {
+            Json(json!({ "token": token_struct })).into_response()
+        }
+        Ok(LoginOutcome::MfaRequired { mfa_token, expires_in }) => Json(json!({
+            "mfa_required": true,
+            "mfa_token": mfa_token,
+            "expires_in": expires_in,
+        }))
+        .into_response(),
         Err(e) => {
             eprintln!("login_user error: {}", e);
-            Json(json!({ "error": e }))
+            login_error_response(e, StatusCode::OK)
         }
     }
 }
 
-/// PUT /user/:id
+/// Locked accounts get `423 Locked` with a `Retry-After` header and a stable
+/// `code` clients can match on; other failures use `failure_status`.
+pub fn login_error_response(err: LoginError, failure_status: StatusCode) -> Response {
+    match err {
+        LoginError::Locked { retry_after } => (
+            StatusCode::LOCKED,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(json!({
+                "error": err.to_string(),
+                "code": "account_locked",
+                "retry_after": retry_after,
+            })),
+        )
+            .into_response(),
+        LoginError::Failed(message) => {
+            (failure_status, Json(json!({ "error": message }))).into_response()
+        }
+    }
+}
+
+/// POST /token/refresh
+/// Exchanges a refresh token for a new access token + rotated refresh token.
+pub async fn refresh_token_handler(
+    State(db): State<UserDb>,
+    Extension(sessions): Extension<SessionDb>,
+    Json(payload): Json<RefreshRequest>,
+) -> impl IntoResponse {
+    match refresh_tokens(&db, &sessions, &payload.refresh_token).await {
+        Ok(tokens) => (StatusCode::OK, Json(json!({ "token": tokens }))),
+        Err(e) => (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /logout?all=true
+/// Revokes the session behind the presented access token,
+/// or every session of the user when `all` is set.
+pub async fn logout_handler(
+    Extension(sessions): Extension<SessionDb>,
+    auth: AuthUser,
+    Query(query): Query<LogoutQuery>,
+) -> impl IntoResponse {
+    let Some(session_id) = &auth.session_id else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "API key requests have no session to log out" })),
+        );
+    };
+
+    let result = if query.all {
+        match auth.user_object_id() {
+            Ok(user_id) => revoke_user_sessions(&sessions, user_id).await,
+            Err(e) => Err(e),
+        }
+    } else {
+        revoke_session(&sessions, session_id).await
+    };
+
+    match result {
+        Ok(()) => (StatusCode::OK, Json(json!({ "message": "Logged out" }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        ),
+    }
+}
+
+/// POST /forgot-password
+/// Always answers 200 so callers can't probe which emails are registered.
+pub async fn forgot_password_handler(
+    State(db): State<UserDb>,
+    Extension(tokens): Extension<UserTokenDb>,
+    Extension(mailer): Extension<SharedMailer>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = request_password_reset(&db, &tokens, &mailer, payload.email.trim()).await {
+        eprintln!("request_password_reset error: {}", e);
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({ "message": "If the email is registered, a reset link has been sent" })),
+    )
+}
+
+/// POST /reset-password
+pub async fn reset_password_handler(
+    State(db): State<UserDb>,
+    Extension(tokens): Extension<UserTokenDb>,
+    Extension(sessions): Extension<SessionDb>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> impl IntoResponse {
+    match reset_password(&db, &tokens, &sessions, &payload.token, &payload.new_password).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Password has been reset" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// Page behind the emailed reset link: reads `token` from the URL and posts
+/// it with the new password to `POST /reset-password`
+const RESET_PASSWORD_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Reset your password</title></head>
+<body>
+<form id="reset">
+  <label>New password <input type="password" name="new_password" minlength="8" required></label>
+  <button type="submit">Reset password</button>
+</form>
+<p id="result"></p>
+<script>
+document.getElementById("reset").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const token = new URLSearchParams(window.location.search).get("token") || "";
+  const response = await fetch(window.location.pathname, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ token, new_password: event.target.new_password.value }),
+  });
+  const body = await response.json();
+  document.getElementById("result").textContent = body.message || body.error;
+});
+</script>
+</body>
+</html>
+"#;
+
+/// GET /reset-password?token=
+/// Where the reset email links to; the form on it calls `POST /reset-password`
+pub async fn reset_password_page_handler() -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "no-store"), (header::REFERRER_POLICY, "no-referrer")],
+        Html(RESET_PASSWORD_PAGE),
+    )
+}
+
+/// GET /verify-email?token=
+pub async fn verify_email_handler(
+    State(db): State<UserDb>,
+    Extension(tokens): Extension<UserTokenDb>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> impl IntoResponse {
+    match verify_email(&db, &tokens, &query.token).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Email verified" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /verify-email/resend
+pub async fn resend_verification_handler(
+    State(db): State<UserDb>,
+    Extension(tokens): Extension<UserTokenDb>,
+    Extension(mailer): Extension<SharedMailer>,
+    Json(payload): Json<ResendVerificationRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = resend_verification_email(&db, &tokens, &mailer, payload.email.trim()).await {
+        eprintln!("resend_verification_email error: {}", e);
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({ "message": "If the account needs verification, a new link has been sent" })),
+    )
+}
+
 /// PUT /user/:id
 pub async fn update_user_handler(
     State(db): State<UserDb>,
-    AuthUser { user_id, role }: AuthUser,
+    Extension(tokens): Extension<UserTokenDb>,
+    Extension(mailer): Extension<SharedMailer>,
+    RequirePermission { user: auth, permissions, .. }: RequirePermission<UserUpdateOwn>,
     AxPath(id): AxPath<String>,
     Json(payload): Json<RegisterUser>,
-) -> Json<serde_json::Value> {
-    // Rule: Admins/SuperAdmins can update anyone
-    // Regular users can only update their own profile
-    if user_id != id {
-        if let Err(err) = require_role(
-            &AuthUser {
-                user_id: user_id.clone(),
-                role: role.clone(),
-            },
-            &[UserRole::Admin],
-        ) {
-            return Json(json!({
+) -> (StatusCode, Json<serde_json::Value>) {
+    // Rule: user:update:any can update anyone
+    // Everyone else can only update their own profile
+    if auth.user_id != id && !permissions.allows(UserUpdateAny::NAME) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
                 "success": false,
-                "message": err.1
-            }));
-        }
+                "message": format!("Forbidden: missing permission {}", UserUpdateAny::NAME)
+            })),
+        );
     }
 
-    match update_user(&db, &id, payload, &user_id).await {
-        Ok(user) => Json(json!({
+    match update_user(&db, &tokens, &mailer, &id, payload).await {
+        Ok(user) => (StatusCode::OK, Json(json!({
             "success": true,
             "message": "User updated successfully",
             "data": {
@@ -165,20 +367,48 @@ pub async fn update_user_handler(
                 "role": user.role,
                 "profile_image": user.profile_image,
             }
-        })),
+        }))),
         Err(err) => {
             eprintln!("update_user error: {}", err);
-            Json(json!({
-                "success": false,
-                "message": err
-            }))
+            (
+                account_error_status(&err),
+                Json(json!({
+                    "success": false,
+                    "message": err.to_string()
+                })),
+            )
         }
     }
 }
 
-/// sanitizer to avoid problematic characters in filenames
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_' || *c == '-')
-        .collect::<String>()
+/// POST /users/:id/role
+/// Promote or demote a user. Requires `user:role:assign`; every change is audited.
+pub async fn assign_role_handler(
+    State(db): State<UserDb>,
+    Extension(roles): Extension<RoleDb>,
+    Extension(audit): Extension<RoleAuditDb>,
+    Extension(sessions): Extension<SessionDb>,
+    RequirePermission { user: actor, permissions, .. }: RequirePermission<UserRoleAssign>,
+    AxPath(id): AxPath<String>,
+    Json(payload): Json<AssignRoleRequest>,
+) -> impl IntoResponse {
+    let actor = RoleActor {
+        user_id: &actor.user_id,
+        permissions: &permissions,
+    };
+    match assign_role(&db, &roles, &audit, &sessions, actor, &id, payload.role.trim()).await {
+        Ok(user) => (
+            StatusCode::OK,
+            Json(json!({
+                "message": "Role updated",
+                "user": {
+                    "id": user.id.map(|i| i.to_hex()),
+                    "name": user.name,
+                    "email": user.email,
+                    "role": user.role,
+                }
+            })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
 }
@@ -1,44 +1,65 @@
 use axum::{
-    extract::{Multipart, Path as AxPath, State},
+    extract::{Extension, Multipart, Path as AxPath, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
+use mongodb::bson::oid::ObjectId;
 use serde_json::json;
-use tokio::io::AsyncWriteExt;
-use uuid::Uuid;
 
 use crate::{
     db::VehicleDb,
-    middlewares::auth_middleware::{require_role, AuthUser},
-    models::{user_model::UserRole, vehicle_model::CreateVehicle},
-    services::vehicle_service::{create_vehicle, update_vehicle},
+    middlewares::{
+        auth_middleware::{AuthUser, RequirePermission},
+        upload_middleware::{save_upload, upload_error_response, UploadBudget, UploadKind},
+    },
+    models::{
+        permission_model::{
+            Permission, PermissionSet, VehicleCreateOwn, VehicleDeleteAny, VehicleDeleteOwn, VehicleReadAny,
+            VehicleReadOwn, VehicleRestore, VehicleUpdateAny, VehicleUpdateOwn,
+        },
+        vehicle_model::{CreateVehicle, UpdateVehicle, VehicleListQuery},
+    },
+    services::vehicle_service::{
+        create_vehicle, delete_vehicle, get_vehicle, list_vehicles, restore_vehicle, update_vehicle,
+        VehicleError,
+    },
+    storage::{discard, SharedFileStore},
+    utils::{validation::FieldError, vin},
 };
 
 /// POST /vehicles
 /// Accepts multipart/form-data:
 /// - make (text)
 /// - model (text)
-/// - year (text)
+/// - year (number)
+/// - vin (text, optional; fills in make/year when those are left blank)
 /// - files[] (file(s), optional)
 pub async fn create_vehicle_handler(
     State(db): State<VehicleDb>,
-    AuthUser { user_id, .. }: AuthUser,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, .. }: RequirePermission<VehicleCreateOwn>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
     let mut make = String::new();
     let mut model = String::new();
     let mut year = String::new();
+    let mut vin = String::new();
     let mut file_paths: Vec<String> = vec![];
+    let mut budget = UploadBudget::from_env();
 
-    println!("Parsing vehicle multipart form...");
-
-    while let Ok(Some( field)) = multipart.next_field().await {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                discard_uploads(&store, &file_paths).await;
+                return upload_error_response(e.into());
+            }
+        };
         let field_name_raw = field.name().unwrap_or("unknown").to_string();
         let field_name = field_name_raw.trim().to_lowercase();
 
-        println!("Received field: '{}'", field_name);
-
         match field_name.as_str() {
             "make" => {
                 if let Ok(text) = field.text().await {
@@ -55,122 +76,303 @@ pub async fn create_vehicle_handler(
                     year = text.trim().to_string();
                 }
             }
+            "vin" => {
+                if let Ok(text) = field.text().await {
+                    vin = text.trim().to_string();
+                }
+            }
             "files" | "files[]" | "file" => {
-                let upload_dir = "./uploads/vehicles";
-                tokio::fs::create_dir_all(upload_dir).await.unwrap();
-
-                let original_name = field.file_name().unwrap_or("file").to_string();
-                let safe_name = sanitize_filename(&original_name);
-                let file_path = format!("{}/{}_{}", upload_dir, Uuid::new_v4(), safe_name);
-
-                let bytes = field.bytes().await.unwrap();
-                let mut file = tokio::fs::File::create(&file_path).await.unwrap();
-                file.write_all(&bytes).await.unwrap();
-
-                println!("Uploaded vehicle file: {}", file_path);
-                file_paths.push(file_path);
+                match save_upload(&store, field, UploadKind::VehiclePhoto, &mut budget).await {
+                    Ok(key) => file_paths.push(key),
+                    Err(e) => {
+                        discard_uploads(&store, &file_paths).await;
+                        return upload_error_response(e);
+                    }
+                }
             }
-            _ => println!("Ignoring unknown field: {}", field_name),
+            _ => {}
         }
     }
 
-    if make.is_empty() || model.is_empty() || year.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({ "error": "Missing required fields: make/model/year" })),
-        );
-    }
+    let year = match parse_year(&year) {
+        Ok(year) => year,
+        Err(e) => {
+            discard_uploads(&store, &file_paths).await;
+            return vehicle_error_response(VehicleError::Invalid(vec![e]));
+        }
+    };
 
-    let payload = CreateVehicle { make, model, year };
+    let payload = CreateVehicle { make, model, year, vin: Some(vin) };
 
-    match create_vehicle(&db, user_id, payload, Some(file_paths)).await {
+    match create_vehicle(&db, user.user_id, payload, Some(file_paths.clone())).await {
         Ok(vehicle) => (
             StatusCode::CREATED,
             Json(json!({ "message": "Vehicle created", "vehicle": vehicle })),
         ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": e })),
-        ),
+        Err(e) => {
+            discard_uploads(&store, &file_paths).await;
+            vehicle_error_response(e)
+        }
     }
 }
 
 /// PUT /vehicles/:id
-/// Only Admin can update vehicle records.
+/// Owners can update their own vehicles; `vehicle:update:any` allows updating any.
 pub async fn update_vehicle_handler(
     State(db): State<VehicleDb>,
-    AuthUser { role, .. }: AuthUser,
+    Extension(store): Extension<SharedFileStore>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleUpdateOwn>,
     AxPath(id): AxPath<String>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
-    // Require Admin role
-    if let Err((code, msg)) = require_role(
-        &AuthUser {
-            user_id: "".to_string(),
-            role: role.clone(),
-        },
-        &[UserRole::Admin],
-    ) {
-        return (code, Json(json!({ "error": msg })));
-    }
+    let owner = match owner_scope::<VehicleUpdateAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
 
-    let mut make = String::new();
-    let mut model = String::new();
-    let mut year = String::new();
+    let mut payload = UpdateVehicle::default();
+    let mut year: Option<String> = None;
     let mut file_paths: Vec<String> = vec![];
+    let mut budget = UploadBudget::from_env();
 
-    while let Ok(Some( field)) = multipart.next_field().await {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                discard_uploads(&store, &file_paths).await;
+                return upload_error_response(e.into());
+            }
+        };
         let field_name = field.name().unwrap_or("").to_lowercase();
         match field_name.as_str() {
             "make" => {
                 if let Ok(text) = field.text().await {
-                    make = text;
+                    payload.make = Some(text);
                 }
             }
             "model" => {
                 if let Ok(text) = field.text().await {
-                    model = text;
+                    payload.model = Some(text);
                 }
             }
             "year" => {
                 if let Ok(text) = field.text().await {
-                    year = text;
+                    year = Some(text);
+                }
+            }
+            "vin" => {
+                if let Ok(text) = field.text().await {
+                    payload.vin = Some(text);
                 }
             }
             "files" | "files[]" | "file" => {
-                let upload_dir = "./uploads/vehicles";
-                tokio::fs::create_dir_all(upload_dir).await.unwrap();
+                match save_upload(&store, field, UploadKind::VehiclePhoto, &mut budget).await {
+                    Ok(key) => file_paths.push(key),
+                    Err(e) => {
+                        discard_uploads(&store, &file_paths).await;
+                        return upload_error_response(e);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(text) = year {
+        let error = match parse_year(&text) {
+            Ok(Some(year)) => {
+                payload.year = Some(year);
+                None
+            }
+            Ok(None) => Some(FieldError {
+                field: "year".to_string(),
+                message: "must not be blank".to_string(),
+            }),
+            Err(e) => Some(e),
+        };
+        if let Some(e) = error {
+            discard_uploads(&store, &file_paths).await;
+            return vehicle_error_response(VehicleError::Invalid(vec![e]));
+        }
+    }
+
+    // Only replace the stored files when new ones were uploaded
+    let new_files = (!file_paths.is_empty()).then(|| file_paths.clone());
+
+    match update_vehicle(&db, &store, &id, owner, payload, new_files).await {
+        Ok(Some(vehicle)) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Vehicle updated successfully", "vehicle": vehicle })),
+        ),
+        Ok(None) => {
+            discard_uploads(&store, &file_paths).await;
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": "Vehicle not found" })),
+            )
+        }
+        Err(e) => {
+            discard_uploads(&store, &file_paths).await;
+            vehicle_error_response(e)
+        }
+    }
+}
 
-                let safe_name = sanitize_filename(field.file_name().unwrap_or("file"));
-                let file_path = format!("{}/{}_{}", upload_dir, Uuid::new_v4(), safe_name);
+/// Validation failures are `422` with one entry per field; anything else is a `400`
+pub fn vehicle_error_response(err: VehicleError) -> (StatusCode, Json<serde_json::Value>) {
+    match err {
+        VehicleError::Invalid(ref fields) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": err.to_string(), "fields": fields })),
+        ),
+        VehicleError::Failed(message) => (StatusCode::BAD_REQUEST, Json(json!({ "error": message }))),
+    }
+}
 
-                let bytes = field.bytes().await.unwrap();
-                let mut file = tokio::fs::File::create(&file_path).await.unwrap();
-                file.write_all(&bytes).await.unwrap();
+/// Multipart fields arrive as text; blank means "not given"
+fn parse_year(text: &str) -> Result<Option<i32>, FieldError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    text.parse::<i32>().map(Some).map_err(|_| FieldError {
+        field: "year".to_string(),
+        message: "must be a whole number".to_string(),
+    })
+}
 
-                file_paths.push(file_path);
+/// GET /vehicle/:id
+/// Callers without `vehicle:read:any` only see their own vehicles; anything else is a 404.
+pub async fn get_vehicle_handler(
+    State(db): State<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    AxPath(id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleReadAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+
+    match get_vehicle(&db, &id, owner).await {
+        Ok(Some(vehicle)) => (StatusCode::OK, Json(json!({ "vehicle": vehicle }))),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Vehicle not found" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vehicles
+/// Query: make, model, year_min, year_max, owner, sort, cursor, limit, include_total.
+/// Without `vehicle:read:any` the listing is always limited to the caller's vehicles.
+pub async fn list_vehicles_handler(
+    State(db): State<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleReadOwn>,
+    Query(query): Query<VehicleListQuery>,
+) -> impl IntoResponse {
+    let owner = if permissions.allows(VehicleReadAny::NAME) {
+        match query.owner.as_deref().map(ObjectId::parse_str).transpose() {
+            Ok(owner) => owner,
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "Invalid owner ID" })),
+                )
             }
-            _ => (),
         }
+    } else {
+        if query.owner.as_ref().is_some_and(|owner| *owner != user.user_id) {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "Forbidden: cannot list other users' vehicles" })),
+            );
+        }
+        match user.user_object_id() {
+            Ok(owner) => Some(owner),
+            Err(e) => return (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))),
+        }
+    };
+
+    match list_vehicles(&db, owner, query).await {
+        Ok(page) => (StatusCode::OK, Json(json!(page))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
     }
+}
 
-    let payload = CreateVehicle { make, model, year };
+/// DELETE /vehicle/:id
+/// Soft-delete; the record and its files are purged after the retention window.
+/// Callers without `vehicle:delete:any` can only delete their own vehicles.
+pub async fn delete_vehicle_handler(
+    State(db): State<VehicleDb>,
+    RequirePermission { user, permissions, .. }: RequirePermission<VehicleDeleteOwn>,
+    AxPath(id): AxPath<String>,
+) -> impl IntoResponse {
+    let owner = match owner_scope::<VehicleDeleteAny>(&user, &permissions) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
 
-    match update_vehicle(&db, &id, payload, Some(file_paths)).await {
-        Ok(vehicle) => (
+    match delete_vehicle(&db, &id, owner).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "Vehicle deleted" }))),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Vehicle not found" })),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// POST /vehicle/:id/restore
+/// Requires `vehicle:restore`.
+pub async fn restore_vehicle_handler(
+    State(db): State<VehicleDb>,
+    _: RequirePermission<VehicleRestore>,
+    AxPath(id): AxPath<String>,
+) -> impl IntoResponse {
+    match restore_vehicle(&db, &id).await {
+        Ok(Some(vehicle)) => (
             StatusCode::OK,
-            Json(json!({ "message": "Vehicle updated successfully", "vehicle": vehicle })),
+            Json(json!({ "message": "Vehicle restored", "vehicle": vehicle })),
         ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": e })),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "No deleted vehicle with that ID" })),
         ),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    }
+}
+
+/// GET /vin/:vin/decode
+/// Offline decode of manufacturer, country, model year and plant code.
+pub async fn decode_vin_handler(
+    _: RequirePermission<VehicleReadOwn>,
+    AxPath(vin): AxPath<String>,
+) -> impl IntoResponse {
+    match vin::decode(&vin) {
+        Ok(decoded) => (StatusCode::OK, Json(json!(decoded))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
     }
 }
 
-/// Small sanitizer for filenames
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_' || *c == '-')
-        .collect::<String>()
+/// Which owner's vehicles the caller may act on: `None` (everyone's) with the
+/// `:any` permission `P`, otherwise only their own
+pub fn owner_scope<P: Permission>(
+    user: &AuthUser,
+    permissions: &PermissionSet,
+) -> Result<Option<ObjectId>, (StatusCode, Json<serde_json::Value>)> {
+    if permissions.allows(P::NAME) {
+        return Ok(None);
+    }
+    user.user_object_id()
+        .map(Some)
+        .map_err(|e| (StatusCode::UNAUTHORIZED, Json(json!({ "error": e }))))
+}
+
+/// Remove files stored for a request that did not go through
+async fn discard_uploads(store: &SharedFileStore, keys: &[String]) {
+    for key in keys {
+        discard(store, key).await;
+    }
 }
@@ -1,12 +1,32 @@
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use mongodb::{options::ClientOptions, Client, Collection};
-use crate::models::{user_model::User,vehicle_model::Vehicle};
+use mongodb::{
+    bson::doc,
+    options::{ClientOptions, IndexOptions},
+    Client, Collection, IndexModel,
+};
+use crate::models::{
+    api_key_model::ApiKey, document_model::VehicleDocument, fuel_model::FuelEntry, maintenance_model::{MaintenanceDueItem, MaintenanceRule}, oidc_model::OidcPendingLogin, role_model::{Role, RoleAuditEntry}, service_record_model::ServiceRecord, session_model::Session, transfer_model::VehicleTransfer, user_model::User, user_token_model::UserToken,
+    vehicle_model::Vehicle,
+};
 use std::env;
 
 // pub type TaskDb = Arc<Mutex<Collection<Task>>>;
 pub type UserDb = Arc<Mutex<Collection<User>>>;
 pub type VehicleDb = Arc<Mutex<Collection<Vehicle>>>;
+pub type SessionDb = Arc<Mutex<Collection<Session>>>;
+pub type UserTokenDb = Arc<Mutex<Collection<UserToken>>>;
+pub type RoleDb = Arc<Mutex<Collection<Role>>>;
+pub type ApiKeyDb = Arc<Mutex<Collection<ApiKey>>>;
+pub type OidcStateDb = Arc<Mutex<Collection<OidcPendingLogin>>>;
+pub type RoleAuditDb = Arc<Mutex<Collection<RoleAuditEntry>>>;
+pub type ServiceRecordDb = Arc<Mutex<Collection<ServiceRecord>>>;
+pub type FuelLogDb = Arc<Mutex<Collection<FuelEntry>>>;
+pub type MaintenanceRuleDb = Arc<Mutex<Collection<MaintenanceRule>>>;
+pub type MaintenanceDueDb = Arc<Mutex<Collection<MaintenanceDueItem>>>;
+pub type VehicleDocumentDb = Arc<Mutex<Collection<VehicleDocument>>>;
+pub type VehicleTransferDb = Arc<Mutex<Collection<VehicleTransfer>>>;
 
 async fn get_client() -> Client {
     let uri = env::var("MONGODB_URI").expect("MONGODB_URI must be set");
@@ -27,6 +47,24 @@ pub async fn connect_user_collection() -> UserDb {
     let client = get_client().await;
     let db = client.database(&db_name);
     let collection = db.collection::<User>("users");
+
+    // One account per external identity
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "oidc_issuer": 1, "oidc_subject": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! { "oidc_subject": { "$exists": true } })
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create user indexes");
+
     Arc::new(Mutex::new(collection))
 }
 
@@ -35,5 +73,319 @@ pub async fn connect_vehicle_collection() -> VehicleDb {
     let client = get_client().await;
     let db = client.database(&db_name);
     let collection = db.collection::<Vehicle>("vehicles");
+
+    // Backs the owner-scoped listing, which pages over `_id`
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "_id": -1 })
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create vehicle indexes");
+
+    // A VIN identifies one vehicle; records without one are exempt
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "vin": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! { "vin": { "$type": "string" } })
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create vehicle indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_session_collection() -> SessionDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<Session>("sessions");
+
+    // Refresh tokens are looked up by hash; expired sessions are reaped by Mongo's TTL monitor
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "refresh_token_hash": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build())
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create session indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_user_token_collection() -> UserTokenDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<UserToken>("user_tokens");
+
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "token_hash": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build())
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create user token indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_role_collection() -> RoleDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<Role>("roles");
+
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "name": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create role indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_role_audit_collection() -> RoleAuditDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<RoleAuditEntry>("role_audit");
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_api_key_collection() -> ApiKeyDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<ApiKey>("api_keys");
+
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "prefix": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create API key indexes");
+
     Arc::new(Mutex::new(collection))
 }
+
+pub async fn connect_oidc_state_collection() -> OidcStateDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<OidcPendingLogin>("oidc_states");
+
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "state": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(IndexOptions::builder().expire_after(Duration::from_secs(0)).build())
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create OIDC state indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_service_record_collection() -> ServiceRecordDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<ServiceRecord>("service_records");
+
+    // History of one vehicle, newest first
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "vehicle_id": 1, "date": -1 })
+                .build(),
+            None,
+        )
+        .await
+        .expect("Failed to create service record indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_fuel_log_collection() -> FuelLogDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<FuelEntry>("fuel_entries");
+
+    // Neighbour lookups for the odometer check and the ordered stats pipelines
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1, "date": 1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1, "odometer_km": 1 })
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create fuel log indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_maintenance_rule_collection() -> MaintenanceRuleDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<MaintenanceRule>("maintenance_rules");
+
+    // Rules of one vehicle; templates are listed by make and model
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "make": 1, "model": 1 })
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create maintenance rule indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_maintenance_due_collection() -> MaintenanceDueDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<MaintenanceDueItem>("maintenance_due");
+
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "user_id": 1 })
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create maintenance due indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_vehicle_document_collection() -> VehicleDocumentDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<VehicleDocument>("vehicle_documents");
+
+    // Documents of one vehicle, and the expiry scan
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1, "expires_on": 1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "expires_on": 1 })
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create vehicle document indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+pub async fn connect_vehicle_transfer_collection() -> VehicleTransferDb {
+    let db_name = env::var("DATABASE_NAME").unwrap_or("async_rust_db".to_string());
+    let client = get_client().await;
+    let db = client.database(&db_name);
+    let collection = db.collection::<VehicleTransfer>("vehicle_transfers");
+
+    let indexes = vec![
+        // At most one open offer per vehicle
+        IndexModel::builder()
+            .keys(doc! { "vehicle_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "status": "pending" })
+                    .build(),
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "to_user_id": 1, "status": 1 })
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "from_user_id": 1, "status": 1 })
+            .build(),
+    ];
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .expect("Failed to create vehicle transfer indexes");
+
+    Arc::new(Mutex::new(collection))
+}
+
+/// Throwaway databases for tests that need MongoDB. They only run when
+/// `TEST_MONGODB_URI` is set and are skipped otherwise.
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+    use mongodb::Database;
+
+    pub async fn test_database() -> Option<Database> {
+        let Ok(uri) = env::var("TEST_MONGODB_URI") else {
+            eprintln!("TEST_MONGODB_URI not set, skipping");
+            return None;
+        };
+        let client = Client::with_uri_str(&uri).await.expect("Invalid TEST_MONGODB_URI");
+        Some(client.database(&format!("async_rust_test_{}", uuid::Uuid::new_v4().simple())))
+    }
+
+    pub fn shared<T: Send + Sync>(db: &Database, name: &str) -> Arc<Mutex<Collection<T>>> {
+        Arc::new(Mutex::new(db.collection::<T>(name)))
+    }
+}
@@ -0,0 +1,33 @@
+use axum::async_trait;
+use uuid::Uuid;
+
+use super::{EmailMessage, Mailer};
+
+/// Writes each message as a JSON file; handy for local development
+pub struct FileMailer {
+    dir: String,
+}
+
+impl FileMailer {
+    pub fn new(dir: String) -> Self {
+        FileMailer { dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: EmailMessage) -> Result<(), String> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let path = format!("{}/{}.json", self.dir, Uuid::new_v4());
+        let body = serde_json::to_vec_pretty(&message).map_err(|e| e.to_string())?;
+        tokio::fs::write(&path, body)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        println!("Mail to {} written to {}", message.to, path);
+        Ok(())
+    }
+}
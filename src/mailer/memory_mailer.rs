@@ -0,0 +1,27 @@
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use super::{EmailMessage, Mailer};
+
+/// Keeps sent messages in memory so tests can assert on them
+#[derive(Default)]
+pub struct InMemoryMailer {
+    sent: Mutex<Vec<EmailMessage>>,
+}
+
+impl InMemoryMailer {
+    /// Messages sent so far, oldest first
+    #[cfg(test)]
+    pub fn sent(&self) -> Vec<EmailMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, message: EmailMessage) -> Result<(), String> {
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use std::env;
+use std::sync::Arc;
+
+use axum::async_trait;
+use serde::Serialize;
+
+pub mod file_mailer;
+pub mod memory_mailer;
+pub mod smtp_mailer;
+
+pub use file_mailer::FileMailer;
+pub use memory_mailer::InMemoryMailer;
+pub use smtp_mailer::SmtpMailer;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: EmailMessage) -> Result<(), String>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer>;
+
+/// Build the mailer selected by `MAIL_TRANSPORT`:
+///  - `smtp`   → SMTP relay configured via `SMTP_*` variables
+///  - `memory` → kept in process memory (tests)
+///  - `file`   → one JSON file per message under `MAIL_OUTBOX_DIR` (default)
+pub fn mailer_from_env() -> SharedMailer {
+    let transport = env::var("MAIL_TRANSPORT").unwrap_or("file".to_string());
+
+    match transport.to_lowercase().as_str() {
+        "smtp" => Arc::new(SmtpMailer::from_env().expect("Invalid SMTP configuration")),
+        "memory" => Arc::new(InMemoryMailer::default()),
+        _ => {
+            let dir = env::var("MAIL_OUTBOX_DIR").unwrap_or("./mail_outbox".to_string());
+            Arc::new(FileMailer::new(dir))
+        }
+    }
+}
+
+/// Public base URL used to build links in outgoing mail
+pub fn app_base_url() -> String {
+    env::var("APP_BASE_URL").unwrap_or("http://localhost:3000".to_string())
+}
@@ -0,0 +1,59 @@
+use std::env;
+
+use axum::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{EmailMessage, Mailer};
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Reads `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD` and `MAIL_FROM`
+    pub fn from_env() -> Result<Self, String> {
+        let host = env::var("SMTP_HOST").map_err(|_| "SMTP_HOST must be set".to_string())?;
+        let from = env::var("MAIL_FROM")
+            .map_err(|_| "MAIL_FROM must be set".to_string())?
+            .parse::<Mailbox>()
+            .map_err(|e| e.to_string())?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+            .map_err(|e| e.to_string())?;
+
+        if let Some(port) = env::var("SMTP_PORT").ok().and_then(|p| p.parse::<u16>().ok()) {
+            builder = builder.port(port);
+        }
+        if let (Ok(user), Ok(pass)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(user, pass));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: EmailMessage) -> Result<(), String> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(message.to.parse::<Mailbox>().map_err(|e| e.to_string())?)
+            .subject(message.subject)
+            .body(message.body)
+            .map_err(|e| e.to_string())?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
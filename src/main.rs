@@ -1,14 +1,18 @@
 mod app;
 mod db;
+mod mailer;
 mod models;
 mod controllers;
 mod services;
 mod routes;
+mod storage;
 mod middlewares;
+mod utils;
 
 
 use dotenvy::dotenv;
 use std::env;
+use std::net::SocketAddr;
 use axum::serve;
 
 #[tokio::main]
@@ -21,7 +25,11 @@ async fn main() {
     let app = app::build_app().await;
 
     println!("Server running at http://{}", addr);
-    serve(tokio::net::TcpListener::bind(&addr).await.unwrap(), app)
-        .await
-        .unwrap();
+    // Connect info gives the rate limiter the client address
+    serve(
+        tokio::net::TcpListener::bind(&addr).await.unwrap(),
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -1,27 +1,109 @@
 use axum::{
     async_trait,
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    extract::{Extension, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
 };
+use mongodb::bson::{doc, oid::ObjectId};
 use axum_extra::extract::TypedHeader;
 use headers::{authorization::Bearer, Authorization};
-use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::marker::PhantomData;
 
+use crate::db::{ApiKeyDb, RoleDb, SessionDb, UserDb};
+use crate::models::permission_model::{Permission, PermissionSet};
 use crate::models::user_model::UserRole;
+use crate::services::api_key_service::authenticate_api_key;
+use crate::services::role_service::permissions_for_role;
+use crate::services::session_service::is_session_active;
+use crate::utils::jwt;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub role: UserRole,
     pub exp: usize,
+    /// Session id; lets a logout or revocation invalidate the token before `exp`
+    pub jti: String,
 }
 
 #[derive(Clone, Debug)]
 pub struct AuthUser {
     pub user_id: String,
     pub role: UserRole,
+    /// Session behind a bearer token; `None` when authenticated with an API key
+    pub session_id: Option<String>,
+    /// Scopes of the API key used; `None` for bearer tokens
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Key from `X-Api-Key: <key>` or `Authorization: ApiKey <key>`, if any
+fn api_key_from_headers(parts: &Parts) -> Option<String> {
+    if let Some(value) = parts.headers.get("x-api-key") {
+        return value.to_str().ok().map(|v| v.trim().to_string());
+    }
+
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("ApiKey "))
+        .map(|v| v.trim().to_string())
+}
+
+async fn authenticate_with_api_key(parts: &mut Parts, key: &str) -> Result<AuthUser, (StatusCode, String)> {
+    let Extension(api_keys) = Extension::<ApiKeyDb>::from_request_parts(parts, &())
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "API key store not configured".to_string()))?;
+    let Extension(users) = Extension::<UserDb>::from_request_parts(parts, &())
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "User store not configured".to_string()))?;
+
+    let api_key = authenticate_api_key(&api_keys, key)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e))?;
+
+    // The role is read live so demoting a user also narrows their keys
+    let collection = users.lock().await;
+    let owner = collection
+        .find_one(doc! { "_id": api_key.user_id }, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()))?;
+
+    Ok(AuthUser {
+        user_id: api_key.user_id.to_hex(),
+        role: owner.role,
+        session_id: None,
+        scopes: Some(api_key.scopes),
+    })
+}
+
+async fn authenticate_with_bearer(parts: &mut Parts) -> Result<AuthUser, (StatusCode, String)> {
+    let TypedHeader(Authorization(bearer)) =
+        TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, &())
+            .await
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Missing Authorization header".to_string()))?;
+
+    let claims: Claims = jwt::verify(bearer.token())
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired token".to_string()))?;
+
+    let Extension(sessions) = Extension::<SessionDb>::from_request_parts(parts, &())
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Session store not configured".to_string()))?;
+
+    let active = is_session_active(&sessions, &claims.jti)
+        .await
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired token".to_string()))?;
+    if !active {
+        return Err((StatusCode::UNAUTHORIZED, "Session has been revoked".to_string()));
+    }
+
+    Ok(AuthUser {
+        user_id: claims.sub,
+        role: claims.role,
+        session_id: Some(claims.jti),
+        scopes: None,
+    })
 }
 
 #[async_trait]
@@ -32,34 +114,61 @@ where
     type Rejection = (StatusCode, String);
 
     async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
-        let TypedHeader(Authorization(bearer)) =
-            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, &())
-                .await
-                .map_err(|_| (StatusCode::UNAUTHORIZED, "Missing Authorization header".to_string()))?;
-
-        let secret = env::var("JWT_SECRET").unwrap_or("mysecret".to_string());
-        let token_data = decode::<Claims>(
-            bearer.token(),
-            &DecodingKey::from_secret(secret.as_ref()),
-            &Validation::default(),
-        )
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired token".to_string()))?;
+        match api_key_from_headers(parts) {
+            Some(key) => authenticate_with_api_key(parts, &key).await,
+            None => authenticate_with_bearer(parts).await,
+        }
+    }
+}
 
-        Ok(AuthUser {
-            user_id: token_data.claims.sub,
-            role: token_data.claims.role,
-        })
+impl AuthUser {
+    pub fn user_object_id(&self) -> Result<ObjectId, String> {
+        ObjectId::parse_str(&self.user_id).map_err(|_| "Invalid user ID".to_string())
     }
 }
 
-/// Middleware helper for role-based routes
-pub fn require_role(user: &AuthUser, allowed: &[UserRole]) -> Result<(), (StatusCode, String)> {
-    if allowed.contains(&user.role) {
-        Ok(())
-    } else {
-        Err((
-            StatusCode::FORBIDDEN,
-            format!("Forbidden: Role {:?} not allowed", user.role),
-        ))
+/// Extractor that only succeeds when the caller's role grants `P`.
+/// Handlers get the authenticated user plus the full permission set, for
+/// follow-up checks such as "`:any` if it isn't the caller's own record".
+pub struct RequirePermission<P: Permission> {
+    pub user: AuthUser,
+    pub permissions: PermissionSet,
+    _permission: PhantomData<fn() -> P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+
+        let Extension(roles) = Extension::<RoleDb>::from_request_parts(parts, &())
+            .await
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Role store not configured".to_string()))?;
+
+        let mut permissions = permissions_for_role(&roles, &user.role)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        if let Some(scopes) = &user.scopes {
+            permissions = permissions.restrict_to(scopes.clone());
+        }
+
+        if !permissions.allows(P::NAME) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Forbidden: missing permission {}", P::NAME),
+            ));
+        }
+
+        Ok(RequirePermission {
+            user,
+            permissions,
+            _permission: PhantomData,
+        })
     }
 }
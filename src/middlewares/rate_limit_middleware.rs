@@ -0,0 +1,207 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Buckets are swept once the map grows past this many client addresses
+const SWEEP_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// In-process token bucket keyed by client IP
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Allow bursts of `per_minute` requests, refilled evenly over a minute
+    pub fn per_minute(per_minute: u32) -> Arc<Self> {
+        let capacity = per_minute.max(1) as f64;
+        Arc::new(RateLimiter {
+            capacity,
+            refill_per_sec: capacity / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Limit read from `var`, falling back to `default` requests per minute
+    pub fn from_env(var: &str, default: u32) -> Arc<Self> {
+        let per_minute = env::var(var)
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(default);
+        Self::per_minute(per_minute)
+    }
+
+    /// Take one token for `ip`, or return how many seconds until one is available
+    fn try_acquire(&self, ip: IpAddr) -> Result<(), u64> {
+        self.try_acquire_at(ip, Instant::now())
+    }
+
+    fn try_acquire_at(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > SWEEP_THRESHOLD {
+            let (capacity, rate) = (self.capacity, self.refill_per_sec);
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / self.refill_per_sec).ceil() as u64)
+        }
+    }
+}
+
+/// Client address. Behind proxies (`TRUST_PROXY_HEADERS=true`) it is taken from
+/// `X-Forwarded-For`, counting `TRUSTED_PROXY_HOPS` (default 1) entries from the
+/// right: those are appended by our own proxies, anything further left is
+/// whatever the client chose to send.
+fn client_ip(req: &Request) -> Option<IpAddr> {
+    let trust_proxy = env::var("TRUST_PROXY_HEADERS")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+
+    if trust_proxy {
+        let hops = env::var("TRUSTED_PROXY_HOPS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1);
+        let forwarded = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Some(ip) = forwarded_client(&forwarded, hops) {
+            return Some(ip);
+        }
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// The entry `hops` places from the right of an `X-Forwarded-For` list
+fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    header
+        .rsplit(',')
+        .nth(hops.checked_sub(1)?)
+        .and_then(|v| v.trim().parse::<IpAddr>().ok())
+}
+
+/// Route layer: `post(handler).layer(from_fn_with_state(limiter, rate_limit))`
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(ip) = client_ip(&req) else {
+        return next.run(req).await;
+    };
+
+    match limiter.try_acquire(ip) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(json!({
+                "error": "Too many requests, slow down",
+                "code": "rate_limited",
+                "retry_after": retry_after,
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7));
+
+    #[test]
+    fn forwarded_client_counts_trusted_hops_from_the_right() {
+        // The client spoofed the first entry; our proxy appended the real one
+        let header = "10.0.0.1, 203.0.113.7";
+        assert_eq!(forwarded_client(header, 1), Some(CLIENT));
+        assert_eq!(forwarded_client("203.0.113.7", 1), Some(CLIENT));
+
+        // Two proxies: the load balancer's address is the last entry
+        let header = "10.0.0.1, 203.0.113.7, 192.0.2.1";
+        assert_eq!(forwarded_client(header, 2), Some(CLIENT));
+
+        // Fewer entries than hops, garbage, or no hops at all fall back to the socket
+        assert_eq!(forwarded_client("203.0.113.7", 2), None);
+        assert_eq!(forwarded_client("10.0.0.1, not-an-ip", 1), None);
+        assert_eq!(forwarded_client(header, 0), None);
+    }
+
+    #[test]
+    fn try_acquire_denies_once_the_burst_is_spent() {
+        let limiter = RateLimiter::per_minute(30);
+        let now = Instant::now();
+
+        for _ in 0..30 {
+            assert_eq!(limiter.try_acquire_at(CLIENT, now), Ok(()));
+        }
+        // One token refills every 2s
+        assert_eq!(limiter.try_acquire_at(CLIENT, now), Err(2));
+
+        // Other clients have their own bucket
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 1));
+        assert_eq!(limiter.try_acquire_at(other, now), Ok(()));
+    }
+
+    #[test]
+    fn try_acquire_refills_over_time_up_to_capacity() {
+        let limiter = RateLimiter::per_minute(30);
+        let start = Instant::now();
+        for _ in 0..30 {
+            limiter.try_acquire_at(CLIENT, start).unwrap();
+        }
+
+        assert_eq!(limiter.try_acquire_at(CLIENT, start + Duration::from_secs(1)), Err(1));
+        assert_eq!(limiter.try_acquire_at(CLIENT, start + Duration::from_secs(2)), Ok(()));
+        assert_eq!(limiter.try_acquire_at(CLIENT, start + Duration::from_secs(2)), Err(2));
+
+        // A long pause refills the burst but never beyond it
+        let later = start + Duration::from_secs(3600);
+        for _ in 0..30 {
+            assert_eq!(limiter.try_acquire_at(CLIENT, later), Ok(()));
+        }
+        assert!(limiter.try_acquire_at(CLIENT, later).is_err());
+    }
+}
@@ -1,45 +1,238 @@
-use axum::{extract::Multipart, http::StatusCode, response::{Json}};
+use axum::{
+    extract::{multipart::{Field, MultipartError}, DefaultBodyLimit},
+    http::StatusCode,
+    response::{Json},
+};
+use axum::body::Bytes;
+use futures_util::{stream, StreamExt};
 use serde_json::json;
-use std::path::Path;
-use tokio::io::AsyncWriteExt;
-use uuid::Uuid;
-use std::collections::HashMap;
-
-/// Handle multiple file uploads and return a map of field names to file paths
-pub async fn handle_multiple_uploads(
-    mut multipart: Multipart,
-    fields: &[&str], // List of field names to look for
-) -> Result<HashMap<String, String>, (StatusCode, Json<serde_json::Value>)> {
-    let mut file_paths: HashMap<String, String> = HashMap::new();
-    let upload_dir = "./uploads";
-
-    // Ensure upload directory exists
-    if !Path::new(upload_dir).exists() {
-        tokio::fs::create_dir_all(upload_dir).await.unwrap();
-    }
-
-    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
-        // Use as_ref() to avoid moving the field
-        if let Some(name) = field.name().map(|n| n.to_string()) {
-            // Check if the field name is in the allowed fields
-            if fields.contains(&name.as_str()) {
-                let file_name = field
-                    .file_name()
-                    .unwrap_or("default.jpg")
-                    .to_string();
-                let file_path = format!("{}/{}", upload_dir, Uuid::new_v4().to_string() + &file_name);
-                let bytes = field.bytes().await.unwrap_or_default();
-
-                let mut file = tokio::fs::File::create(&file_path)
-                    .await
-                    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "File save failed"}))))?;
-                file.write_all(&bytes).await.unwrap();
-
-                // Store the file path with the field name as the key
-                file_paths.insert(name, file_path);
-            }
+use std::env;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+
+use crate::services::{
+    document_service::DOCUMENT_KEY_PREFIX, service_record_service::RECEIPT_KEY_PREFIX,
+    user_service::PROFILE_IMAGE_KEY_PREFIX, vehicle_service::VEHICLE_KEY_PREFIX,
+};
+use crate::storage::{self, SharedFileStore};
+use crate::utils::file_type::{self, SNIFF_LEN};
+
+/// Largest single uploaded file, in bytes (`UPLOAD_MAX_FILE_BYTES`, default 10 MiB)
+fn max_file_bytes() -> u64 {
+    env::var("UPLOAD_MAX_FILE_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(10 * 1024 * 1024)
+}
+
+/// Most file bytes one request may upload in total
+/// (`UPLOAD_MAX_REQUEST_BYTES`, default 25 MiB)
+fn max_request_bytes() -> u64 {
+    env::var("UPLOAD_MAX_REQUEST_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(25 * 1024 * 1024)
+}
+
+/// Room for the text fields and multipart framing on top of the files
+const FORM_OVERHEAD_BYTES: u64 = 1024 * 1024;
+
+/// Body limit for routes that take uploads. Every other route keeps axum's
+/// default, which is far too small for files.
+pub fn upload_body_limit() -> DefaultBodyLimit {
+    let limit = max_request_bytes().saturating_add(FORM_OVERHEAD_BYTES);
+    DefaultBodyLimit::max(usize::try_from(limit).unwrap_or(usize::MAX))
+}
+
+const IMAGE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/webp", "image/gif", "image/heic"];
+const IMAGE_AND_PDF_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/heic",
+    "application/pdf",
+];
+
+/// What an upload field holds, which decides where it is stored and which
+/// types it accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadKind {
+    ProfileImage,
+    VehiclePhoto,
+    Receipt,
+    VehicleDocument,
+}
+
+impl UploadKind {
+    fn key_prefix(self) -> &'static str {
+        match self {
+            UploadKind::ProfileImage => PROFILE_IMAGE_KEY_PREFIX,
+            UploadKind::VehiclePhoto => VEHICLE_KEY_PREFIX,
+            UploadKind::Receipt => RECEIPT_KEY_PREFIX,
+            UploadKind::VehicleDocument => DOCUMENT_KEY_PREFIX,
+        }
+    }
+
+    /// Accepted MIME types, overridable with a comma-separated list in
+    ///  - `UPLOAD_TYPES_PROFILE_IMAGE`    (default: images)
+    ///  - `UPLOAD_TYPES_VEHICLE_PHOTO`    (default: images)
+    ///  - `UPLOAD_TYPES_RECEIPT`          (default: images and PDF)
+    ///  - `UPLOAD_TYPES_VEHICLE_DOCUMENT` (default: images and PDF)
+    ///
+    /// Only types `file_type::sniff` recognises can ever get through.
+    fn allowed_types(self) -> Vec<String> {
+        let (var, default) = match self {
+            UploadKind::ProfileImage => ("UPLOAD_TYPES_PROFILE_IMAGE", IMAGE_TYPES),
+            UploadKind::VehiclePhoto => ("UPLOAD_TYPES_VEHICLE_PHOTO", IMAGE_TYPES),
+            UploadKind::Receipt => ("UPLOAD_TYPES_RECEIPT", IMAGE_AND_PDF_TYPES),
+            UploadKind::VehicleDocument => ("UPLOAD_TYPES_VEHICLE_DOCUMENT", IMAGE_AND_PDF_TYPES),
+        };
+        match env::var(var) {
+            Ok(list) => list
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            Err(_) => default.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("File is larger than the {0}-byte limit")]
+    FileTooLarge(u64),
+    #[error("Upload is larger than the {0}-byte limit")]
+    RequestTooLarge(u64),
+    /// Contents are not of a type the field accepts; holds the detected type, if any
+    #[error("{}", unsupported_message(.0))]
+    UnsupportedType(Option<String>),
+    #[error("File extension .{extension} does not match its contents ({detected})")]
+    ExtensionMismatch { extension: String, detected: String },
+    /// The multipart body itself could not be read
+    #[error("{1}")]
+    Malformed(StatusCode, String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+fn unsupported_message(detected: &Option<String>) -> String {
+    match detected {
+        Some(detected) => format!("Files of type {} are not accepted here", detected),
+        None => "File type not recognised or not accepted".to_string(),
+    }
+}
+
+impl From<MultipartError> for UploadError {
+    fn from(err: MultipartError) -> Self {
+        UploadError::Malformed(err.status(), err.body_text())
+    }
+}
+
+/// Oversized uploads are `413`, refused types `415`, unreadable bodies keep the
+/// status axum gives them, and storage failures are a `500`
+pub fn upload_error_response(err: UploadError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match &err {
+        UploadError::FileTooLarge(_) | UploadError::RequestTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        UploadError::UnsupportedType(_) | UploadError::ExtensionMismatch { .. } => {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        }
+        UploadError::Malformed(status, _) => *status,
+        UploadError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": err.to_string() })))
+}
+
+/// Byte allowance for the files of one request
+pub struct UploadBudget {
+    max_file: u64,
+    max_request: u64,
+    remaining: u64,
+}
+
+impl UploadBudget {
+    pub fn from_env() -> Self {
+        let max_request = max_request_bytes();
+        UploadBudget {
+            max_file: max_file_bytes(),
+            max_request,
+            remaining: max_request,
         }
     }
+}
+
+/// Stream one uploaded file into the store under a unique key and return the
+/// key. The type is detected from the first bytes and checked against what
+/// `kind` accepts and the file name's extension; the detected type, not the
+/// one the client sent, is stored with the file. The file is never held in
+/// memory as a whole, and nothing is kept when it is refused, runs over the
+/// budget or the body breaks off.
+pub async fn save_upload(
+    store: &SharedFileStore,
+    mut field: Field<'_>,
+    kind: UploadKind,
+    budget: &mut UploadBudget,
+) -> Result<String, UploadError> {
+    let file_name = field.file_name().unwrap_or("file").to_string();
 
-    Ok(file_paths)
+    let mut head = Vec::new();
+    while head.len() < SNIFF_LEN {
+        match field.chunk().await? {
+            Some(chunk) => head.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+
+    let detected = file_type::sniff(&head).ok_or(UploadError::UnsupportedType(None))?;
+    if !kind.allowed_types().iter().any(|t| t == detected.mime) {
+        return Err(UploadError::UnsupportedType(Some(detected.mime.to_string())));
+    }
+    let file_name = file_type::name_for(detected, &file_name).ok_or_else(|| UploadError::ExtensionMismatch {
+        extension: file_type::extension(&file_name).unwrap_or_default(),
+        detected: detected.mime.to_string(),
+    })?;
+    let key = storage::new_key(kind.key_prefix(), &file_name);
+
+    let (limit, too_large) = if budget.max_file <= budget.remaining {
+        (budget.max_file, UploadError::FileTooLarge(budget.max_file))
+    } else {
+        (budget.remaining, UploadError::RequestTooLarge(budget.max_request))
+    };
+
+    let received = AtomicU64::new(0);
+    let body_status = AtomicU16::new(0);
+    let rest = field.map(|chunk| {
+        chunk.map_err(|e| {
+            body_status.store(e.status().as_u16(), Ordering::Relaxed);
+            std::io::Error::other(e.body_text())
+        })
+    });
+    let body = stream::once(async { Ok(Bytes::from(head)) })
+        .chain(rest)
+        .map(|chunk| {
+            let chunk = chunk?;
+            let total = received.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+            if total > limit {
+                return Err(std::io::Error::other("Upload limit exceeded"));
+            }
+            Ok(chunk)
+        })
+        .boxed();
+
+    let stored = store.put(&key, body, Some(detected.mime)).await;
+    let received = received.into_inner();
+
+    match stored {
+        Ok(_) => {
+            budget.remaining -= received;
+            Ok(key)
+        }
+        Err(_) if received > limit => Err(too_large),
+        Err(e) => match StatusCode::from_u16(body_status.into_inner()) {
+            Ok(status) => Err(UploadError::Malformed(status, e)),
+            Err(_) => Err(UploadError::Failed(e)),
+        },
+    }
 }
@@ -0,0 +1,62 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A user-owned key for machine-to-machine access.
+/// Keys look like `ak_<prefix>_<secret>`; only the secret's SHA-256 is stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub name: String,
+    pub prefix: String,
+    pub secret_hash: String,
+    /// Permissions the key may exercise. A key can only narrow its owner's role:
+    /// a request is allowed when both the role and these scopes grant it.
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKey {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Omit for a key that never expires
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateApiKey {
+    pub name: Option<String>,
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Public view of a key; never includes the secret hash
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(key: ApiKey) -> Self {
+        ApiKeyResponse {
+            id: key.id.map(|i| i.to_hex()).unwrap_or_default(),
+            name: key.name,
+            prefix: key.prefix,
+            scopes: key.scopes,
+            expires_at: key.expires_at.map(|d| d.try_to_rfc3339_string().unwrap_or_default()),
+            last_used_at: key.last_used_at.map(|d| d.try_to_rfc3339_string().unwrap_or_default()),
+            created_at: key.created_at.map(|d| d.try_to_rfc3339_string().unwrap_or_default()),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::validation::{optional_text, parse_date, required_text, FieldError, Validator};
+
+const MAX_ISSUER_LEN: usize = 120;
+const MAX_NUMBER_LEN: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    Insurance,
+    Registration,
+    Inspection,
+    Other,
+}
+
+impl DocumentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentKind::Insurance => "insurance",
+            DocumentKind::Registration => "registration",
+            DocumentKind::Inspection => "inspection",
+            DocumentKind::Other => "other",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "insurance" => Some(DocumentKind::Insurance),
+            "registration" => Some(DocumentKind::Registration),
+            "inspection" => Some(DocumentKind::Inspection),
+            "other" => Some(DocumentKind::Other),
+            _ => None,
+        }
+    }
+}
+
+/// A paper that belongs to a vehicle, such as an insurance policy or registration
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VehicleDocument {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: ObjectId,
+    pub kind: DocumentKind,
+    pub issuer: Option<String>,
+    pub number: Option<String>,
+    pub issued_on: Option<DateTime>,
+    pub expires_on: Option<DateTime>,
+    /// Storage key of the attached scan or PDF
+    pub file: Option<String>,
+    /// Set once the owner has been told about the coming expiry; cleared when
+    /// `expires_on` changes
+    #[serde(default)]
+    pub expiry_notified_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
+    pub updated_at: Option<DateTime>,
+}
+
+pub struct CreateVehicleDocument {
+    pub kind: DocumentKind,
+    pub issuer: Option<String>,
+    pub number: Option<String>,
+    pub issued_on: Option<DateTime>,
+    pub expires_on: Option<DateTime>,
+    /// Storage key of the uploaded file, filled in after the form is parsed
+    pub file: Option<String>,
+}
+
+/// Partial update: only the fields that are present are changed
+pub struct UpdateVehicleDocument {
+    pub kind: Option<DocumentKind>,
+    pub issuer: Option<String>,
+    pub number: Option<String>,
+    pub issued_on: Option<DateTime>,
+    pub expires_on: Option<DateTime>,
+    /// Replaces the current file
+    pub file: Option<String>,
+}
+
+/// Text fields as received in the multipart form, before parsing
+#[derive(Debug, Default)]
+pub struct VehicleDocumentForm {
+    pub kind: Option<String>,
+    pub issuer: Option<String>,
+    pub number: Option<String>,
+    pub issued_on: Option<String>,
+    pub expires_on: Option<String>,
+}
+
+impl VehicleDocumentForm {
+    /// `kind` is required, everything else optional
+    pub fn into_create(self) -> Result<CreateVehicleDocument, Vec<FieldError>> {
+        let mut v = Validator::new();
+        let kind = self.parsed_kind(&mut v, true);
+        let issued_on = parsed_date(&mut v, "issued_on", self.issued_on.as_deref());
+        let expires_on = parsed_date(&mut v, "expires_on", self.expires_on.as_deref());
+        check_order(&mut v, issued_on, expires_on);
+        let issuer = optional_text(&mut v, "issuer", self.issuer, MAX_ISSUER_LEN);
+        let number = optional_text(&mut v, "number", self.number, MAX_NUMBER_LEN);
+
+        v.finish()?;
+        let Some(kind) = kind else {
+            return Err(vec![]);
+        };
+
+        Ok(CreateVehicleDocument {
+            kind,
+            issuer,
+            number,
+            issued_on,
+            expires_on,
+            file: None,
+        })
+    }
+
+    /// Parse and check only the fields that were sent. When only one of the
+    /// dates is sent, its order against the stored one is not checked here.
+    pub fn into_update(self) -> Result<UpdateVehicleDocument, Vec<FieldError>> {
+        let mut v = Validator::new();
+        let update = UpdateVehicleDocument {
+            kind: self.parsed_kind(&mut v, false),
+            issued_on: parsed_date(&mut v, "issued_on", self.issued_on.as_deref()),
+            expires_on: parsed_date(&mut v, "expires_on", self.expires_on.as_deref()),
+            issuer: optional_text(&mut v, "issuer", self.issuer, MAX_ISSUER_LEN),
+            number: optional_text(&mut v, "number", self.number, MAX_NUMBER_LEN),
+            file: None,
+        };
+        check_order(&mut v, update.issued_on, update.expires_on);
+        v.finish()?;
+        Ok(update)
+    }
+
+    fn parsed_kind(&self, v: &mut Validator, required: bool) -> Option<DocumentKind> {
+        let text = required_text(v, "kind", self.kind.as_deref(), required)?;
+        let kind = DocumentKind::parse(text);
+        v.check(
+            kind.is_some(),
+            "kind",
+            "must be one of insurance, registration, inspection, other",
+        );
+        kind
+    }
+}
+
+/// Blank dates are treated as absent
+fn parsed_date(v: &mut Validator, field: &str, text: Option<&str>) -> Option<DateTime> {
+    let text = text.map(str::trim).filter(|t| !t.is_empty())?;
+    let date = parse_date(text).map(DateTime::from_chrono);
+    v.check(date.is_some(), field, "must be YYYY-MM-DD or an RFC 3339 timestamp");
+    date
+}
+
+fn check_order(v: &mut Validator, issued_on: Option<DateTime>, expires_on: Option<DateTime>) {
+    if let (Some(issued), Some(expires)) = (issued_on, expires_on) {
+        v.check(expires > issued, "expires_on", "must be after issued_on");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_names_match_the_wire_format() {
+        for kind in [
+            DocumentKind::Insurance,
+            DocumentKind::Registration,
+            DocumentKind::Inspection,
+            DocumentKind::Other,
+        ] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+            assert_eq!(DocumentKind::parse(kind.as_str()), Some(kind));
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+/// How a browser should treat a downloaded file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Disposition {
+    /// Shown in place, e.g. in an `<img>` tag
+    Inline,
+    /// Saved under its original file name
+    Attachment,
+}
+
+impl Disposition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Disposition::Inline => "inline",
+            Disposition::Attachment => "attachment",
+        }
+    }
+}
+
+/// Body of `POST /signed-urls`
+#[derive(Debug, Deserialize)]
+pub struct SignFileRequest {
+    pub key: String,
+    pub disposition: Option<Disposition>,
+    /// Lifetime in seconds; defaults to `SIGNED_URL_TTL_SECONDS`
+    pub expires_in: Option<i64>,
+}
+
+/// A link anyone can use to fetch one file until it expires
+#[derive(Debug, Serialize)]
+pub struct SignedFileUrl {
+    pub url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Query string of `GET /files`
+#[derive(Debug, Deserialize)]
+pub struct FileListQuery {
+    /// Only keys starting with this, e.g. `vehicles/`; everything when omitted
+    #[serde(default)]
+    pub prefix: String,
+}
+
+/// Query string of a signed download link
+#[derive(Debug, Deserialize)]
+pub struct SignedFileQuery {
+    /// Unix timestamp, in seconds
+    pub expires: i64,
+    pub disposition: Option<Disposition>,
+    pub signature: String,
+}
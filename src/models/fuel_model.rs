@@ -0,0 +1,152 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::validation::{parse_date, FieldError, Validator};
+
+pub const LITRES_PER_US_GALLON: f64 = 3.785411784;
+pub const KM_PER_MILE: f64 = 1.609344;
+
+/// One fill-up. Stored in metric units whatever the client sent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FuelEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: ObjectId,
+    pub date: DateTime,
+    pub odometer_km: f64,
+    pub litres: f64,
+    /// What was paid for this fill-up
+    pub total_cost: f64,
+    /// Tank filled to the brim; consumption is only measured between full fill-ups
+    pub full_tank: bool,
+    pub notes: Option<String>,
+    pub created_at: Option<DateTime>,
+}
+
+/// Body of `POST /vehicle/:id/fuel`
+#[derive(Debug, Deserialize)]
+pub struct CreateFuelEntry {
+    /// YYYY-MM-DD or RFC 3339; defaults to now
+    pub date: Option<String>,
+    pub odometer: f64,
+    /// `km` (default) or `mi`
+    pub distance_unit: Option<String>,
+    pub volume: f64,
+    /// `litres` (default) or `gallons` (US)
+    pub volume_unit: Option<String>,
+    pub total_cost: f64,
+    #[serde(default = "default_full_tank")]
+    pub full_tank: bool,
+    pub notes: Option<String>,
+}
+
+fn default_full_tank() -> bool {
+    true
+}
+
+/// A validated fill-up, converted to metric
+pub struct NewFuelEntry {
+    pub date: DateTime,
+    pub odometer_km: f64,
+    pub litres: f64,
+    pub total_cost: f64,
+    pub full_tank: bool,
+    pub notes: Option<String>,
+}
+
+impl CreateFuelEntry {
+    pub fn validate(self) -> Result<NewFuelEntry, Vec<FieldError>> {
+        let mut v = Validator::new();
+
+        let date = match self.date.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            None => chrono::Utc::now(),
+            Some(text) => match parse_date(text) {
+                Some(date) if date <= chrono::Utc::now() + chrono::Duration::days(1) => date,
+                Some(_) => {
+                    v.add("date", "must not be in the future");
+                    chrono::Utc::now()
+                }
+                None => {
+                    v.add("date", "must be YYYY-MM-DD or an RFC 3339 timestamp");
+                    chrono::Utc::now()
+                }
+            },
+        };
+
+        let km_per_unit = match self.distance_unit.as_deref().unwrap_or("km") {
+            "km" => 1.0,
+            "mi" => KM_PER_MILE,
+            _ => {
+                v.add("distance_unit", "must be km or mi");
+                1.0
+            }
+        };
+        let litres_per_unit = match self.volume_unit.as_deref().unwrap_or("litres") {
+            "litres" | "liters" | "l" => 1.0,
+            "gallons" | "gal" => LITRES_PER_US_GALLON,
+            _ => {
+                v.add("volume_unit", "must be litres or gallons");
+                1.0
+            }
+        };
+
+        v.check(
+            self.odometer.is_finite() && self.odometer >= 0.0,
+            "odometer",
+            "must be a non-negative number",
+        );
+        v.check(
+            self.volume.is_finite() && self.volume > 0.0,
+            "volume",
+            "must be a positive number",
+        );
+        v.check(
+            self.total_cost.is_finite() && self.total_cost >= 0.0,
+            "total_cost",
+            "must be a non-negative number",
+        );
+        let notes = self.notes.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        v.check(
+            notes.as_ref().is_none_or(|n| n.chars().count() <= 500),
+            "notes",
+            "must be at most 500 characters",
+        );
+
+        v.finish()?;
+
+        Ok(NewFuelEntry {
+            date: DateTime::from_chrono(date),
+            odometer_km: self.odometer * km_per_unit,
+            litres: self.volume * litres_per_unit,
+            total_cost: self.total_cost,
+            full_tank: self.full_tank,
+            notes,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthlyFuel {
+    /// `YYYY-MM`
+    pub month: String,
+    pub fill_ups: i64,
+    pub litres: f64,
+    pub total_cost: f64,
+    /// Between the lowest and highest odometer reading logged that month
+    pub distance_km: f64,
+}
+
+/// Consumption figures only count distance between two full-tank fill-ups,
+/// so they are `None` until there are at least two.
+#[derive(Debug, Serialize)]
+pub struct FuelStats {
+    pub fill_ups: i64,
+    pub total_litres: f64,
+    pub total_cost: f64,
+    pub measured_distance_km: f64,
+    pub litres_per_100km: Option<f64>,
+    pub mpg: Option<f64>,
+    pub cost_per_km: Option<f64>,
+    pub cost_per_mile: Option<f64>,
+    pub monthly: Vec<MonthlyFuel>,
+}
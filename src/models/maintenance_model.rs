@@ -0,0 +1,108 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::models::service_record_model::ServiceCategory;
+use crate::utils::validation::{FieldError, Validator};
+
+/// "Do `task` every `interval_km` or every `interval_months`, whichever comes first".
+/// A rule either belongs to one vehicle or is a template applied to every
+/// vehicle of a make (and optionally model).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceRule {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: Option<ObjectId>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub task: String,
+    /// Service records of this category count as the task having been done
+    pub category: ServiceCategory,
+    pub interval_km: Option<f64>,
+    pub interval_months: Option<u32>,
+    pub created_at: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMaintenanceRule {
+    pub task: String,
+    pub category: ServiceCategory,
+    pub interval_km: Option<f64>,
+    pub interval_months: Option<u32>,
+}
+
+impl CreateMaintenanceRule {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        self.check(&mut v);
+        v.finish()
+    }
+
+    fn check(&self, v: &mut Validator) {
+        let task = self.task.trim();
+        v.check(!task.is_empty(), "task", "must not be blank");
+        v.check(task.chars().count() <= 100, "task", "must be at most 100 characters");
+        v.check(
+            self.interval_km.is_some() || self.interval_months.is_some(),
+            "interval",
+            "set interval_km, interval_months or both",
+        );
+        if let Some(km) = self.interval_km {
+            v.check(km.is_finite() && km > 0.0, "interval_km", "must be a positive number");
+        }
+        if let Some(months) = self.interval_months {
+            v.check((1..=240).contains(&months), "interval_months", "must be between 1 and 240");
+        }
+    }
+}
+
+/// A rule template for every vehicle of `make` (and `model`, when given)
+#[derive(Debug, Deserialize)]
+pub struct CreateMaintenanceTemplate {
+    pub make: String,
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub rule: CreateMaintenanceRule,
+}
+
+impl CreateMaintenanceTemplate {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.check(!self.make.trim().is_empty(), "make", "must not be blank");
+        if let Some(model) = &self.model {
+            v.check(!model.trim().is_empty(), "model", "must not be blank");
+        }
+        self.rule.check(&mut v);
+        v.finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DueStatus {
+    DueSoon,
+    Overdue,
+}
+
+/// Output of the reminder engine: a rule that needs attention on one vehicle.
+/// Rebuilt for each vehicle on every evaluation run.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceDueItem {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// Owner at evaluation time, for `GET /me/reminders`
+    pub user_id: ObjectId,
+    pub vehicle_id: ObjectId,
+    pub rule_id: ObjectId,
+    pub task: String,
+    pub category: ServiceCategory,
+    pub status: DueStatus,
+    pub due_km: Option<f64>,
+    pub due_date: Option<DateTime>,
+    /// Negative once overdue
+    pub remaining_km: Option<f64>,
+    pub days_remaining: Option<i64>,
+    pub current_km: Option<f64>,
+    pub last_done_at: Option<DateTime>,
+    pub last_done_km: Option<f64>,
+    pub evaluated_at: DateTime,
+}
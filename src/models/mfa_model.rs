@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+/// Second login step: the pending token from `/login` plus a TOTP or recovery code
+#[derive(Debug, Deserialize)]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+/// Disabling 2FA requires the password and a current second factor
+#[derive(Debug, Deserialize)]
+pub struct DisableMfaRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Claims of the short-lived token returned by `/login` when 2FA is on.
+/// It carries no role or `jti`, so the `AuthUser` extractor rejects it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaPendingClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub purpose: String,
+}
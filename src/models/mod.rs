@@ -1,2 +1,15 @@
+pub mod api_key_model;
+pub mod document_model;
+pub mod file_model;
+pub mod fuel_model;
+pub mod maintenance_model;
+pub mod mfa_model;
+pub mod oidc_model;
+pub mod permission_model;
+pub mod role_model;
+pub mod service_record_model;
+pub mod session_model;
+pub mod transfer_model;
 pub mod user_model;
+pub mod user_token_model;
 pub mod  vehicle_model;
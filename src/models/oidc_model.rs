@@ -0,0 +1,46 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Authorization request waiting for the IdP to redirect back.
+/// Keyed by `state`; consumed exactly once by the callback.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OidcPendingLogin {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Subset of the provider's `/.well-known/openid-configuration`
+#[derive(Debug, Deserialize, Clone)]
+pub struct OidcDiscovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcTokenResponse {
+    pub id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
@@ -0,0 +1,187 @@
+/// Named permissions, written `resource:action:scope`.
+/// An `:any` permission also grants the matching `:own` one, and `*` grants everything.
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+macro_rules! permissions {
+    ($($ty:ident => $name:literal),* $(,)?) => {
+        $(
+            #[allow(dead_code)]
+            pub struct $ty;
+
+            impl Permission for $ty {
+                const NAME: &'static str = $name;
+            }
+        )*
+
+        /// Every permission a role may be granted
+        pub const ALL_PERMISSIONS: &[&str] = &[$($name),*];
+    };
+}
+
+permissions! {
+    VehicleCreateOwn => "vehicle:create:own",
+    VehicleReadOwn => "vehicle:read:own",
+    VehicleReadAny => "vehicle:read:any",
+    VehicleUpdateOwn => "vehicle:update:own",
+    VehicleUpdateAny => "vehicle:update:any",
+    VehicleDeleteOwn => "vehicle:delete:own",
+    VehicleDeleteAny => "vehicle:delete:any",
+    VehicleRestore => "vehicle:restore",
+    UserUpdateOwn => "user:update:own",
+    UserUpdateAny => "user:update:any",
+    UserRoleAssign => "user:role:assign",
+    RoleManage => "role:manage",
+    MaintenanceTemplateManage => "maintenance:template:manage",
+    FileList => "file:list",
+}
+
+/// Grants every permission
+pub const WILDCARD: &str = "*";
+
+/// Permissions the built-in `User` role starts with
+pub const DEFAULT_USER_PERMISSIONS: &[&str] = &[
+    VehicleCreateOwn::NAME,
+    VehicleReadOwn::NAME,
+    VehicleUpdateOwn::NAME,
+    VehicleDeleteOwn::NAME,
+    UserUpdateOwn::NAME,
+];
+
+pub fn is_known_permission(name: &str) -> bool {
+    name == WILDCARD || ALL_PERMISSIONS.contains(&name)
+}
+
+fn grants(list: &[String], permission: &str) -> bool {
+    let any_scope = permission
+        .strip_suffix(":own")
+        .map(|base| format!("{}:any", base));
+
+    list.iter().any(|granted| {
+        granted == WILDCARD || granted == permission || Some(granted) == any_scope.as_ref()
+    })
+}
+
+/// The resolved permissions of a caller's role, optionally narrowed by API-key scopes
+#[derive(Debug, Clone, Default)]
+pub struct PermissionSet {
+    granted: Vec<String>,
+    scopes: Option<Vec<String>>,
+}
+
+impl PermissionSet {
+    pub fn new(permissions: Vec<String>) -> Self {
+        PermissionSet {
+            granted: permissions,
+            scopes: None,
+        }
+    }
+
+    /// Only allow what is granted by the role *and* listed in `scopes`
+    pub fn restrict_to(mut self, scopes: Vec<String>) -> Self {
+        self.scopes = Some(scopes);
+        self
+    }
+
+    pub fn allows(&self, permission: &str) -> bool {
+        grants(&self.granted, permission)
+            && self.scopes.as_ref().is_none_or(|scopes| grants(scopes, permission))
+    }
+
+    /// The first of `permissions` this set does not allow, if any.
+    /// Used to stop callers from handing out more than they hold.
+    pub fn first_missing<'a>(&self, permissions: &'a [String]) -> Option<&'a str> {
+        permissions
+            .iter()
+            .map(String::as_str)
+            .find(|permission| !self.allows(permission))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(permissions: &[&str]) -> PermissionSet {
+        PermissionSet::new(permissions.iter().map(|p| p.to_string()).collect())
+    }
+
+    fn scopes(permissions: &[&str]) -> Vec<String> {
+        permissions.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn wildcard_grants_every_permission() {
+        let admin = set(&[WILDCARD]);
+        for permission in ALL_PERMISSIONS {
+            assert!(admin.allows(permission), "{permission}");
+        }
+        assert!(admin.allows(WILDCARD));
+    }
+
+    #[test]
+    fn any_scope_grants_the_matching_own_scope_only() {
+        let moderator = set(&[VehicleReadAny::NAME]);
+        assert!(moderator.allows(VehicleReadAny::NAME));
+        assert!(moderator.allows(VehicleReadOwn::NAME));
+        assert!(!moderator.allows(VehicleUpdateOwn::NAME));
+        assert!(!moderator.allows(VehicleCreateOwn::NAME));
+
+        // :own never implies :any
+        let user = set(&[VehicleReadOwn::NAME]);
+        assert!(!user.allows(VehicleReadAny::NAME));
+    }
+
+    #[test]
+    fn unrelated_and_partial_names_do_not_match() {
+        let user = set(DEFAULT_USER_PERMISSIONS);
+        assert!(!user.allows(RoleManage::NAME));
+        assert!(!user.allows(WILDCARD));
+        assert!(!user.allows("vehicle:read"));
+        assert!(!user.allows("vehicle"));
+
+        // Only the bare `*` is a wildcard; `vehicle:*` is just an unknown name
+        let partial = set(&["vehicle:*"]);
+        assert!(!is_known_permission("vehicle:*"));
+        assert!(!partial.allows(VehicleReadOwn::NAME));
+        assert!(!partial.allows(WILDCARD));
+
+        assert!(!PermissionSet::default().allows(VehicleReadOwn::NAME));
+    }
+
+    #[test]
+    fn restrict_to_never_widens_the_role() {
+        let user = set(DEFAULT_USER_PERMISSIONS);
+
+        let narrowed = user.clone().restrict_to(scopes(&[VehicleReadOwn::NAME]));
+        assert!(narrowed.allows(VehicleReadOwn::NAME));
+        assert!(!narrowed.allows(VehicleUpdateOwn::NAME));
+
+        // A wildcard or :any scope can't grant what the role lacks
+        let wide = user.clone().restrict_to(scopes(&[WILDCARD, VehicleReadAny::NAME, RoleManage::NAME]));
+        for permission in ALL_PERMISSIONS {
+            assert_eq!(wide.allows(permission), user.allows(permission), "{permission}");
+        }
+        assert!(!wide.allows(WILDCARD));
+
+        // A key scoped to :any on a wildcard role still passes the :own check
+        let admin = set(&[WILDCARD]).restrict_to(scopes(&[VehicleReadAny::NAME]));
+        assert!(admin.allows(VehicleReadOwn::NAME));
+        assert!(!admin.allows(VehicleUpdateOwn::NAME));
+
+        assert!(!user.restrict_to(vec![]).allows(VehicleReadOwn::NAME));
+    }
+
+    #[test]
+    fn first_missing_finds_permissions_outside_the_set() {
+        let moderator = set(&[VehicleReadAny::NAME, UserRoleAssign::NAME]);
+        assert_eq!(moderator.first_missing(&scopes(&[VehicleReadOwn::NAME, VehicleReadAny::NAME])), None);
+        assert_eq!(
+            moderator.first_missing(&scopes(&[VehicleReadOwn::NAME, RoleManage::NAME])),
+            Some(RoleManage::NAME)
+        );
+        assert_eq!(moderator.first_missing(&scopes(&[WILDCARD])), Some(WILDCARD));
+        assert_eq!(set(&[WILDCARD]).first_missing(&scopes(&[WILDCARD])), None);
+    }
+}
@@ -0,0 +1,45 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A named set of permissions. `User.role` holds the role name.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Role {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub permissions: Vec<String>,
+    /// Built-in roles are seeded at startup and cannot be deleted
+    #[serde(default)]
+    pub built_in: bool,
+    pub created_at: Option<DateTime>,
+    pub updated_at: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRole {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRole {
+    pub permissions: Vec<String>,
+}
+
+/// Audit record written every time a user's role is changed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleAuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// `None` when the change came from the startup bootstrap
+    pub actor_id: Option<ObjectId>,
+    pub target_user_id: ObjectId,
+    pub previous_role: Option<String>,
+    pub new_role: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRoleRequest {
+    pub role: String,
+}
@@ -0,0 +1,191 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::validation::{optional_text, parse_date, required_text, FieldError, Validator};
+
+const MAX_SHOP_LEN: usize = 120;
+const MAX_NOTES_LEN: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceCategory {
+    OilChange,
+    Tires,
+    Brakes,
+    Inspection,
+    Repair,
+    Other,
+}
+
+impl ServiceCategory {
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "oil_change" => Some(ServiceCategory::OilChange),
+            "tires" => Some(ServiceCategory::Tires),
+            "brakes" => Some(ServiceCategory::Brakes),
+            "inspection" => Some(ServiceCategory::Inspection),
+            "repair" => Some(ServiceCategory::Repair),
+            "other" => Some(ServiceCategory::Other),
+            _ => None,
+        }
+    }
+}
+
+/// A maintenance or repair job done on a vehicle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceRecord {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: ObjectId,
+    pub date: DateTime,
+    pub odometer: i64,
+    pub category: ServiceCategory,
+    pub cost: f64,
+    pub shop: Option<String>,
+    pub notes: Option<String>,
+    /// Storage keys of uploaded receipts
+    #[serde(default)]
+    pub receipts: Vec<String>,
+    pub created_at: Option<DateTime>,
+    pub updated_at: Option<DateTime>,
+}
+
+pub struct CreateServiceRecord {
+    pub date: DateTime,
+    pub odometer: i64,
+    pub category: ServiceCategory,
+    pub cost: f64,
+    pub shop: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Partial update: only the fields that are present are changed
+pub struct UpdateServiceRecord {
+    pub date: Option<DateTime>,
+    pub odometer: Option<i64>,
+    pub category: Option<ServiceCategory>,
+    pub cost: Option<f64>,
+    pub shop: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Text fields as received in the multipart form, before parsing
+#[derive(Debug, Default)]
+pub struct ServiceRecordForm {
+    pub date: Option<String>,
+    pub odometer: Option<String>,
+    pub category: Option<String>,
+    pub cost: Option<String>,
+    pub shop: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl ServiceRecordForm {
+    /// Parse and check every field, all required
+    pub fn into_create(self) -> Result<CreateServiceRecord, Vec<FieldError>> {
+        let mut v = Validator::new();
+        let date = self.parsed_date(&mut v, true);
+        let odometer = self.parsed_odometer(&mut v, true);
+        let category = self.parsed_category(&mut v, true);
+        let cost = self.parsed_cost(&mut v, true);
+        let shop = optional_text(&mut v, "shop", self.shop, MAX_SHOP_LEN);
+        let notes = optional_text(&mut v, "notes", self.notes, MAX_NOTES_LEN);
+
+        // Every `None` above has recorded an error, so this only passes with all four set
+        v.finish()?;
+        let (Some(date), Some(odometer), Some(category), Some(cost)) = (date, odometer, category, cost)
+        else {
+            return Err(vec![]);
+        };
+
+        Ok(CreateServiceRecord {
+            date,
+            odometer,
+            category,
+            cost,
+            shop,
+            notes,
+        })
+    }
+
+    /// Parse and check only the fields that were sent
+    pub fn into_update(self) -> Result<UpdateServiceRecord, Vec<FieldError>> {
+        let mut v = Validator::new();
+        let update = UpdateServiceRecord {
+            date: self.parsed_date(&mut v, false),
+            odometer: self.parsed_odometer(&mut v, false),
+            category: self.parsed_category(&mut v, false),
+            cost: self.parsed_cost(&mut v, false),
+            shop: optional_text(&mut v, "shop", self.shop, MAX_SHOP_LEN),
+            notes: optional_text(&mut v, "notes", self.notes, MAX_NOTES_LEN),
+        };
+        v.finish()?;
+        Ok(update)
+    }
+
+    fn parsed_date(&self, v: &mut Validator, required: bool) -> Option<DateTime> {
+        let text = required_text(v, "date", self.date.as_deref(), required)?;
+        match parse_date(text) {
+            // A day of slack for time zones
+            Some(date) if date <= chrono::Utc::now() + chrono::Duration::days(1) => {
+                Some(DateTime::from_chrono(date))
+            }
+            Some(_) => {
+                v.add("date", "must not be in the future");
+                None
+            }
+            None => {
+                v.add("date", "must be YYYY-MM-DD or an RFC 3339 timestamp");
+                None
+            }
+        }
+    }
+
+    fn parsed_odometer(&self, v: &mut Validator, required: bool) -> Option<i64> {
+        let text = required_text(v, "odometer", self.odometer.as_deref(), required)?;
+        match text.trim().parse::<i64>() {
+            Ok(odometer) if odometer >= 0 => Some(odometer),
+            _ => {
+                v.add("odometer", "must be a non-negative whole number");
+                None
+            }
+        }
+    }
+
+    fn parsed_category(&self, v: &mut Validator, required: bool) -> Option<ServiceCategory> {
+        let text = required_text(v, "category", self.category.as_deref(), required)?;
+        let category = ServiceCategory::parse(text);
+        v.check(
+            category.is_some(),
+            "category",
+            "must be one of oil_change, tires, brakes, inspection, repair, other",
+        );
+        category
+    }
+
+    fn parsed_cost(&self, v: &mut Validator, required: bool) -> Option<f64> {
+        let text = required_text(v, "cost", self.cost.as_deref(), required)?;
+        match text.trim().parse::<f64>() {
+            Ok(cost) if cost.is_finite() && cost >= 0.0 => Some(cost),
+            _ => {
+                v.add("cost", "must be a non-negative number");
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryCost {
+    pub category: ServiceCategory,
+    pub total_cost: f64,
+    pub count: i64,
+}
+
+/// Spend on a vehicle, overall and per category
+#[derive(Debug, Serialize)]
+pub struct ServiceCostTotals {
+    pub total_cost: f64,
+    pub record_count: i64,
+    pub by_category: Vec<CategoryCost>,
+}
@@ -0,0 +1,32 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A login session backing one refresh-token chain.
+/// The session id is also the `jti` claim of every access token minted for it,
+/// so revoking the session invalidates those access tokens immediately.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Session {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub refresh_token_hash: String,
+    #[serde(default)]
+    pub previous_refresh_token_hash: Option<String>,
+    pub expires_at: DateTime,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
+    pub updated_at: Option<DateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutQuery {
+    /// Revoke every session of the user instead of only the current one
+    #[serde(default)]
+    pub all: bool,
+}
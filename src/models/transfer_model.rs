@@ -0,0 +1,75 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    Pending,
+    Accepted,
+    Rejected,
+    /// Withdrawn by the owner before the recipient answered
+    Cancelled,
+    Expired,
+}
+
+/// An offer to hand a vehicle over to another user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VehicleTransfer {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: ObjectId,
+    pub from_user_id: ObjectId,
+    /// `None` when no account uses `to_email`: the offer looks the same to the
+    /// sender, so it doesn't reveal who is registered, but nobody can accept it
+    pub to_user_id: Option<ObjectId>,
+    pub to_email: String,
+    pub status: TransferStatus,
+    pub created_at: DateTime,
+    /// A pending transfer past this point counts as expired
+    pub expires_at: DateTime,
+    pub responded_at: Option<DateTime>,
+}
+
+/// Body of `POST /vehicle/:id/transfer`
+#[derive(Debug, Deserialize)]
+pub struct CreateTransfer {
+    /// Email of the recipient's account
+    pub email: String,
+}
+
+/// What API responses show of a transfer; leaves out `to_user_id`, which
+/// would tell the sender whether the address belongs to an account
+#[derive(Debug, Serialize)]
+pub struct TransferSummary {
+    #[serde(rename = "_id")]
+    pub id: Option<ObjectId>,
+    pub vehicle_id: ObjectId,
+    pub from_user_id: ObjectId,
+    pub to_email: String,
+    pub status: TransferStatus,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    pub responded_at: Option<DateTime>,
+}
+
+impl From<VehicleTransfer> for TransferSummary {
+    fn from(transfer: VehicleTransfer) -> Self {
+        TransferSummary {
+            id: transfer.id,
+            vehicle_id: transfer.vehicle_id,
+            from_user_id: transfer.from_user_id,
+            to_email: transfer.to_email,
+            status: transfer.status,
+            created_at: transfer.created_at,
+            expires_at: transfer.expires_at,
+            responded_at: transfer.responded_at,
+        }
+    }
+}
+
+/// Pending transfers involving the caller
+#[derive(Debug, Serialize)]
+pub struct TransferInbox {
+    pub incoming: Vec<TransferSummary>,
+    pub outgoing: Vec<TransferSummary>,
+}
@@ -1,17 +1,35 @@
 use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone,PartialEq,Eq)]
-pub enum  UserRole {
-    Admin,
-    User,
+/// Name of the role in the `roles` collection that grants this user's permissions.
+/// Serialized as a bare string, so documents written with the old enum still load.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct UserRole(pub String);
+
+impl UserRole {
+    pub const ADMIN: &'static str = "Admin";
+    pub const USER: &'static str = "User";
+
+    pub fn admin() -> Self {
+        UserRole(Self::ADMIN.to_string())
+    }
+
+    pub fn user() -> Self {
+        UserRole(Self::USER.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 impl Default for UserRole {
     fn default() -> Self {
-        UserRole::User
+        UserRole::user()
     }
 }
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -19,19 +37,49 @@ pub struct User {
     pub name: String,
     pub email: String,
     pub password: String,
+    /// Storage key of the uploaded image
     pub profile_image: Option<String>, 
     #[serde(default)]
     pub role:UserRole,
+    /// Accounts created before verification existed are treated as verified
+    #[serde(default = "legacy_email_verified")]
+    pub email_verified: bool,
+    /// Two-factor authentication state
+    #[serde(default)]
+    pub totp_enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_secret: Option<String>,
+    /// Secret generated by `/2fa/setup`, promoted to `totp_secret` once confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_pending_secret: Option<String>,
+    /// Last accepted TOTP time step, so a code can't be replayed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp_last_step: Option<i64>,
+    #[serde(default)]
+    pub recovery_code_hashes: Vec<String>,
+    /// Consecutive failed logins; reset on success
+    #[serde(default)]
+    pub failed_login_attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<DateTime>,
+    /// Identity at an OpenID Connect provider this account is linked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc_issuer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oidc_subject: Option<String>,
     #[serde(default)]
     pub created_at: Option<DateTime>,
 }
 
+fn legacy_email_verified() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RegisterUser {
     pub name: String,
     pub email: String,
     pub password: String,
-    pub role: Option<UserRole>
 }
 
 #[derive(Debug, Deserialize)]
@@ -1,5 +1,17 @@
 use serde::{Deserialize, Serialize};
 use mongodb::bson::{oid::ObjectId, DateTime};
+use chrono::Datelike;
+
+use crate::utils::{validation::{FieldError, Validator}, vin};
+
+/// The first production automobile
+pub const MIN_VEHICLE_YEAR: i32 = 1886;
+const MAX_NAME_LEN: usize = 64;
+
+/// Latest accepted model year: manufacturers sell next year's models early
+pub fn max_vehicle_year() -> i32 {
+    chrono::Utc::now().year() + 1
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Vehicle {
@@ -9,17 +21,147 @@ pub struct Vehicle {
     pub user_id: ObjectId,
     pub make: String,
     pub model: String,
-    pub year: String,
+    /// Only `None` for legacy records whose text year could not be converted
+    #[serde(default)]
+    pub year: Option<i32>,
+    /// The unconvertible text year of such a record, kept for manual review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_year: Option<String>,
+    #[serde(default)]
+    pub vin: Option<String>,
 
+    /// Storage keys of the uploaded files
     pub files: Option<Vec<String>>, 
 
     pub created_at: Option<DateTime>,
     pub updated_at: Option<DateTime>,
+    /// Set by `DELETE /vehicle/:id`; the record is purged once the retention window passes
+    #[serde(default)]
+    pub deleted_at: Option<DateTime>,
+    /// Previous owners, oldest first; appended when a transfer is accepted
+    #[serde(default)]
+    pub ownership_history: Vec<OwnershipRecord>,
+}
+
+/// One past owner of a vehicle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OwnershipRecord {
+    pub user_id: ObjectId,
+    pub transferred_at: DateTime,
+    pub transfer_id: ObjectId,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateVehicle {
     pub make: String,
     pub model: String,
-    pub year: String,
+    pub year: Option<i32>,
+    /// A recognised VIN fills in a blank `make` / missing `year`
+    pub vin: Option<String>,
+}
+
+impl CreateVehicle {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        check_name(&mut v, "make", &self.make);
+        check_name(&mut v, "model", &self.model);
+        match self.year {
+            Some(year) => check_year(&mut v, year),
+            None => v.add("year", "is required"),
+        }
+        if let Some(raw) = &self.vin {
+            if let Err(e) = vin::normalize(raw) {
+                v.add("vin", e);
+            }
+        }
+        v.finish()
+    }
+}
+
+/// Partial update: only the fields that are present are changed
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateVehicle {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub year: Option<i32>,
+    pub vin: Option<String>,
+}
+
+impl UpdateVehicle {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        if let Some(make) = &self.make {
+            check_name(&mut v, "make", make);
+        }
+        if let Some(model) = &self.model {
+            check_name(&mut v, "model", model);
+        }
+        if let Some(year) = self.year {
+            check_year(&mut v, year);
+        }
+        if let Some(raw) = &self.vin {
+            if let Err(e) = vin::normalize(raw) {
+                v.add("vin", e);
+            }
+        }
+        v.finish()
+    }
+}
+
+fn check_name(v: &mut Validator, field: &str, value: &str) {
+    if value.trim().is_empty() {
+        v.add(field, "must not be blank");
+    } else if value.chars().count() > MAX_NAME_LEN {
+        v.add(field, format!("must be at most {} characters", MAX_NAME_LEN));
+    }
+}
+
+fn check_year(v: &mut Validator, year: i32) {
+    let max = max_vehicle_year();
+    v.check(
+        (MIN_VEHICLE_YEAR..=max).contains(&year),
+        "year",
+        &format!("must be between {} and {}", MIN_VEHICLE_YEAR, max),
+    );
+}
+
+/// Query string for `GET /vehicles`
+#[derive(Debug, Deserialize)]
+pub struct VehicleListQuery {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub year_min: Option<i32>,
+    pub year_max: Option<i32>,
+    /// Owner's user ID; only honoured for callers with `vehicle:read:any`
+    pub owner: Option<String>,
+    /// `make`, `model`, `year` or `created_at`, `-` prefix for descending.
+    /// Defaults to newest first.
+    pub sort: Option<String>,
+    /// Opaque `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub include_total: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VehiclePage {
+    pub vehicles: Vec<Vehicle>,
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+/// What can be read from a VIN without a network lookup
+#[derive(Debug, Serialize)]
+pub struct VinDecoded {
+    pub vin: String,
+    /// World manufacturer identifier, positions 1-3
+    pub wmi: String,
+    pub manufacturer: Option<String>,
+    pub country: Option<String>,
+    pub model_year: Option<i32>,
+    /// Assembly plant, position 11; meaning is manufacturer-specific
+    pub plant_code: String,
+    pub serial_number: String,
 }
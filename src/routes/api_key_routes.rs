@@ -0,0 +1,15 @@
+use axum::{
+    Router,
+    routing::{get, put},
+};
+use crate::controllers::api_key_controller::{
+    create_api_key_handler, delete_api_key_handler, list_api_keys_handler, update_api_key_handler,
+};
+use crate::db::ApiKeyDb;
+
+pub fn api_key_routes(db: ApiKeyDb) -> Router {
+    Router::new()
+        .route("/api-keys", get(list_api_keys_handler).post(create_api_key_handler))
+        .route("/api-keys/:id", put(update_api_key_handler).delete(delete_api_key_handler))
+        .with_state(db)
+}
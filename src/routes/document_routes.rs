@@ -0,0 +1,22 @@
+use axum::{Router, handler::Handler, routing::get};
+use crate::controllers::document_controller::{
+    create_document_handler, delete_document_handler, get_document_handler, list_documents_handler,
+    update_document_handler,
+};
+use crate::db::VehicleDocumentDb;
+use crate::middlewares::upload_middleware::upload_body_limit;
+
+pub fn document_routes(db: VehicleDocumentDb) -> Router {
+    Router::new()
+        .route(
+            "/vehicle/:id/documents",
+            get(list_documents_handler).post(create_document_handler.layer(upload_body_limit())),
+        )
+        .route(
+            "/vehicle/:id/documents/:document_id",
+            get(get_document_handler)
+                .put(update_document_handler.layer(upload_body_limit()))
+                .delete(delete_document_handler),
+        )
+        .with_state(db)
+}
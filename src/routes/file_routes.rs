@@ -0,0 +1,18 @@
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use crate::controllers::file_controller::{
+    download_file_handler, list_files_handler, public_file_handler, sign_file_handler,
+};
+use crate::storage::SharedFileStore;
+
+pub fn file_routes(store: SharedFileStore) -> Router {
+    Router::new()
+        .route("/files", get(list_files_handler))
+        // Keys contain `/`, so the whole rest of the path is the key
+        .route("/files/*key", get(download_file_handler))
+        .route("/signed-urls", post(sign_file_handler))
+        .route("/public/files/*key", get(public_file_handler))
+        .with_state(store)
+}
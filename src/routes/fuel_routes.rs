@@ -0,0 +1,19 @@
+use axum::{
+    Router,
+    routing::{delete, get},
+};
+use crate::controllers::fuel_controller::{
+    add_fuel_entry_handler, delete_fuel_entry_handler, fuel_stats_handler, list_fuel_entries_handler,
+};
+use crate::db::FuelLogDb;
+
+pub fn fuel_routes(db: FuelLogDb) -> Router {
+    Router::new()
+        .route(
+            "/vehicle/:id/fuel",
+            get(list_fuel_entries_handler).post(add_fuel_entry_handler),
+        )
+        .route("/vehicle/:id/fuel/stats", get(fuel_stats_handler))
+        .route("/vehicle/:id/fuel/:entry_id", delete(delete_fuel_entry_handler))
+        .with_state(db)
+}
@@ -0,0 +1,6 @@
+use axum::{Router, routing::get};
+use crate::controllers::jwks_controller::jwks_handler;
+
+pub fn jwks_routes() -> Router {
+    Router::new().route("/.well-known/jwks.json", get(jwks_handler))
+}
@@ -0,0 +1,30 @@
+use axum::{
+    Router,
+    routing::{delete, get},
+};
+use crate::controllers::maintenance_controller::{
+    create_template_handler, create_vehicle_rule_handler, delete_template_handler,
+    delete_vehicle_rule_handler, list_templates_handler, list_vehicle_rules_handler,
+    my_reminders_handler, vehicle_due_handler,
+};
+use crate::db::MaintenanceRuleDb;
+
+pub fn maintenance_routes(db: MaintenanceRuleDb) -> Router {
+    Router::new()
+        .route(
+            "/vehicle/:id/maintenance/rules",
+            get(list_vehicle_rules_handler).post(create_vehicle_rule_handler),
+        )
+        .route(
+            "/vehicle/:id/maintenance/rules/:rule_id",
+            delete(delete_vehicle_rule_handler),
+        )
+        .route("/vehicle/:id/maintenance/due", get(vehicle_due_handler))
+        .route("/me/reminders", get(my_reminders_handler))
+        .route(
+            "/maintenance/templates",
+            get(list_templates_handler).post(create_template_handler),
+        )
+        .route("/maintenance/templates/:id", delete(delete_template_handler))
+        .with_state(db)
+}
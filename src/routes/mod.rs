@@ -1,2 +1,12 @@
+pub mod api_key_routes;
+pub mod document_routes;
+pub mod file_routes;
+pub mod fuel_routes;
+pub mod jwks_routes;
+pub mod maintenance_routes;
+pub mod oidc_routes;
+pub mod role_routes;
+pub mod service_record_routes;
+pub mod transfer_routes;
 pub mod user_routes;
 pub mod vehicle_routes;
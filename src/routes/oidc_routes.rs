@@ -0,0 +1,10 @@
+use axum::{Router, routing::get};
+use crate::controllers::oidc_controller::{oidc_callback_handler, oidc_login_handler};
+use crate::db::UserDb;
+
+pub fn oidc_routes(db: UserDb) -> Router {
+    Router::new()
+        .route("/auth/oidc/login", get(oidc_login_handler))
+        .route("/auth/oidc/callback", get(oidc_callback_handler))
+        .with_state(db)
+}
@@ -0,0 +1,17 @@
+use axum::{
+    Router,
+    routing::{get, put},
+};
+use crate::controllers::role_controller::{
+    create_role_handler, delete_role_handler, list_permissions_handler, list_roles_handler,
+    update_role_handler,
+};
+use crate::db::RoleDb;
+
+pub fn role_routes(db: RoleDb) -> Router {
+    Router::new()
+        .route("/permissions", get(list_permissions_handler))
+        .route("/roles", get(list_roles_handler).post(create_role_handler))
+        .route("/roles/:name", put(update_role_handler).delete(delete_role_handler))
+        .with_state(db)
+}
@@ -0,0 +1,28 @@
+use axum::{
+    Router,
+    handler::Handler,
+    routing::get,
+};
+use crate::controllers::service_record_controller::{
+    create_service_record_handler, delete_service_record_handler, get_service_record_handler,
+    list_service_records_handler, service_cost_totals_handler, update_service_record_handler,
+};
+use crate::db::ServiceRecordDb;
+use crate::middlewares::upload_middleware::upload_body_limit;
+
+pub fn service_record_routes(db: ServiceRecordDb) -> Router {
+    Router::new()
+        .route(
+            "/vehicle/:id/services",
+            get(list_service_records_handler)
+                .post(create_service_record_handler.layer(upload_body_limit())),
+        )
+        .route("/vehicle/:id/services/totals", get(service_cost_totals_handler))
+        .route(
+            "/vehicle/:id/services/:record_id",
+            get(get_service_record_handler)
+                .put(update_service_record_handler.layer(upload_body_limit()))
+                .delete(delete_service_record_handler),
+        )
+        .with_state(db)
+}
@@ -0,0 +1,19 @@
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use crate::controllers::transfer_controller::{
+    accept_transfer_handler, cancel_transfer_handler, initiate_transfer_handler,
+    list_transfers_handler, reject_transfer_handler,
+};
+use crate::db::VehicleTransferDb;
+
+pub fn transfer_routes(db: VehicleTransferDb) -> Router {
+    Router::new()
+        .route("/vehicle/:id/transfer", post(initiate_transfer_handler))
+        .route("/transfers", get(list_transfers_handler))
+        .route("/transfers/:id/accept", post(accept_transfer_handler))
+        .route("/transfers/:id/reject", post(reject_transfer_handler))
+        .route("/transfers/:id/cancel", post(cancel_transfer_handler))
+        .with_state(db)
+}
@@ -1,11 +1,46 @@
-use axum::{Router, routing::{post,put}};
-use crate::controllers::user_controller::{register_handler, login_handler,update_user_handler};
+use axum::{Router, middleware::from_fn_with_state, routing::{get,post,put}};
+use crate::controllers::user_controller::{
+    register_handler, login_handler, update_user_handler, refresh_token_handler, logout_handler,
+    forgot_password_handler, reset_password_handler, reset_password_page_handler, verify_email_handler,
+    resend_verification_handler, assign_role_handler,
+};
+use crate::controllers::mfa_controller::{
+    mfa_login_handler, totp_confirm_handler, totp_disable_handler, totp_setup_handler,
+};
 use crate::db::UserDb;
+use crate::middlewares::rate_limit_middleware::{rate_limit, RateLimiter};
+use crate::middlewares::upload_middleware::upload_body_limit;
 
 pub fn user_routes(db: UserDb) -> Router {
+    // Per-IP throttling; /login and /login/2fa share a bucket
+    let login_limiter = RateLimiter::from_env("LOGIN_RATE_LIMIT_PER_MINUTE", 10);
+    let register_limiter = RateLimiter::from_env("REGISTER_RATE_LIMIT_PER_MINUTE", 5);
+
     Router::new()
-        .route("/register", post(register_handler))
-        .route("/login", post(login_handler))
+        .route(
+            "/register",
+            post(register_handler)
+                .layer(upload_body_limit())
+                .layer(from_fn_with_state(register_limiter, rate_limit)),
+        )
+        .route(
+            "/login",
+            post(login_handler).layer(from_fn_with_state(login_limiter.clone(), rate_limit)),
+        )
+        .route(
+            "/login/2fa",
+            post(mfa_login_handler).layer(from_fn_with_state(login_limiter, rate_limit)),
+        )
+        .route("/token/refresh", post(refresh_token_handler))
+        .route("/logout", post(logout_handler))
+        .route("/forgot-password", post(forgot_password_handler))
+        .route("/reset-password", get(reset_password_page_handler).post(reset_password_handler))
+        .route("/verify-email", get(verify_email_handler))
+        .route("/verify-email/resend", post(resend_verification_handler))
+        .route("/2fa/setup", post(totp_setup_handler))
+        .route("/2fa/confirm", post(totp_confirm_handler))
+        .route("/2fa/disable", post(totp_disable_handler))
         .route("/user/:id", put(update_user_handler))
+        .route("/users/:id/role", post(assign_role_handler))
         .with_state(db)
 }
@@ -1,13 +1,26 @@
 use axum::{
     Router,
-    routing::{post, put},
+    handler::Handler,
+    routing::{get, post},
+};
+use crate::controllers::vehicle_controller::{
+    create_vehicle_handler, decode_vin_handler, delete_vehicle_handler, get_vehicle_handler,
+    list_vehicles_handler, restore_vehicle_handler, update_vehicle_handler,
 };
-use crate::controllers::vehicle_controller::{create_vehicle_handler, update_vehicle_handler};
 use crate::db::VehicleDb;
+use crate::middlewares::upload_middleware::upload_body_limit;
 
 pub fn vehicle_routes(db: VehicleDb) -> Router {
     Router::new()
-        .route("/vehicle", post(create_vehicle_handler))
-        .route("/vehicle/:id", put(update_vehicle_handler))
+        .route("/vehicle", post(create_vehicle_handler).layer(upload_body_limit()))
+        .route(
+            "/vehicle/:id",
+            get(get_vehicle_handler)
+                .put(update_vehicle_handler.layer(upload_body_limit()))
+                .delete(delete_vehicle_handler),
+        )
+        .route("/vehicle/:id/restore", post(restore_vehicle_handler))
+        .route("/vehicles", get(list_vehicles_handler))
+        .route("/vin/:vin/decode", get(decode_vin_handler))
         .with_state(db)
 }
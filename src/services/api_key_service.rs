@@ -0,0 +1,171 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::db::ApiKeyDb;
+use crate::models::api_key_model::{ApiKey, CreateApiKey, UpdateApiKey};
+use crate::models::permission_model::is_known_permission;
+use crate::utils::crypto::{random_token, sha256_hex};
+
+const KEY_PREFIX: &str = "ak";
+
+fn validate_scopes(scopes: &[String]) -> Result<(), String> {
+    if scopes.is_empty() {
+        return Err("At least one scope is required".to_string());
+    }
+    match scopes.iter().find(|s| !is_known_permission(s)) {
+        Some(unknown) => Err(format!("Unknown scope: {}", unknown)),
+        None => Ok(()),
+    }
+}
+
+/// Split `ak_<prefix>_<secret>` into its prefix and secret
+fn parse_key(key: &str) -> Option<(&str, &str)> {
+    let rest = key.strip_prefix(KEY_PREFIX)?.strip_prefix('_')?;
+    rest.split_once('_')
+}
+
+/// Create a key for `user_id`. Returns the stored record and the full key,
+/// which is only ever shown this once.
+pub async fn create_api_key(
+    db: &ApiKeyDb,
+    user_id: &str,
+    payload: CreateApiKey,
+) -> Result<(ApiKey, String), String> {
+    let user_obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Key name is required".to_string());
+    }
+    validate_scopes(&payload.scopes)?;
+
+    let expires_at = match payload.expires_in_days {
+        Some(days) if days <= 0 => return Err("expires_in_days must be positive".to_string()),
+        Some(days) => Some(DateTime::from_chrono(chrono::Utc::now() + chrono::Duration::days(days))),
+        None => None,
+    };
+
+    // Prefix is alphanumeric so it can't contain the `_` separator
+    let prefix: String = random_token(12)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(8)
+        .collect();
+    let secret = random_token(32);
+
+    let key = ApiKey {
+        id: Some(ObjectId::new()),
+        user_id: user_obj_id,
+        name,
+        prefix: prefix.clone(),
+        secret_hash: sha256_hex(&secret),
+        scopes: payload.scopes,
+        expires_at,
+        last_used_at: None,
+        created_at: Some(DateTime::now()),
+    };
+
+    let collection = db.lock().await;
+    collection
+        .insert_one(&key, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok((key, format!("{}_{}_{}", KEY_PREFIX, prefix, secret)))
+}
+
+pub async fn list_api_keys(db: &ApiKeyDb, user_id: &str) -> Result<Vec<ApiKey>, String> {
+    let user_obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+
+    let collection = db.lock().await;
+    let cursor = collection
+        .find(doc! { "user_id": user_obj_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    cursor.try_collect().await.map_err(|e| e.to_string())
+}
+
+pub async fn update_api_key(
+    db: &ApiKeyDb,
+    user_id: &str,
+    key_id: &str,
+    payload: UpdateApiKey,
+) -> Result<ApiKey, String> {
+    let user_obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let key_obj_id = ObjectId::parse_str(key_id).map_err(|_| "Invalid key ID".to_string())?;
+
+    let mut update_doc = Document::new();
+    if let Some(name) = payload.name {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Key name is required".to_string());
+        }
+        update_doc.insert("name", name);
+    }
+    if let Some(scopes) = payload.scopes {
+        validate_scopes(&scopes)?;
+        update_doc.insert("scopes", scopes);
+    }
+    if update_doc.is_empty() {
+        return Err("Nothing to update".to_string());
+    }
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! { "_id": key_obj_id, "user_id": user_obj_id },
+            doc! { "$set": update_doc },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("API key not found".to_string())
+}
+
+pub async fn delete_api_key(db: &ApiKeyDb, user_id: &str, key_id: &str) -> Result<(), String> {
+    let user_obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let key_obj_id = ObjectId::parse_str(key_id).map_err(|_| "Invalid key ID".to_string())?;
+
+    let collection = db.lock().await;
+    let result = collection
+        .delete_one(doc! { "_id": key_obj_id, "user_id": user_obj_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.deleted_count == 0 {
+        return Err("API key not found".to_string());
+    }
+
+    Ok(())
+}
+
+/// Resolve a presented key to its record, stamping `last_used_at`.
+/// Unknown, malformed and expired keys are all reported the same way.
+pub async fn authenticate_api_key(db: &ApiKeyDb, key: &str) -> Result<ApiKey, String> {
+    let (prefix, secret) = parse_key(key.trim()).ok_or("Invalid API key")?;
+    let now = DateTime::now();
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! {
+                "prefix": prefix,
+                "secret_hash": sha256_hex(secret),
+                "$or": [
+                    { "expires_at": null },
+                    { "expires_at": { "$gt": now } },
+                ],
+            },
+            doc! { "$set": { "last_used_at": now } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invalid API key".to_string())
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::env;
+
+use futures_util::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::db::{UserDb, VehicleDb, VehicleDocumentDb};
+use crate::mailer::{EmailMessage, SharedMailer};
+use crate::models::document_model::{CreateVehicleDocument, UpdateVehicleDocument, VehicleDocument};
+use crate::models::vehicle_model::Vehicle;
+use crate::services::vehicle_service::accessible_vehicle_id;
+use crate::storage::{discard, SharedFileStore};
+
+/// Storage key prefix for document scans
+pub const DOCUMENT_KEY_PREFIX: &str = "documents";
+
+/// How far ahead owners are warned about expiring documents
+/// (`DOCUMENT_EXPIRY_WINDOW_DAYS`, default 30)
+fn expiry_window() -> chrono::Duration {
+    let days = env::var("DOCUMENT_EXPIRY_WINDOW_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(30);
+    chrono::Duration::days(days)
+}
+
+/// How often the expiry check runs, in hours (`DOCUMENT_EXPIRY_CHECK_INTERVAL_HOURS`, default 24)
+fn expiry_check_interval() -> std::time::Duration {
+    let hours = env::var("DOCUMENT_EXPIRY_CHECK_INTERVAL_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|h| *h > 0)
+        .unwrap_or(24);
+    std::time::Duration::from_secs(hours * 60 * 60)
+}
+
+fn parse_document_id(document_id: &str) -> Result<ObjectId, String> {
+    ObjectId::parse_str(document_id).map_err(|_| "Invalid document ID".to_string())
+}
+
+/// Attach a document to a vehicle. `None` if the vehicle isn't accessible.
+pub async fn create_document(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+    payload: CreateVehicleDocument,
+) -> Result<Option<VehicleDocument>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let document = VehicleDocument {
+        id: Some(ObjectId::new()),
+        vehicle_id,
+        kind: payload.kind,
+        issuer: payload.issuer,
+        number: payload.number,
+        issued_on: payload.issued_on,
+        expires_on: payload.expires_on,
+        file: payload.file,
+        expiry_notified_at: None,
+        created_at: Some(DateTime::now()),
+        updated_at: Some(DateTime::now()),
+    };
+
+    let collection = db.lock().await;
+    collection
+        .insert_one(&document, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(document))
+}
+
+/// The vehicle's documents, soonest expiry first
+pub async fn list_documents(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vec<VehicleDocument>>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+    let documents = collection
+        .find(
+            doc! { "vehicle_id": vehicle_id },
+            FindOptions::builder()
+                .sort(doc! { "expires_on": 1, "_id": 1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(documents))
+}
+
+pub async fn get_document(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    document_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<VehicleDocument>, String> {
+    let document_id = parse_document_id(document_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+    collection
+        .find_one(doc! { "_id": document_id, "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Change the given fields. A new file replaces the old one, which is deleted.
+pub async fn update_document(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    store: &SharedFileStore,
+    vehicle_id: &str,
+    document_id: &str,
+    owner: Option<ObjectId>,
+    payload: UpdateVehicleDocument,
+) -> Result<Option<VehicleDocument>, String> {
+    let document_id = parse_document_id(document_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let mut set = doc! { "updated_at": DateTime::now() };
+    if let Some(kind) = payload.kind {
+        set.insert("kind", bson::to_bson(&kind).map_err(|e| e.to_string())?);
+    }
+    if let Some(issuer) = payload.issuer {
+        set.insert("issuer", issuer);
+    }
+    if let Some(number) = payload.number {
+        set.insert("number", number);
+    }
+    if let Some(issued_on) = payload.issued_on {
+        set.insert("issued_on", issued_on);
+    }
+    if let Some(expires_on) = payload.expires_on {
+        set.insert("expires_on", expires_on);
+        // A renewed document gets its own warning
+        set.insert("expiry_notified_at", bson::Bson::Null);
+    }
+    if let Some(file) = &payload.file {
+        set.insert("file", file);
+    }
+
+    let collection = db.lock().await;
+    let before = collection
+        .find_one_and_update(
+            doc! { "_id": document_id, "vehicle_id": vehicle_id },
+            doc! { "$set": set },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::Before)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(before) = before else {
+        return Ok(None);
+    };
+    let updated = collection
+        .find_one(doc! { "_id": document_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(collection);
+
+    if let (Some(_), Some(old)) = (&payload.file, before.file) {
+        discard(store, &old).await;
+    }
+
+    Ok(updated)
+}
+
+/// Delete a document and its file. Returns `false` if nothing matched.
+pub async fn delete_document(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    store: &SharedFileStore,
+    vehicle_id: &str,
+    document_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<bool, String> {
+    let document_id = parse_document_id(document_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(false);
+    };
+
+    let deleted = {
+        let collection = db.lock().await;
+        collection
+            .find_one_and_delete(doc! { "_id": document_id, "vehicle_id": vehicle_id }, None)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    let Some(document) = deleted else {
+        return Ok(false);
+    };
+    if let Some(key) = document.file {
+        discard(store, &key).await;
+    }
+
+    Ok(true)
+}
+
+/// Flag documents that expire within the warning window (or already have) and
+/// mail each owner one summary. Documents are only flagged once the mail went
+/// out, so a failed send is retried on the next run. Returns how many were flagged.
+pub async fn notify_expiring_documents(
+    db: &VehicleDocumentDb,
+    vehicles: &VehicleDb,
+    users: &UserDb,
+    mailer: &SharedMailer,
+) -> Result<usize, String> {
+    let cutoff = DateTime::from_chrono(chrono::Utc::now() + expiry_window());
+    let expiring: Vec<VehicleDocument> = {
+        let collection = db.lock().await;
+        collection
+            .find(
+                doc! {
+                    "expires_on": { "$ne": null, "$lte": cutoff },
+                    "expiry_notified_at": null,
+                },
+                FindOptions::builder().sort(doc! { "expires_on": 1 }).build(),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    if expiring.is_empty() {
+        return Ok(0);
+    }
+
+    let vehicle_ids: Vec<ObjectId> = expiring.iter().map(|d| d.vehicle_id).collect();
+    let owned: HashMap<ObjectId, Vehicle> = {
+        let collection = vehicles.lock().await;
+        collection
+            .find(doc! { "_id": { "$in": vehicle_ids }, "deleted_at": null }, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect::<Vec<Vehicle>>()
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter_map(|v| Some((v.id?, v)))
+            .collect()
+    };
+
+    let mut by_owner: HashMap<ObjectId, Vec<(&VehicleDocument, &Vehicle)>> = HashMap::new();
+    for document in &expiring {
+        if let Some(vehicle) = owned.get(&document.vehicle_id) {
+            by_owner.entry(vehicle.user_id).or_default().push((document, vehicle));
+        }
+    }
+
+    let mut flagged = 0;
+    for (user_id, documents) in by_owner {
+        let user = {
+            let collection = users.lock().await;
+            collection
+                .find_one(doc! { "_id": user_id }, None)
+                .await
+                .map_err(|e| e.to_string())?
+        };
+        let Some(user) = user else { continue };
+
+        let now = chrono::Utc::now();
+        let lines: Vec<String> = documents
+            .iter()
+            .map(|(document, vehicle)| {
+                let expires = document.expires_on.map(|d| d.to_chrono()).unwrap_or(now);
+                let when = if expires <= now { "expired" } else { "expires" };
+                format!(
+                    "- {} {} {}{}: {} {}",
+                    vehicle.make,
+                    vehicle.model,
+                    document.kind.as_str(),
+                    document.number.as_deref().map(|n| format!(" ({})", n)).unwrap_or_default(),
+                    when,
+                    expires.format("%Y-%m-%d")
+                )
+            })
+            .collect();
+
+        let sent = mailer
+            .send(EmailMessage {
+                to: user.email,
+                subject: "Vehicle documents expiring soon".to_string(),
+                body: format!(
+                    "Hi {},\n\nThese vehicle documents need renewing:\n\n{}\n\nUpload the renewed documents to stop these reminders.",
+                    user.name,
+                    lines.join("\n")
+                ),
+            })
+            .await;
+        if let Err(e) = sent {
+            eprintln!("Failed to send document expiry notice: {}", e);
+            continue;
+        }
+
+        let ids: Vec<ObjectId> = documents.iter().filter_map(|(d, _)| d.id).collect();
+        let collection = db.lock().await;
+        collection
+            .update_many(
+                doc! { "_id": { "$in": &ids } },
+                doc! { "$set": { "expiry_notified_at": DateTime::now() } },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        flagged += ids.len();
+    }
+
+    Ok(flagged)
+}
+
+/// Start the background task that warns owners about expiring documents
+pub fn spawn_document_expiry_check(
+    db: VehicleDocumentDb,
+    vehicles: VehicleDb,
+    users: UserDb,
+    mailer: SharedMailer,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(expiry_check_interval());
+        loop {
+            interval.tick().await;
+            match notify_expiring_documents(&db, &vehicles, &users, &mailer).await {
+                Ok(0) => {}
+                Ok(count) => println!("Flagged {} expiring document(s)", count),
+                Err(e) => eprintln!("Document expiry check failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Remove every document of a vehicle, returning the keys of their files for
+/// the caller to delete from the store
+pub async fn delete_vehicle_documents(
+    db: &VehicleDocumentDb,
+    vehicle_id: ObjectId,
+) -> Result<Vec<String>, String> {
+    let collection = db.lock().await;
+    let documents: Vec<VehicleDocument> = collection
+        .find(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    collection
+        .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(documents.into_iter().filter_map(|d| d.file).collect())
+}
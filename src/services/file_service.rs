@@ -0,0 +1,276 @@
+use std::env;
+
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+
+use crate::db::{ServiceRecordDb, UserDb, VehicleDb, VehicleDocumentDb};
+use crate::mailer::app_base_url;
+use crate::models::file_model::{Disposition, SignedFileUrl};
+use crate::services::document_service::DOCUMENT_KEY_PREFIX;
+use crate::services::service_record_service::RECEIPT_KEY_PREFIX;
+use crate::services::vehicle_service::VEHICLE_KEY_PREFIX;
+use crate::utils::signed_url;
+
+/// Default lifetime of a signed file link, in seconds (`SIGNED_URL_TTL_SECONDS`, default 900)
+fn signed_url_ttl() -> i64 {
+    env::var("SIGNED_URL_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(15 * 60)
+}
+
+/// Longest lifetime a caller may ask for, in seconds (`SIGNED_URL_MAX_TTL_SECONDS`, default 604800)
+fn signed_url_max_ttl() -> i64 {
+    env::var("SIGNED_URL_MAX_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(7 * 24 * 60 * 60)
+}
+
+/// The collections whose records point at stored files
+#[derive(Clone)]
+pub struct FileOwnerDbs {
+    pub vehicles: VehicleDb,
+    pub users: UserDb,
+    pub services: ServiceRecordDb,
+    pub documents: VehicleDocumentDb,
+}
+
+/// Whose permission a stored file is read under
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileOwner {
+    /// Vehicle photo, receipt or document scan, owned by this user
+    Vehicle(ObjectId),
+    /// This user's profile image
+    User(ObjectId),
+}
+
+/// Where uploads were written before they went through a `FileStore`
+const LEGACY_UPLOAD_DIR: &str = "./uploads/";
+
+/// `value` with a leading `./uploads/` removed, as an aggregation expression
+fn strip_legacy_dir(value: &str) -> Bson {
+    let len = LEGACY_UPLOAD_DIR.len() as i32;
+    Bson::Document(doc! {
+        "$cond": [
+            { "$eq": [{ "$substrCP": [value, 0, len] }, LEGACY_UPLOAD_DIR] },
+            { "$substrCP": [value, len, { "$strLenCP": value }] },
+            value,
+        ]
+    })
+}
+
+fn legacy_filter(field: &str) -> Document {
+    doc! { field: { "$regex": "^\\./uploads/" } }
+}
+
+/// One-off rewrite of the filesystem paths stored by older records into
+/// storage keys. With the local backend rooted at `./uploads` (the default)
+/// the keys point at the same files; other backends need the files copied
+/// over separately. Returns how many records were changed.
+pub async fn migrate_upload_paths(
+    vehicles: &VehicleDb,
+    users: &UserDb,
+    services: &ServiceRecordDb,
+    documents: &VehicleDocumentDb,
+) -> Result<u64, String> {
+    let mapped = |field: &str| {
+        doc! {
+            "$set": {
+                field: {
+                    "$map": {
+                        "input": format!("${}", field),
+                        "as": "path",
+                        "in": strip_legacy_dir("$$path"),
+                    }
+                }
+            }
+        }
+    };
+    let single = |field: &str| doc! { "$set": { field: strip_legacy_dir(&format!("${}", field)) } };
+
+    let mut migrated = 0;
+    {
+        let collection = vehicles.lock().await;
+        migrated += collection
+            .update_many(legacy_filter("files"), vec![mapped("files")], None)
+            .await
+            .map_err(|e| e.to_string())?
+            .modified_count;
+    }
+    {
+        let collection = users.lock().await;
+        migrated += collection
+            .update_many(legacy_filter("profile_image"), vec![single("profile_image")], None)
+            .await
+            .map_err(|e| e.to_string())?
+            .modified_count;
+    }
+    {
+        let collection = services.lock().await;
+        migrated += collection
+            .update_many(legacy_filter("receipts"), vec![mapped("receipts")], None)
+            .await
+            .map_err(|e| e.to_string())?
+            .modified_count;
+    }
+    {
+        let collection = documents.lock().await;
+        migrated += collection
+            .update_many(legacy_filter("file"), vec![single("file")], None)
+            .await
+            .map_err(|e| e.to_string())?
+            .modified_count;
+    }
+
+    Ok(migrated)
+}
+
+/// The owner of the vehicle with this id, unless the vehicle was deleted
+async fn vehicle_owner(vehicles: &VehicleDb, vehicle_id: ObjectId) -> Result<Option<FileOwner>, String> {
+    let collection = vehicles.lock().await;
+    let vehicle = collection
+        .find_one(doc! { "_id": vehicle_id, "deleted_at": null }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(vehicle.map(|v| FileOwner::Vehicle(v.user_id)))
+}
+
+/// Find the record that references `key`, going by its prefix. Keys without a
+/// known prefix are profile images (older ones were stored at the root).
+/// `None` if nothing live refers to the file.
+pub async fn file_owner(dbs: &FileOwnerDbs, key: &str) -> Result<Option<FileOwner>, String> {
+    let FileOwnerDbs { vehicles, users, services, documents } = dbs;
+    let prefix = key.split_once('/').map(|(prefix, _)| prefix).unwrap_or_default();
+
+    match prefix {
+        VEHICLE_KEY_PREFIX => {
+            let collection = vehicles.lock().await;
+            let vehicle = collection
+                .find_one(doc! { "files": key, "deleted_at": null }, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(vehicle.map(|v| FileOwner::Vehicle(v.user_id)))
+        }
+        RECEIPT_KEY_PREFIX => {
+            let record = {
+                let collection = services.lock().await;
+                collection
+                    .find_one(doc! { "receipts": key }, None)
+                    .await
+                    .map_err(|e| e.to_string())?
+            };
+            match record {
+                Some(record) => vehicle_owner(vehicles, record.vehicle_id).await,
+                None => Ok(None),
+            }
+        }
+        DOCUMENT_KEY_PREFIX => {
+            let document = {
+                let collection = documents.lock().await;
+                collection
+                    .find_one(doc! { "file": key }, None)
+                    .await
+                    .map_err(|e| e.to_string())?
+            };
+            match document {
+                Some(document) => vehicle_owner(vehicles, document.vehicle_id).await,
+                None => Ok(None),
+            }
+        }
+        _ => {
+            let collection = users.lock().await;
+            let user = collection
+                .find_one(doc! { "profile_image": key }, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(user.and_then(|u| u.id).map(FileOwner::User))
+        }
+    }
+}
+
+/// A public link to `key` that works until it expires. The caller must already
+/// have checked access to the file.
+pub fn signed_file_url(
+    key: &str,
+    disposition: Option<Disposition>,
+    expires_in: Option<i64>,
+) -> Result<SignedFileUrl, String> {
+    let max_ttl = signed_url_max_ttl();
+    let ttl = expires_in.unwrap_or_else(signed_url_ttl);
+    if ttl <= 0 || ttl > max_ttl {
+        return Err(format!("expires_in must be between 1 and {} seconds", max_ttl));
+    }
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(ttl);
+    let expires = expires_at.timestamp();
+
+    let mut url = reqwest::Url::parse(&app_base_url()).map_err(|e| format!("Invalid APP_BASE_URL: {}", e))?;
+    url.path_segments_mut()
+        .map_err(|_| "APP_BASE_URL cannot have a path".to_string())?
+        .pop_if_empty()
+        .extend(["api", "v1", "public", "files"])
+        .extend(key.split('/'));
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("expires", &expires.to_string());
+        if let Some(disposition) = disposition {
+            query.append_pair("disposition", disposition.as_str());
+        }
+        query.append_pair("signature", &signed_url::sign(key, expires, disposition));
+    }
+
+    Ok(SignedFileUrl { url: url.to_string(), expires_at })
+}
+
+/// Whether a signed link is genuine and still valid
+pub fn check_signed_url(
+    key: &str,
+    expires: i64,
+    disposition: Option<Disposition>,
+    signature: &str,
+) -> bool {
+    chrono::Utc::now().timestamp() <= expires && signed_url::verify(key, expires, disposition, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_links_are_refused() {
+        signed_url::init_for_tests();
+        let key = "documents/3f2a_policy.pdf";
+        let past = chrono::Utc::now().timestamp() - 1;
+        let signature = signed_url::sign(key, past, None);
+
+        assert!(signed_url::verify(key, past, None, &signature));
+        assert!(!check_signed_url(key, past, None, &signature));
+    }
+
+    #[test]
+    fn issued_links_check_out_until_they_expire() {
+        signed_url::init_for_tests();
+        let key = "documents/3f2a_policy.pdf";
+        let signed = signed_file_url(key, Some(Disposition::Attachment), Some(60)).unwrap();
+
+        let url = reqwest::Url::parse(&signed.url).unwrap();
+        assert!(url.path().ends_with("/api/v1/public/files/documents/3f2a_policy.pdf"));
+        let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let expires: i64 = query["expires"].parse().unwrap();
+        assert_eq!(expires, signed.expires_at.timestamp());
+        assert_eq!(query["disposition"], "attachment");
+
+        assert!(check_signed_url(key, expires, Some(Disposition::Attachment), &query["signature"]));
+        assert!(!check_signed_url(key, expires, None, &query["signature"]));
+    }
+
+    #[test]
+    fn link_lifetime_must_be_in_range() {
+        signed_url::init_for_tests();
+        assert!(signed_file_url("documents/a.pdf", None, Some(0)).is_err());
+        assert!(signed_file_url("documents/a.pdf", None, Some(-5)).is_err());
+        assert!(signed_file_url("documents/a.pdf", None, Some(signed_url_max_ttl() + 1)).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, Bson, DateTime, Document};
+use mongodb::options::{FindOneOptions, FindOptions};
+
+use crate::db::{FuelLogDb, VehicleDb};
+use crate::models::fuel_model::{
+    FuelEntry, FuelStats, MonthlyFuel, NewFuelEntry, KM_PER_MILE, LITRES_PER_US_GALLON,
+};
+use crate::services::vehicle_service::{accessible_vehicle_id, VehicleError};
+use crate::utils::validation::FieldError;
+
+/// Log a fill-up. The reading must fit between the entries logged before and
+/// after its date, so the odometer never goes backwards; the collection lock
+/// is held from that check through the insert.
+pub async fn add_fuel_entry(
+    db: &FuelLogDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+    entry: NewFuelEntry,
+) -> Result<Option<FuelEntry>, VehicleError> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+
+    let previous = collection
+        .find_one(
+            doc! { "vehicle_id": vehicle_id, "date": { "$lte": entry.date } },
+            FindOneOptions::builder()
+                .sort(doc! { "date": -1, "odometer_km": -1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let next = collection
+        .find_one(
+            doc! { "vehicle_id": vehicle_id, "date": { "$gt": entry.date } },
+            FindOneOptions::builder()
+                .sort(doc! { "date": 1, "odometer_km": 1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(previous) = previous.filter(|p| entry.odometer_km < p.odometer_km) {
+        return Err(odometer_error(format!(
+            "must be at least {:.1} km, the reading logged on {}",
+            previous.odometer_km,
+            previous.date.to_chrono().format("%Y-%m-%d")
+        )));
+    }
+    if let Some(next) = next.filter(|n| entry.odometer_km > n.odometer_km) {
+        return Err(odometer_error(format!(
+            "must be at most {:.1} km, the reading logged on {}",
+            next.odometer_km,
+            next.date.to_chrono().format("%Y-%m-%d")
+        )));
+    }
+
+    let record = FuelEntry {
+        id: Some(ObjectId::new()),
+        vehicle_id,
+        date: entry.date,
+        odometer_km: entry.odometer_km,
+        litres: entry.litres,
+        total_cost: entry.total_cost,
+        full_tank: entry.full_tank,
+        notes: entry.notes,
+        created_at: Some(DateTime::now()),
+    };
+
+    collection
+        .insert_one(&record, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(record))
+}
+
+fn odometer_error(message: String) -> VehicleError {
+    VehicleError::Invalid(vec![FieldError {
+        field: "odometer".to_string(),
+        message,
+    }])
+}
+
+/// The vehicle's fuel log, most recent first
+pub async fn list_fuel_entries(
+    db: &FuelLogDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vec<FuelEntry>>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+    let entries = collection
+        .find(
+            doc! { "vehicle_id": vehicle_id },
+            FindOptions::builder()
+                .sort(doc! { "date": -1, "odometer_km": -1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(entries))
+}
+
+/// Remove one fill-up. Returns `false` if nothing matched.
+pub async fn delete_fuel_entry(
+    db: &FuelLogDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    entry_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<bool, String> {
+    let entry_id = ObjectId::parse_str(entry_id).map_err(|_| "Invalid fuel entry ID".to_string())?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(false);
+    };
+
+    let collection = db.lock().await;
+    let result = collection
+        .delete_one(doc! { "_id": entry_id, "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.deleted_count == 1)
+}
+
+/// Consumption, cost per distance and monthly totals, computed in the database.
+///
+/// Consumption uses the full-tank method: the fuel bought after one full
+/// fill-up, up to and including the next, was burned over the distance
+/// between the two. Partial fill-ups are folded into the next full one, and
+/// anything before the first full tank is ignored.
+pub async fn fuel_stats(
+    db: &FuelLogDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<FuelStats>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let totals_pipeline = vec![
+        doc! { "$match": { "vehicle_id": vehicle_id } },
+        doc! {
+            "$group": {
+                "_id": null,
+                "fill_ups": { "$sum": 1 },
+                "litres": { "$sum": "$litres" },
+                "cost": { "$sum": "$total_cost" },
+            }
+        },
+    ];
+
+    let before_this = doc! { "documents": ["unbounded", -1] };
+    let measured_pipeline = vec![
+        doc! { "$match": { "vehicle_id": vehicle_id } },
+        doc! {
+            "$setWindowFields": {
+                "sortBy": { "odometer_km": 1, "date": 1 },
+                "output": {
+                    // Full tanks before this entry: entries sharing a value form
+                    // one stretch that ends at the next full tank
+                    "segment": {
+                        "$sum": { "$cond": ["$full_tank", 1, 0] },
+                        "window": before_this.clone(),
+                    },
+                    // Odometer at the full tank that started the stretch
+                    "start_km": {
+                        "$max": { "$cond": ["$full_tank", "$odometer_km", null] },
+                        "window": before_this,
+                    },
+                }
+            }
+        },
+        doc! { "$match": { "start_km": { "$ne": null } } },
+        doc! {
+            "$group": {
+                "_id": "$segment",
+                "start_km": { "$first": "$start_km" },
+                "end_km": { "$max": "$odometer_km" },
+                "litres": { "$sum": "$litres" },
+                "cost": { "$sum": "$total_cost" },
+                "closed": { "$max": "$full_tank" },
+            }
+        },
+        // A stretch still waiting for its closing full tank can't be measured yet
+        doc! { "$match": { "closed": true } },
+        doc! {
+            "$group": {
+                "_id": null,
+                "distance_km": { "$sum": { "$subtract": ["$end_km", "$start_km"] } },
+                "litres": { "$sum": "$litres" },
+                "cost": { "$sum": "$cost" },
+            }
+        },
+    ];
+
+    let monthly_pipeline = vec![
+        doc! { "$match": { "vehicle_id": vehicle_id } },
+        doc! {
+            "$group": {
+                "_id": { "$dateToString": { "format": "%Y-%m", "date": "$date" } },
+                "fill_ups": { "$sum": 1 },
+                "litres": { "$sum": "$litres" },
+                "cost": { "$sum": "$total_cost" },
+                "min_km": { "$min": "$odometer_km" },
+                "max_km": { "$max": "$odometer_km" },
+            }
+        },
+        doc! { "$sort": { "_id": 1 } },
+    ];
+
+    let totals = aggregate(db, totals_pipeline).await?.pop().unwrap_or_default();
+    let measured = aggregate(db, measured_pipeline).await?.pop().unwrap_or_default();
+    let monthly = aggregate(db, monthly_pipeline).await?;
+
+    let distance_km = number(&measured, "distance_km");
+    let measured_litres = number(&measured, "litres");
+    let measured_cost = number(&measured, "cost");
+    let measured = distance_km > 0.0;
+
+    Ok(Some(FuelStats {
+        fill_ups: number(&totals, "fill_ups") as i64,
+        total_litres: number(&totals, "litres"),
+        total_cost: number(&totals, "cost"),
+        measured_distance_km: distance_km,
+        litres_per_100km: measured.then(|| measured_litres / distance_km * 100.0),
+        mpg: (measured && measured_litres > 0.0).then(|| {
+            (distance_km / KM_PER_MILE) / (measured_litres / LITRES_PER_US_GALLON)
+        }),
+        cost_per_km: measured.then(|| measured_cost / distance_km),
+        cost_per_mile: measured.then(|| measured_cost / (distance_km / KM_PER_MILE)),
+        monthly: monthly
+            .iter()
+            .map(|month| MonthlyFuel {
+                month: month.get_str("_id").unwrap_or_default().to_string(),
+                fill_ups: number(month, "fill_ups") as i64,
+                litres: number(month, "litres"),
+                total_cost: number(month, "cost"),
+                distance_km: number(month, "max_km") - number(month, "min_km"),
+            })
+            .collect(),
+    }))
+}
+
+async fn aggregate(db: &FuelLogDb, pipeline: Vec<Document>) -> Result<Vec<Document>, String> {
+    let collection = db.lock().await;
+    collection
+        .aggregate(pipeline, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Aggregation sums come back as whichever numeric type fits
+fn number(doc: &Document, key: &str) -> f64 {
+    match doc.get(key) {
+        Some(Bson::Double(v)) => *v,
+        Some(Bson::Int32(v)) => *v as f64,
+        Some(Bson::Int64(v)) => *v as f64,
+        _ => 0.0,
+    }
+}
+
+/// Remove the whole fuel log of a vehicle
+pub async fn delete_vehicle_fuel_entries(db: &FuelLogDb, vehicle_id: ObjectId) -> Result<(), String> {
+    let collection = db.lock().await;
+    collection
+        .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+
+    fn fill_up(date: &str, odometer_km: f64, litres: f64, total_cost: f64, full_tank: bool) -> NewFuelEntry {
+        NewFuelEntry {
+            date: DateTime::parse_rfc3339_str(format!("{date}T12:00:00Z")).unwrap(),
+            odometer_km,
+            litres,
+            total_cost,
+            full_tank,
+            notes: None,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[tokio::test]
+    async fn stats_measure_only_closed_full_tank_segments() {
+        let Some(database) = test_database().await else { return };
+        let fuel: FuelLogDb = shared(&database, "fuel_entries");
+        let vehicles: VehicleDb = shared(&database, "vehicles");
+        let vehicle_id = ObjectId::new();
+        database
+            .collection::<Document>("vehicles")
+            .insert_one(doc! { "_id": vehicle_id, "user_id": ObjectId::new(), "make": "Make", "model": "Model" }, None)
+            .await
+            .unwrap();
+        let id = vehicle_id.to_hex();
+
+        let series = [
+            // Before the first full tank: counted in totals, never measured
+            fill_up("2024-01-05", 10_000.0, 30.0, 45.0, false),
+            fill_up("2024-01-10", 10_100.0, 40.0, 60.0, true),
+            // Partial fill-up folded into the next full one: 600 km on 40 L
+            fill_up("2024-01-20", 10_400.0, 15.0, 22.5, false),
+            fill_up("2024-02-01", 10_700.0, 25.0, 37.5, true),
+            // 500 km on 30 L
+            fill_up("2024-02-15", 11_200.0, 30.0, 45.0, true),
+            // Still waiting for the next full tank
+            fill_up("2024-02-20", 11_300.0, 10.0, 15.0, false),
+        ];
+        for entry in series {
+            add_fuel_entry(&fuel, &vehicles, &id, None, entry).await.unwrap().unwrap();
+        }
+
+        let stats = fuel_stats(&fuel, &vehicles, &id, None).await.unwrap().unwrap();
+        assert_eq!(stats.fill_ups, 6);
+        assert_close(stats.total_litres, 150.0);
+        assert_close(stats.total_cost, 225.0);
+        assert_close(stats.measured_distance_km, 1_100.0);
+        assert_close(stats.litres_per_100km.unwrap(), 70.0 / 1_100.0 * 100.0);
+        assert_close(stats.cost_per_km.unwrap(), 105.0 / 1_100.0);
+        assert_close(
+            stats.mpg.unwrap(),
+            (1_100.0 / KM_PER_MILE) / (70.0 / LITRES_PER_US_GALLON),
+        );
+
+        let months: Vec<_> = stats
+            .monthly
+            .iter()
+            .map(|m| (m.month.as_str(), m.fill_ups, m.litres, m.total_cost, m.distance_km))
+            .collect();
+        assert_eq!(
+            months,
+            [("2024-01", 3, 85.0, 127.5, 400.0), ("2024-02", 3, 65.0, 97.5, 600.0)]
+        );
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn readings_that_roll_the_odometer_back_are_rejected() {
+        let Some(database) = test_database().await else { return };
+        let fuel: FuelLogDb = shared(&database, "fuel_entries");
+        let vehicles: VehicleDb = shared(&database, "vehicles");
+        let vehicle_id = ObjectId::new();
+        database
+            .collection::<Document>("vehicles")
+            .insert_one(doc! { "_id": vehicle_id, "user_id": ObjectId::new(), "make": "Make", "model": "Model" }, None)
+            .await
+            .unwrap();
+        let id = vehicle_id.to_hex();
+
+        for entry in [
+            fill_up("2024-03-01", 20_000.0, 40.0, 60.0, true),
+            fill_up("2024-03-20", 20_800.0, 40.0, 60.0, true),
+        ] {
+            add_fuel_entry(&fuel, &vehicles, &id, None, entry).await.unwrap().unwrap();
+        }
+
+        // Lower than the reading logged before it
+        let rollback = fill_up("2024-03-25", 20_500.0, 10.0, 15.0, false);
+        match add_fuel_entry(&fuel, &vehicles, &id, None, rollback).await {
+            Err(VehicleError::Invalid(errors)) => {
+                assert_eq!(errors[0].field, "odometer");
+                assert!(errors[0].message.contains("at least 20800.0 km"));
+            }
+            other => panic!("expected an odometer error, got {:?}", other.map(|_| ())),
+        }
+
+        // Backdated past a later, lower reading
+        let backdated = fill_up("2024-03-10", 21_000.0, 10.0, 15.0, false);
+        match add_fuel_entry(&fuel, &vehicles, &id, None, backdated).await {
+            Err(VehicleError::Invalid(errors)) => {
+                assert!(errors[0].message.contains("at most 20800.0 km"));
+            }
+            other => panic!("expected an odometer error, got {:?}", other.map(|_| ())),
+        }
+
+        // Fitting between the two is fine; nothing else was stored
+        add_fuel_entry(&fuel, &vehicles, &id, None, fill_up("2024-03-10", 20_400.0, 10.0, 15.0, false))
+            .await
+            .unwrap()
+            .unwrap();
+        let entries = list_fuel_entries(&fuel, &vehicles, &id, None).await.unwrap().unwrap();
+        assert_eq!(entries.len(), 3);
+
+        database.drop(None).await.unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use std::env;
+
+use crate::db::UserDb;
+use crate::models::user_model::User;
+
+fn env_i64(name: &str, default: i64) -> i64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(default)
+}
+
+/// Failed attempts allowed before the first lockout (`LOGIN_MAX_ATTEMPTS`, default 5)
+fn max_attempts() -> i64 {
+    env_i64("LOGIN_MAX_ATTEMPTS", 5)
+}
+
+/// Lockout after the `n`th failure past the limit: base * 2^n, capped.
+/// (`LOGIN_LOCKOUT_BASE_SECONDS`, default 60; `LOGIN_LOCKOUT_MAX_SECONDS`, default 86400)
+fn lockout_duration(failures_past_limit: i64) -> chrono::Duration {
+    let base = env_i64("LOGIN_LOCKOUT_BASE_SECONDS", 60);
+    let max = env_i64("LOGIN_LOCKOUT_MAX_SECONDS", 86_400);
+    backoff(base, max, failures_past_limit)
+}
+
+fn backoff(base: i64, max: i64, failures_past_limit: i64) -> chrono::Duration {
+    let exponent = failures_past_limit.clamp(0, 20) as u32;
+    chrono::Duration::seconds(base.saturating_mul(1 << exponent).min(max))
+}
+
+/// Seconds until the account unlocks, if it is currently locked
+pub fn seconds_until_unlock(user: &User) -> Option<i64> {
+    let locked_until = user.locked_until?.to_chrono();
+    let remaining = (locked_until - chrono::Utc::now()).num_seconds();
+    (remaining > 0).then_some(remaining)
+}
+
+/// Count a failed attempt and lock the account once over the limit.
+/// Returns the new lock duration in seconds, if this failure triggered one.
+pub async fn record_failed_login(db: &UserDb, user_id: ObjectId) -> Result<Option<i64>, String> {
+    let collection = db.lock().await;
+    let user = collection
+        .find_one_and_update(
+            doc! { "_id": user_id },
+            doc! { "$inc": { "failed_login_attempts": 1 } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    let past_limit = user.failed_login_attempts as i64 - max_attempts();
+    if past_limit < 0 {
+        return Ok(None);
+    }
+
+    let duration = lockout_duration(past_limit);
+    collection
+        .update_one(
+            doc! { "_id": user_id },
+            doc! { "$set": { "locked_until": DateTime::from_chrono(chrono::Utc::now() + duration) } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(duration.num_seconds()))
+}
+
+pub async fn reset_failed_logins(db: &UserDb, user_id: ObjectId) -> Result<(), String> {
+    let collection = db.lock().await;
+    collection
+        .update_one(
+            doc! { "_id": user_id, "failed_login_attempts": { "$gt": 0 } },
+            doc! {
+                "$set": { "failed_login_attempts": 0 },
+                "$unset": { "locked_until": "" },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::Document;
+
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+
+    #[test]
+    fn backoff_doubles_from_the_base_up_to_the_cap() {
+        let seconds = |n| backoff(60, 86_400, n).num_seconds();
+        assert_eq!(seconds(0), 60);
+        assert_eq!(seconds(1), 120);
+        assert_eq!(seconds(2), 240);
+        assert_eq!(seconds(5), 1_920);
+        assert_eq!(seconds(11), 86_400);
+        assert_eq!(seconds(1_000), 86_400);
+        assert_eq!(seconds(-3), 60);
+        assert_eq!(backoff(1, i64::from(u32::MAX), 1_000).num_seconds(), 1 << 20);
+    }
+
+    #[tokio::test]
+    async fn record_failed_login_locks_after_the_limit_with_backoff() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let user_id = ObjectId::new();
+        database
+            .collection::<Document>("users")
+            .insert_one(
+                doc! { "_id": user_id, "name": "Locked", "email": "locked@example.com", "password": "x" },
+                None,
+            )
+            .await
+            .unwrap();
+
+        for _ in 1..max_attempts() {
+            assert_eq!(record_failed_login(&users, user_id).await.unwrap(), None);
+        }
+        assert_eq!(record_failed_login(&users, user_id).await.unwrap(), Some(60));
+        assert_eq!(record_failed_login(&users, user_id).await.unwrap(), Some(120));
+
+        let user = users.lock().await.find_one(doc! { "_id": user_id }, None).await.unwrap().unwrap();
+        let remaining = seconds_until_unlock(&user).unwrap();
+        assert!(remaining > 100 && remaining <= 120);
+
+        reset_failed_logins(&users, user_id).await.unwrap();
+        let user = users.lock().await.find_one(doc! { "_id": user_id }, None).await.unwrap().unwrap();
+        assert_eq!(user.failed_login_attempts, 0);
+        assert_eq!(seconds_until_unlock(&user), None);
+
+        database.drop(None).await.unwrap();
+    }
+}
@@ -0,0 +1,660 @@
+use std::env;
+
+use chrono::Months;
+use futures_util::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneOptions, FindOptions};
+
+use crate::db::{FuelLogDb, MaintenanceDueDb, MaintenanceRuleDb, ServiceRecordDb, VehicleDb};
+use crate::models::maintenance_model::{
+    CreateMaintenanceRule, CreateMaintenanceTemplate, DueStatus, MaintenanceDueItem, MaintenanceRule,
+};
+use crate::models::service_record_model::ServiceRecord;
+use crate::models::vehicle_model::Vehicle;
+use crate::services::vehicle_service::{accessible_vehicle_id, get_vehicle};
+
+/// Distance left at which a task counts as due soon (`MAINTENANCE_DUE_SOON_KM`, default 1000)
+fn due_soon_km() -> f64 {
+    env::var("MAINTENANCE_DUE_SOON_KM")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(1000.0)
+}
+
+/// Days left at which a task counts as due soon (`MAINTENANCE_DUE_SOON_DAYS`, default 30)
+fn due_soon_days() -> i64 {
+    env::var("MAINTENANCE_DUE_SOON_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(30)
+}
+
+/// How often reminders are recomputed, in minutes
+/// (`MAINTENANCE_EVALUATION_INTERVAL_MINUTES`, default 60)
+fn evaluation_interval() -> std::time::Duration {
+    let minutes = env::var("MAINTENANCE_EVALUATION_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(60);
+    std::time::Duration::from_secs(minutes * 60)
+}
+
+fn parse_rule_id(rule_id: &str) -> Result<ObjectId, String> {
+    ObjectId::parse_str(rule_id).map_err(|_| "Invalid maintenance rule ID".to_string())
+}
+
+fn new_rule(
+    vehicle_id: Option<ObjectId>,
+    make: Option<String>,
+    model: Option<String>,
+    payload: CreateMaintenanceRule,
+) -> MaintenanceRule {
+    MaintenanceRule {
+        id: Some(ObjectId::new()),
+        vehicle_id,
+        make,
+        model,
+        task: payload.task.trim().to_string(),
+        category: payload.category,
+        interval_km: payload.interval_km,
+        interval_months: payload.interval_months,
+        created_at: Some(DateTime::now()),
+    }
+}
+
+/// Whether a template applies to a vehicle; make and model compare case-insensitively
+fn template_matches(template: &MaintenanceRule, vehicle: &Vehicle) -> bool {
+    let same = |a: &str, b: &str| a.trim().to_lowercase() == b.trim().to_lowercase();
+    template.make.as_deref().is_some_and(|make| same(make, &vehicle.make))
+        && template.model.as_deref().is_none_or(|model| same(model, &vehicle.model))
+}
+
+/// The vehicle's own rules plus the templates for its make/model. A vehicle
+/// rule replaces a template for the same task.
+fn effective_rules(
+    vehicle: &Vehicle,
+    own: Vec<MaintenanceRule>,
+    templates: &[MaintenanceRule],
+) -> Vec<MaintenanceRule> {
+    let overridden = |task: &str| own.iter().any(|r| r.task.eq_ignore_ascii_case(task));
+    let inherited: Vec<MaintenanceRule> = templates
+        .iter()
+        .filter(|t| template_matches(t, vehicle) && !overridden(&t.task))
+        .cloned()
+        .collect();
+    own.into_iter().chain(inherited).collect()
+}
+
+async fn vehicle_rules(db: &MaintenanceRuleDb, vehicle_id: ObjectId) -> Result<Vec<MaintenanceRule>, String> {
+    let collection = db.lock().await;
+    collection
+        .find(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Add a rule to one vehicle. `None` if the vehicle isn't accessible.
+pub async fn create_vehicle_rule(
+    db: &MaintenanceRuleDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+    payload: CreateMaintenanceRule,
+) -> Result<Option<MaintenanceRule>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let rule = new_rule(Some(vehicle_id), None, None, payload);
+    let collection = db.lock().await;
+    collection
+        .insert_one(&rule, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(rule))
+}
+
+/// Every rule that applies to a vehicle, its own and inherited from templates
+pub async fn list_vehicle_rules(
+    db: &MaintenanceRuleDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vec<MaintenanceRule>>, String> {
+    let Some(vehicle) = get_vehicle(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+    let Some(id) = vehicle.id else {
+        return Ok(None);
+    };
+
+    let own = vehicle_rules(db, id).await?;
+    let templates = list_templates(db).await?;
+    Ok(Some(effective_rules(&vehicle, own, &templates)))
+}
+
+/// Remove one of the vehicle's own rules; templates are managed separately.
+/// Returns `false` if nothing matched.
+pub async fn delete_vehicle_rule(
+    db: &MaintenanceRuleDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    rule_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<bool, String> {
+    let rule_id = parse_rule_id(rule_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(false);
+    };
+
+    let collection = db.lock().await;
+    let result = collection
+        .delete_one(doc! { "_id": rule_id, "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.deleted_count == 1)
+}
+
+pub async fn create_template(
+    db: &MaintenanceRuleDb,
+    payload: CreateMaintenanceTemplate,
+) -> Result<MaintenanceRule, String> {
+    let make = payload.make.trim().to_string();
+    let model = payload.model.map(|m| m.trim().to_string());
+    let rule = new_rule(None, Some(make), model, payload.rule);
+
+    let collection = db.lock().await;
+    collection
+        .insert_one(&rule, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(rule)
+}
+
+pub async fn list_templates(db: &MaintenanceRuleDb) -> Result<Vec<MaintenanceRule>, String> {
+    let collection = db.lock().await;
+    collection
+        .find(
+            doc! { "vehicle_id": null },
+            FindOptions::builder()
+                .sort(doc! { "make": 1, "model": 1, "task": 1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns `false` if nothing matched
+pub async fn delete_template(db: &MaintenanceRuleDb, rule_id: &str) -> Result<bool, String> {
+    let rule_id = parse_rule_id(rule_id)?;
+
+    let collection = db.lock().await;
+    let result = collection
+        .delete_one(doc! { "_id": rule_id, "vehicle_id": null }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.deleted_count == 1)
+}
+
+/// Reminders from the last evaluation for one vehicle, overdue first
+pub async fn due_for_vehicle(
+    db: &MaintenanceDueDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vec<MaintenanceDueItem>>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    due_items(db, doc! { "vehicle_id": vehicle_id }).await.map(Some)
+}
+
+/// Reminders across every vehicle the user owns, overdue first
+pub async fn reminders_for_user(
+    db: &MaintenanceDueDb,
+    user_id: ObjectId,
+) -> Result<Vec<MaintenanceDueItem>, String> {
+    due_items(db, doc! { "user_id": user_id }).await
+}
+
+async fn due_items(
+    db: &MaintenanceDueDb,
+    filter: bson::Document,
+) -> Result<Vec<MaintenanceDueItem>, String> {
+    let collection = db.lock().await;
+    let mut items: Vec<MaintenanceDueItem> = collection
+        .find(filter, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    items.sort_by_key(|item| {
+        (item.status != DueStatus::Overdue, item.days_remaining.unwrap_or(i64::MAX))
+    });
+    Ok(items)
+}
+
+/// Highest odometer reading logged for the vehicle, from fill-ups or services
+async fn latest_odometer(
+    services: &ServiceRecordDb,
+    fuel: &FuelLogDb,
+    vehicle_id: ObjectId,
+) -> Result<Option<f64>, String> {
+    let from_service = {
+        let collection = services.lock().await;
+        collection
+            .find_one(
+                doc! { "vehicle_id": vehicle_id },
+                FindOneOptions::builder().sort(doc! { "odometer": -1 }).build(),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|r| r.odometer as f64)
+    };
+    let from_fuel = {
+        let collection = fuel.lock().await;
+        collection
+            .find_one(
+                doc! { "vehicle_id": vehicle_id },
+                FindOneOptions::builder().sort(doc! { "odometer_km": -1 }).build(),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|e| e.odometer_km)
+    };
+
+    Ok(match (from_service, from_fuel) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    })
+}
+
+/// Clock, odometer and thresholds shared by every rule of one vehicle's evaluation
+struct DueCheck {
+    now: chrono::DateTime<chrono::Utc>,
+    current_km: Option<f64>,
+    soon_km: f64,
+    soon_days: i64,
+}
+
+impl DueCheck {
+    /// The reminder for `rule`, or `None` while it is neither due soon nor overdue.
+    /// A task never done is measured from zero km and from when the vehicle was added.
+    fn item(
+        &self,
+        vehicle: &Vehicle,
+        vehicle_id: ObjectId,
+        rule: &MaintenanceRule,
+        last_done: Option<&ServiceRecord>,
+    ) -> Option<MaintenanceDueItem> {
+        let last_done_km = last_done.map(|r| r.odometer as f64);
+        let since = last_done
+            .map(|r| r.date)
+            .or(vehicle.created_at)
+            .unwrap_or_else(|| DateTime::from_chrono(self.now))
+            .to_chrono();
+
+        let due_km = rule.interval_km.map(|km| last_done_km.unwrap_or(0.0) + km);
+        let remaining_km = due_km.zip(self.current_km).map(|(due, current)| due - current);
+        let due_date = rule
+            .interval_months
+            .and_then(|months| since.checked_add_months(Months::new(months)));
+        let days_remaining = due_date.map(|due| (due - self.now).num_days());
+
+        let overdue = remaining_km.is_some_and(|km| km <= 0.0)
+            || due_date.is_some_and(|due| due <= self.now);
+        let due_soon = remaining_km.is_some_and(|km| km <= self.soon_km)
+            || days_remaining.is_some_and(|days| days <= self.soon_days);
+        let status = match (overdue, due_soon) {
+            (true, _) => DueStatus::Overdue,
+            (false, true) => DueStatus::DueSoon,
+            (false, false) => return None,
+        };
+
+        Some(MaintenanceDueItem {
+            id: Some(ObjectId::new()),
+            user_id: vehicle.user_id,
+            vehicle_id,
+            rule_id: rule.id?,
+            task: rule.task.clone(),
+            category: rule.category,
+            status,
+            due_km,
+            due_date: due_date.map(DateTime::from_chrono),
+            remaining_km,
+            days_remaining,
+            current_km: self.current_km,
+            last_done_at: last_done.map(|r| r.date),
+            last_done_km,
+            evaluated_at: DateTime::from_chrono(self.now),
+        })
+    }
+}
+
+/// Check each rule against the vehicle's history. A task counts as done by the
+/// latest service record of its category.
+async fn evaluate_vehicle(
+    services: &ServiceRecordDb,
+    fuel: &FuelLogDb,
+    vehicle: &Vehicle,
+    vehicle_id: ObjectId,
+    rules: &[MaintenanceRule],
+) -> Result<Vec<MaintenanceDueItem>, String> {
+    let check = DueCheck {
+        now: chrono::Utc::now(),
+        current_km: latest_odometer(services, fuel, vehicle_id).await?,
+        soon_km: due_soon_km(),
+        soon_days: due_soon_days(),
+    };
+
+    let mut items = vec![];
+    for rule in rules {
+        if rule.id.is_none() {
+            continue;
+        }
+        let category = bson::to_bson(&rule.category).map_err(|e| e.to_string())?;
+        let last_done = {
+            let collection = services.lock().await;
+            collection
+                .find_one(
+                    doc! { "vehicle_id": vehicle_id, "category": category },
+                    FindOneOptions::builder().sort(doc! { "date": -1 }).build(),
+                )
+                .await
+                .map_err(|e| e.to_string())?
+        };
+
+        items.extend(check.item(vehicle, vehicle_id, rule, last_done.as_ref()));
+    }
+
+    Ok(items)
+}
+
+/// Recompute the reminders of every live vehicle. Returns how many items are
+/// due soon or overdue.
+pub async fn evaluate_maintenance(
+    rules: &MaintenanceRuleDb,
+    due: &MaintenanceDueDb,
+    vehicles: &VehicleDb,
+    services: &ServiceRecordDb,
+    fuel: &FuelLogDb,
+) -> Result<usize, String> {
+    let live: Vec<Vehicle> = {
+        let collection = vehicles.lock().await;
+        collection
+            .find(doc! { "deleted_at": null }, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    let templates = list_templates(rules).await?;
+
+    let mut total = 0;
+    let mut evaluated = Vec::with_capacity(live.len());
+    for vehicle in &live {
+        let Some(vehicle_id) = vehicle.id else { continue };
+        let applicable = effective_rules(vehicle, vehicle_rules(rules, vehicle_id).await?, &templates);
+        let items = evaluate_vehicle(services, fuel, vehicle, vehicle_id, &applicable).await?;
+        total += items.len();
+
+        let collection = due.lock().await;
+        collection
+            .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !items.is_empty() {
+            collection
+                .insert_many(&items, None)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        evaluated.push(vehicle_id);
+    }
+
+    // Vehicles deleted since the last run
+    let collection = due.lock().await;
+    collection
+        .delete_many(doc! { "vehicle_id": { "$nin": evaluated } }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(total)
+}
+
+/// Start the background task that keeps the reminders current. Runs once at
+/// startup, then every evaluation interval.
+pub fn spawn_maintenance_evaluator(
+    rules: MaintenanceRuleDb,
+    due: MaintenanceDueDb,
+    vehicles: VehicleDb,
+    services: ServiceRecordDb,
+    fuel: FuelLogDb,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(evaluation_interval());
+        loop {
+            interval.tick().await;
+            if let Err(e) = evaluate_maintenance(&rules, &due, &vehicles, &services, &fuel).await {
+                eprintln!("Maintenance evaluation failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Remove a vehicle's own maintenance rules and its reminders. Templates,
+/// which belong to no vehicle, are kept.
+pub async fn delete_vehicle_maintenance(
+    rules: &MaintenanceRuleDb,
+    due: &MaintenanceDueDb,
+    vehicle_id: ObjectId,
+) -> Result<(), String> {
+    {
+        let collection = rules.lock().await;
+        collection
+            .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let collection = due.lock().await;
+    collection
+        .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::service_record_model::ServiceCategory;
+
+    fn at(date: &str) -> chrono::DateTime<chrono::Utc> {
+        DateTime::parse_rfc3339_str(format!("{date}T00:00:00Z")).unwrap().to_chrono()
+    }
+
+    fn vehicle(make: &str, model: &str) -> Vehicle {
+        Vehicle {
+            id: Some(ObjectId::new()),
+            user_id: ObjectId::new(),
+            make: make.to_string(),
+            model: model.to_string(),
+            year: Some(2018),
+            legacy_year: None,
+            vin: None,
+            files: None,
+            created_at: Some(DateTime::from_chrono(at("2024-01-01"))),
+            updated_at: None,
+            deleted_at: None,
+            ownership_history: vec![],
+        }
+    }
+
+    fn rule(task: &str, interval_km: Option<f64>, interval_months: Option<u32>) -> MaintenanceRule {
+        MaintenanceRule {
+            id: Some(ObjectId::new()),
+            vehicle_id: None,
+            make: None,
+            model: None,
+            task: task.to_string(),
+            category: ServiceCategory::OilChange,
+            interval_km,
+            interval_months,
+            created_at: None,
+        }
+    }
+
+    fn template(make: &str, model: Option<&str>, task: &str) -> MaintenanceRule {
+        MaintenanceRule {
+            make: Some(make.to_string()),
+            model: model.map(str::to_string),
+            ..rule(task, Some(10_000.0), None)
+        }
+    }
+
+    fn service(date: &str, odometer: i64) -> ServiceRecord {
+        ServiceRecord {
+            id: Some(ObjectId::new()),
+            vehicle_id: ObjectId::new(),
+            date: DateTime::from_chrono(at(date)),
+            odometer,
+            category: ServiceCategory::OilChange,
+            cost: 80.0,
+            shop: None,
+            notes: None,
+            receipts: vec![],
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn check(now: &str, current_km: Option<f64>) -> DueCheck {
+        DueCheck {
+            now: at(now),
+            current_km,
+            soon_km: 1_000.0,
+            soon_days: 30,
+        }
+    }
+
+    fn status(check: &DueCheck, rule: &MaintenanceRule, last_done: Option<&ServiceRecord>) -> Option<DueStatus> {
+        let vehicle = vehicle("Honda", "Civic");
+        check.item(&vehicle, vehicle.id.unwrap(), rule, last_done).map(|item| item.status)
+    }
+
+    #[test]
+    fn own_rules_replace_matching_templates() {
+        let civic = vehicle("Honda", "Civic");
+        let own = vec![rule("Oil change", Some(8_000.0), None)];
+        let templates = [
+            template("honda", None, "OIL CHANGE"),
+            template("HONDA", Some(" civic "), "Timing belt"),
+            template("Honda", Some("Accord"), "Cabin filter"),
+            template("Toyota", None, "Tires"),
+        ];
+
+        let rules = effective_rules(&civic, own, &templates);
+        let tasks: Vec<_> = rules.iter().map(|r| r.task.as_str()).collect();
+        assert_eq!(tasks, ["Oil change", "Timing belt"]);
+        assert_eq!(rules[0].interval_km, Some(8_000.0));
+
+        // Without an own rule the make-wide template applies
+        let tasks: Vec<_> = effective_rules(&civic, vec![], &templates)
+            .into_iter()
+            .map(|r| r.task)
+            .collect();
+        assert_eq!(tasks, ["OIL CHANGE", "Timing belt"]);
+    }
+
+    #[test]
+    fn mileage_rules_fall_due_from_the_last_service() {
+        let every_10k = rule("Oil change", Some(10_000.0), None);
+        let done = service("2024-02-01", 40_000);
+
+        assert_eq!(status(&check("2024-03-01", Some(45_000.0)), &every_10k, Some(&done)), None);
+        assert_eq!(
+            status(&check("2024-03-01", Some(49_500.0)), &every_10k, Some(&done)),
+            Some(DueStatus::DueSoon)
+        );
+        assert_eq!(
+            status(&check("2024-03-01", Some(50_000.0)), &every_10k, Some(&done)),
+            Some(DueStatus::Overdue)
+        );
+        // Never done: measured from zero
+        assert_eq!(
+            status(&check("2024-03-01", Some(12_000.0)), &every_10k, None),
+            Some(DueStatus::Overdue)
+        );
+        // No reading yet, so there is nothing to compare
+        assert_eq!(status(&check("2024-03-01", None), &every_10k, None), None);
+
+        let vehicle = vehicle("Honda", "Civic");
+        let item = check("2024-03-01", Some(49_500.0))
+            .item(&vehicle, vehicle.id.unwrap(), &every_10k, Some(&done))
+            .unwrap();
+        assert_eq!(item.due_km, Some(50_000.0));
+        assert_eq!(item.remaining_km, Some(500.0));
+        assert_eq!(item.last_done_km, Some(40_000.0));
+    }
+
+    #[test]
+    fn date_rules_fall_due_from_the_last_service_or_when_added() {
+        let every_6_months = rule("Inspection", None, Some(6));
+        let done = service("2024-03-01", 40_000);
+
+        assert_eq!(status(&check("2024-05-01", None), &every_6_months, Some(&done)), None);
+        assert_eq!(
+            status(&check("2024-08-15", None), &every_6_months, Some(&done)),
+            Some(DueStatus::DueSoon)
+        );
+        assert_eq!(
+            status(&check("2024-09-01", None), &every_6_months, Some(&done)),
+            Some(DueStatus::Overdue)
+        );
+        // Never done: measured from when the vehicle was added (2024-01-01)
+        assert_eq!(
+            status(&check("2024-07-02", None), &every_6_months, None),
+            Some(DueStatus::Overdue)
+        );
+
+        let vehicle = vehicle("Honda", "Civic");
+        let item = check("2024-08-15", None)
+            .item(&vehicle, vehicle.id.unwrap(), &every_6_months, Some(&done))
+            .unwrap();
+        assert_eq!(item.due_date, Some(DateTime::from_chrono(at("2024-09-01"))));
+        assert_eq!(item.days_remaining, Some(17));
+    }
+
+    #[test]
+    fn whichever_limit_comes_first_decides() {
+        let both = rule("Oil change", Some(10_000.0), Some(12));
+        let done = service("2023-01-01", 40_000);
+
+        // Barely driven, but a year has passed
+        assert_eq!(
+            status(&check("2024-01-02", Some(41_000.0)), &both, Some(&done)),
+            Some(DueStatus::Overdue)
+        );
+        // Recent, but the distance is up
+        assert_eq!(
+            status(&check("2023-02-01", Some(50_100.0)), &both, Some(&done)),
+            Some(DueStatus::Overdue)
+        );
+        assert_eq!(status(&check("2023-02-01", Some(41_000.0)), &both, Some(&done)), None);
+    }
+}
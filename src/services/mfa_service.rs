@@ -0,0 +1,277 @@
+use bcrypt::verify;
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use rand::{distributions::Uniform, rngs::OsRng, Rng};
+use std::env;
+
+use crate::db::UserDb;
+use crate::models::mfa_model::{MfaPendingClaims, TotpSetupResponse};
+use crate::models::user_model::User;
+use crate::utils::{crypto::sha256_hex, jwt, totp};
+
+const MFA_TOKEN_PURPOSE: &str = "mfa_pending";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Lifetime of the "mfa pending" token, in minutes (`MFA_TOKEN_TTL_MINUTES`, default 5)
+fn mfa_token_ttl() -> chrono::Duration {
+    let minutes = env::var("MFA_TOKEN_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(5);
+    chrono::Duration::minutes(minutes)
+}
+
+async fn find_user(db: &UserDb, user_id: &ObjectId) -> Result<User, String> {
+    let collection = db.lock().await;
+    collection
+        .find_one(doc! { "_id": user_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found".to_string())
+}
+
+/// Generate a new (not yet active) TOTP secret for the user
+pub async fn begin_totp_setup(db: &UserDb, user_id: &str) -> Result<TotpSetupResponse, String> {
+    let obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let user = find_user(db, &obj_id).await?;
+
+    if user.totp_enabled {
+        return Err("Two-factor authentication is already enabled".to_string());
+    }
+
+    let secret = totp::generate_secret();
+    let issuer = env::var("TOTP_ISSUER").unwrap_or("AsyncRust".to_string());
+
+    let collection = db.lock().await;
+    collection
+        .update_one(
+            doc! { "_id": obj_id },
+            doc! { "$set": { "totp_pending_secret": &secret, "updated_at": DateTime::now() } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(TotpSetupResponse {
+        otpauth_uri: totp::provisioning_uri(&secret, &issuer, &user.email),
+        secret,
+    })
+}
+
+/// Activate 2FA once the user proves their authenticator produces valid codes.
+/// Returns the clear-text recovery codes; they are never shown again.
+pub async fn confirm_totp_setup(db: &UserDb, user_id: &str, code: &str) -> Result<Vec<String>, String> {
+    let obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let user = find_user(db, &obj_id).await?;
+
+    let secret = user
+        .totp_pending_secret
+        .ok_or("No two-factor setup in progress")?;
+    let step = totp::verify(&secret, code, chrono::Utc::now().timestamp())
+        .ok_or("Invalid verification code")?;
+
+    let codes = generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| sha256_hex(c)).collect();
+
+    let collection = db.lock().await;
+    collection
+        .update_one(
+            doc! { "_id": obj_id },
+            doc! {
+                "$set": {
+                    "totp_enabled": true,
+                    "totp_secret": &secret,
+                    "totp_last_step": step,
+                    "recovery_code_hashes": hashes,
+                    "updated_at": DateTime::now(),
+                },
+                "$unset": { "totp_pending_secret": "" },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(codes)
+}
+
+/// Turn 2FA off after re-checking both the password and a second factor
+pub async fn disable_totp(db: &UserDb, user_id: &str, password: &str, code: &str) -> Result<(), String> {
+    let obj_id = ObjectId::parse_str(user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let user = find_user(db, &obj_id).await?;
+
+    if !user.totp_enabled {
+        return Err("Two-factor authentication is not enabled".to_string());
+    }
+    if !verify(password, &user.password).map_err(|e| e.to_string())? {
+        return Err("Invalid password".to_string());
+    }
+    verify_second_factor(db, &user, code).await?;
+
+    let collection = db.lock().await;
+    collection
+        .update_one(
+            doc! { "_id": obj_id },
+            doc! {
+                "$set": {
+                    "totp_enabled": false,
+                    "recovery_code_hashes": [],
+                    "updated_at": DateTime::now(),
+                },
+                "$unset": { "totp_secret": "", "totp_pending_secret": "", "totp_last_step": "" },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Accept either a TOTP code or an unused recovery code.
+/// Both are consumed with a filtered update so they can't be replayed concurrently.
+pub async fn verify_second_factor(db: &UserDb, user: &User, code: &str) -> Result<(), String> {
+    let user_id = user.id.ok_or("User has no ID")?;
+    let secret = user.totp_secret.as_deref().ok_or("Two-factor authentication is not enabled")?;
+
+    let collection = db.lock().await;
+
+    if let Some(step) = totp::verify(secret, code, chrono::Utc::now().timestamp()) {
+        let result = collection
+            .update_one(
+                doc! {
+                    "_id": user_id,
+                    "$or": [
+                        { "totp_last_step": null },
+                        { "totp_last_step": { "$lt": step } },
+                    ],
+                },
+                doc! { "$set": { "totp_last_step": step } },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        return if result.modified_count == 1 {
+            Ok(())
+        } else {
+            Err("Verification code already used".to_string())
+        };
+    }
+
+    let code_hash = sha256_hex(&normalize_recovery_code(code));
+    let result = collection
+        .update_one(
+            doc! { "_id": user_id, "recovery_code_hashes": &code_hash },
+            doc! { "$pull": { "recovery_code_hashes": &code_hash } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.modified_count == 1 {
+        Ok(())
+    } else {
+        Err("Invalid verification code".to_string())
+    }
+}
+
+/// Sign the short-lived token that stands in for a session between the two login steps
+pub fn issue_mfa_token(user_id: &ObjectId) -> Result<(String, i64), String> {
+    let ttl = mfa_token_ttl();
+
+    let claims = MfaPendingClaims {
+        sub: user_id.to_hex(),
+        exp: (chrono::Utc::now() + ttl).timestamp() as usize,
+        purpose: MFA_TOKEN_PURPOSE.to_string(),
+    };
+
+    let token = jwt::sign(&claims)?;
+
+    Ok((token, ttl.num_seconds()))
+}
+
+/// Validate an "mfa pending" token and return the user it was issued for
+pub fn decode_mfa_token(token: &str) -> Result<ObjectId, String> {
+    let claims: MfaPendingClaims =
+        jwt::verify(token).map_err(|_| "Invalid or expired MFA token".to_string())?;
+
+    if claims.purpose != MFA_TOKEN_PURPOSE {
+        return Err("Invalid or expired MFA token".to_string());
+    }
+
+    ObjectId::parse_str(&claims.sub).map_err(|_| "Invalid or expired MFA token".to_string())
+}
+
+/// Recovery codes look like `abcde-fghij`
+fn generate_recovery_codes() -> Vec<String> {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let dist = Uniform::from(0..ALPHABET.len());
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let raw: String = (0..10)
+                .map(|_| ALPHABET[OsRng.sample(dist)] as char)
+                .collect();
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    let raw: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if raw.len() == 10 {
+        format!("{}-{}", &raw[..5], &raw[5..])
+    } else {
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::Document;
+
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+
+    #[tokio::test]
+    async fn totp_code_is_rejected_once_used() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let secret = totp::generate_secret();
+
+        database
+            .collection::<Document>("users")
+            .insert_one(
+                doc! {
+                    "name": "Mfa",
+                    "email": "mfa@example.com",
+                    "password": "unused",
+                    "totp_enabled": true,
+                    "totp_secret": &secret,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let user = users
+            .lock()
+            .await
+            .find_one(doc! { "email": "mfa@example.com" }, None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let code = totp::code_at(&secret, chrono::Utc::now().timestamp()).unwrap();
+        verify_second_factor(&users, &user, &code).await.unwrap();
+        assert_eq!(
+            verify_second_factor(&users, &user, &code).await,
+            Err("Verification code already used".to_string())
+        );
+
+        database.drop(None).await.unwrap();
+    }
+}
@@ -1,2 +1,15 @@
+pub mod api_key_service;
+pub mod document_service;
+pub mod file_service;
+pub mod fuel_service;
+pub mod lockout_service;
+pub mod maintenance_service;
+pub mod mfa_service;
+pub mod oidc_service;
+pub mod role_service;
+pub mod service_record_service;
+pub mod session_service;
+pub mod transfer_service;
 pub mod user_service;
+pub mod user_token_service;
 pub mod vehicle_service;
@@ -0,0 +1,526 @@
+//! OpenID Connect authorization-code login with PKCE.
+//!
+//! Configured with `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_REDIRECT_URI`, and optionally
+//! `OIDC_CLIENT_SECRET` and `OIDC_SCOPES`. SSO is disabled when `OIDC_ISSUER` is unset.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bcrypt::{hash, DEFAULT_COST};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+use crate::db::{OidcStateDb, SessionDb, UserDb};
+use crate::models::oidc_model::{IdTokenClaims, OidcDiscovery, OidcPendingLogin, OidcTokenResponse};
+use crate::models::user_model::{User, UserRole};
+use crate::services::user_service::{finish_login, LoginError, LoginOutcome};
+use crate::utils::crypto::{random_token, sha256_hex};
+
+/// How long the user has to finish signing in at the provider
+pub const PENDING_LOGIN_TTL_MINUTES: i64 = 10;
+
+/// Cached provider keys are refetched for an unknown `kid` at most this often
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+/// ID tokens must be signed asymmetrically; `HS*` would mean trusting the client secret
+const ALLOWED_ID_TOKEN_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+}
+
+impl OidcConfig {
+    pub fn from_env() -> Option<Self> {
+        Some(OidcConfig {
+            issuer: env::var("OIDC_ISSUER").ok()?.trim_end_matches('/').to_string(),
+            client_id: env::var("OIDC_CLIENT_ID").ok()?,
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_uri: env::var("OIDC_REDIRECT_URI").ok()?,
+            scopes: env::var("OIDC_SCOPES").unwrap_or("openid email profile".to_string()),
+        })
+    }
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// Provider metadata, fetched once and cached for the life of the process
+async fn discovery(config: &OidcConfig) -> Result<&'static OidcDiscovery, String> {
+    static DISCOVERY: OnceCell<OidcDiscovery> = OnceCell::const_new();
+
+    DISCOVERY
+        .get_or_try_init(|| async {
+            let url = format!("{}/.well-known/openid-configuration", config.issuer);
+            let metadata: OidcDiscovery = http_client()
+                .get(&url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("OIDC discovery failed: {}", e))?
+                .json()
+                .await
+                .map_err(|e| format!("Invalid OIDC discovery document: {}", e))?;
+
+            if metadata.issuer.trim_end_matches('/') != config.issuer {
+                return Err("OIDC discovery issuer does not match OIDC_ISSUER".to_string());
+            }
+            Ok(metadata)
+        })
+        .await
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched: Instant,
+}
+
+/// Provider signing keys, cached like `discovery`. A token naming a key we
+/// don't have means the provider rotated, so the set is refetched then,
+/// but no more than once per `JWKS_MIN_REFRESH`.
+async fn provider_key(metadata: &OidcDiscovery, kid: Option<&str>) -> Result<Jwk, String> {
+    static JWKS: Mutex<Option<CachedJwks>> = Mutex::new(None);
+
+    if let Some(cached) = JWKS.lock().unwrap().as_ref() {
+        if let Some(jwk) = select_key(&cached.keys, kid) {
+            return Ok(jwk.clone());
+        }
+        if cached.fetched.elapsed() < JWKS_MIN_REFRESH {
+            return Err("No provider key matches the ID token".to_string());
+        }
+    }
+
+    let keys: JwkSet = http_client()
+        .get(&metadata.jwks_uri)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Fetching provider JWKS failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid provider JWKS: {}", e))?;
+
+    let jwk = select_key(&keys, kid).cloned();
+    *JWKS.lock().unwrap() = Some(CachedJwks {
+        keys,
+        fetched: Instant::now(),
+    });
+    jwk.ok_or("No provider key matches the ID token".to_string())
+}
+
+fn select_key<'a>(keys: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => keys.find(kid),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// What the browser must present at the callback for `state`: the caller
+/// stores it in a cookie when redirecting, so a login started in one browser
+/// can't be completed in another.
+pub fn state_binding(state: &str) -> String {
+    sha256_hex(state)
+}
+
+/// Where to send the browser, and the `state_binding` to set on it
+pub struct OidcRedirect {
+    pub url: String,
+    pub binding: String,
+}
+
+/// Persist a pending login and return the provider URL to redirect the browser to
+pub async fn begin_oidc_login(config: &OidcConfig, states: &OidcStateDb) -> Result<OidcRedirect, String> {
+    let metadata = discovery(config).await?;
+
+    let pending = OidcPendingLogin {
+        id: None,
+        state: random_token(24),
+        nonce: random_token(24),
+        code_verifier: random_token(48),
+        expires_at: DateTime::from_chrono(
+            chrono::Utc::now() + chrono::Duration::minutes(PENDING_LOGIN_TTL_MINUTES),
+        ),
+    };
+
+    let url = Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", config.redirect_uri.as_str()),
+            ("scope", config.scopes.as_str()),
+            ("state", pending.state.as_str()),
+            ("nonce", pending.nonce.as_str()),
+            ("code_challenge", pkce_challenge(&pending.code_verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let collection = states.lock().await;
+    collection
+        .insert_one(&pending, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(OidcRedirect {
+        url: url.to_string(),
+        binding: state_binding(&pending.state),
+    })
+}
+
+/// Handle the provider redirect: redeem the code, validate the ID token, then
+/// sign the linked (or newly created) user in exactly like a password login.
+/// `binding` is what the browser presented from `begin_oidc_login`.
+pub async fn complete_oidc_login(
+    config: &OidcConfig,
+    users: &UserDb,
+    states: &OidcStateDb,
+    sessions: &SessionDb,
+    code: &str,
+    state: &str,
+    binding: Option<&str>,
+) -> Result<LoginOutcome, LoginError> {
+    if binding != Some(state_binding(state).as_str()) {
+        return Err("Login state does not belong to this browser".into());
+    }
+
+    let metadata = discovery(config).await?;
+
+    let collection = states.lock().await;
+    let pending = collection
+        .find_one_and_delete(
+            doc! { "state": state, "expires_at": { "$gt": DateTime::now() } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Unknown or expired login state")?;
+    drop(collection);
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", config.redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", pending.code_verifier.as_str()),
+    ];
+    if let Some(secret) = &config.client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+
+    let tokens: OidcTokenResponse = http_client()
+        .post(&metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("OIDC token exchange failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid OIDC token response: {}", e))?;
+
+    let claims = validate_id_token(config, metadata, &tokens.id_token, &pending.nonce).await?;
+    let user = find_or_create_user(users, &metadata.issuer, claims).await?;
+
+    finish_login(users, sessions, &user).await
+}
+
+async fn validate_id_token(
+    config: &OidcConfig,
+    metadata: &OidcDiscovery,
+    id_token: &str,
+    expected_nonce: &str,
+) -> Result<IdTokenClaims, String> {
+    let header = decode_header(id_token).map_err(|e| format!("Invalid ID token: {}", e))?;
+    if !ALLOWED_ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+        return Err(format!("ID token algorithm {:?} not allowed", header.alg));
+    }
+
+    let jwk = provider_key(metadata, header.kid.as_deref()).await?;
+    let key = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_issuer(&[&metadata.issuer]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| format!("Invalid ID token: {}", e))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(expected_nonce) {
+        return Err("ID token nonce mismatch".to_string());
+    }
+
+    Ok(claims)
+}
+
+/// Resolve the local account for an external identity:
+///  1. an account already linked to `issuer`/`sub`
+///  2. an account with the same email, if the provider vouches for that email
+///  3. otherwise a new account with an unusable password
+async fn find_or_create_user(users: &UserDb, issuer: &str, claims: IdTokenClaims) -> Result<User, String> {
+    let collection = users.lock().await;
+
+    if let Some(user) = collection
+        .find_one(doc! { "oidc_issuer": issuer, "oidc_subject": &claims.sub }, None)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(user);
+    }
+
+    let email = claims.email.ok_or("Identity provider did not return an email")?;
+
+    if let Some(existing) = collection
+        .find_one(doc! { "email": &email }, None)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        if !claims.email_verified {
+            return Err("An account with this email already exists".to_string());
+        }
+
+        let user_id = existing.id.ok_or("User has no ID")?;
+        collection
+            .update_one(
+                doc! { "_id": user_id },
+                doc! {
+                    "$set": {
+                        "oidc_issuer": issuer,
+                        "oidc_subject": &claims.sub,
+                        "email_verified": true,
+                        "updated_at": DateTime::now(),
+                    }
+                },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        return Ok(User {
+            oidc_issuer: Some(issuer.to_string()),
+            oidc_subject: Some(claims.sub),
+            email_verified: true,
+            ..existing
+        });
+    }
+
+    let name = claims
+        .name
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+
+    let new_user = User {
+        id: Some(ObjectId::new()),
+        name,
+        email,
+        // Nobody knows this password; the account signs in through the provider
+        password: hash(random_token(32), DEFAULT_COST).map_err(|e| e.to_string())?,
+        profile_image: None,
+        role: UserRole::user(),
+        email_verified: claims.email_verified,
+        totp_enabled: false,
+        totp_secret: None,
+        totp_pending_secret: None,
+        totp_last_step: None,
+        recovery_code_hashes: vec![],
+        failed_login_attempts: 0,
+        locked_until: None,
+        oidc_issuer: Some(issuer.to_string()),
+        oidc_subject: Some(claims.sub),
+        created_at: Some(DateTime::now()),
+    };
+
+    collection
+        .insert_one(&new_user, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(new_user)
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../../examples/mock_oidc_idp.rs"]
+mod mock_oidc_idp;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+
+    const CLIENT_ID: &str = "async-rust";
+    const REDIRECT_URI: &str = "http://localhost:3000/api/v1/auth/oidc/callback";
+
+    /// One mock provider for the whole test binary, since discovery and keys are
+    /// cached per process. It runs on its own thread so it outlives each test's runtime.
+    fn config() -> OidcConfig {
+        static ISSUER: OnceLock<String> = OnceLock::new();
+        let issuer = ISSUER.get_or_init(|| {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.set_nonblocking(true).unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let app = super::mock_oidc_idp::router(issuer.clone());
+            std::thread::spawn(move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async move {
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    axum::serve(listener, app).await.unwrap();
+                })
+            });
+            issuer
+        });
+
+        OidcConfig {
+            issuer: issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_uri: REDIRECT_URI.to_string(),
+            scopes: "openid email profile".to_string(),
+        }
+    }
+
+    /// Follow the authorization URL and return the query the provider redirects back with
+    async fn authorize(url: &str) -> HashMap<String, String> {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let response = client.get(url).send().await.unwrap();
+        let location = response.headers()[reqwest::header::LOCATION].to_str().unwrap();
+        Url::parse(location).unwrap().query_pairs().into_owned().collect()
+    }
+
+    async fn id_token(config: &OidcConfig, nonce: &str) -> String {
+        let metadata = discovery(config).await.unwrap();
+        let verifier = random_token(48);
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", CLIENT_ID),
+                ("redirect_uri", REDIRECT_URI),
+                ("nonce", nonce),
+                ("code_challenge", pkce_challenge(&verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .unwrap();
+        let code = authorize(url.as_str()).await.remove("code").unwrap();
+
+        let tokens: OidcTokenResponse = http_client()
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", REDIRECT_URI),
+                ("client_id", CLIENT_ID),
+                ("code_verifier", verifier.as_str()),
+            ])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        tokens.id_token
+    }
+
+    #[tokio::test]
+    async fn mock_provider_id_tokens_validate_against_cached_keys() {
+        let config = config();
+        let metadata = discovery(&config).await.unwrap();
+
+        let token = id_token(&config, "nonce-1").await;
+        let claims = validate_id_token(&config, metadata, &token, "nonce-1").await.unwrap();
+        assert_eq!(claims.email.as_deref(), Some("sso.user@example.com"));
+        assert!(claims.email_verified);
+
+        assert!(validate_id_token(&config, metadata, &token, "other-nonce").await.is_err());
+
+        // Keys come from the cache now; an unknown kid can't force another fetch straight away
+        assert!(provider_key(metadata, Some("mock-idp-1")).await.is_ok());
+        assert!(provider_key(metadata, Some("rotated-away")).await.is_err());
+
+        // A token from another audience is refused
+        let other_client = OidcConfig {
+            client_id: "someone-else".to_string(),
+            ..config
+        };
+        assert!(validate_id_token(&other_client, metadata, &token, "nonce-1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn login_completes_only_in_the_browser_that_started_it() {
+        let Some(database) = test_database().await else { return };
+        crate::utils::jwt::init_for_tests();
+        let users: UserDb = shared(&database, "users");
+        let states: OidcStateDb = shared(&database, "oidc_states");
+        let sessions: SessionDb = shared(&database, "sessions");
+        let config = config();
+
+        let redirect = begin_oidc_login(&config, &states).await.unwrap();
+        let callback = authorize(&redirect.url).await;
+        let (code, state) = (&callback["code"], &callback["state"]);
+        assert_eq!(redirect.binding, state_binding(state));
+
+        // No cookie, or another browser's cookie: refused, and the pending login survives
+        for binding in [None, Some(state_binding("someone-elses-state"))] {
+            let result =
+                complete_oidc_login(&config, &users, &states, &sessions, code, state, binding.as_deref()).await;
+            assert!(result.is_err());
+        }
+
+        let outcome = complete_oidc_login(&config, &users, &states, &sessions, code, state, Some(&redirect.binding))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, LoginOutcome::Authenticated(_)));
+        let user = users
+            .lock()
+            .await
+            .find_one(doc! { "email": "sso.user@example.com" }, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.oidc_subject.as_deref(), Some("mock-user-1"));
+        assert!(user.email_verified);
+
+        // The state is single-use
+        assert!(
+            complete_oidc_login(&config, &users, &states, &sessions, code, state, Some(&redirect.binding))
+                .await
+                .is_err()
+        );
+
+        database.drop(None).await.unwrap();
+    }
+}
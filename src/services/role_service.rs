@@ -0,0 +1,211 @@
+use mongodb::bson::{doc, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions};
+use futures_util::TryStreamExt;
+
+use crate::db::RoleDb;
+use crate::models::permission_model::{is_known_permission, PermissionSet, DEFAULT_USER_PERMISSIONS, WILDCARD};
+use crate::models::role_model::{CreateRole, Role};
+use crate::models::user_model::UserRole;
+
+/// Make sure the built-in `Admin` and `User` roles exist.
+/// Existing documents are left alone so edits to `User` survive restarts.
+pub async fn seed_builtin_roles(db: &RoleDb) -> Result<(), String> {
+    let builtins = [
+        (UserRole::ADMIN, vec![WILDCARD.to_string()]),
+        (
+            UserRole::USER,
+            DEFAULT_USER_PERMISSIONS.iter().map(|p| p.to_string()).collect(),
+        ),
+    ];
+
+    let collection = db.lock().await;
+    for (name, permissions) in builtins {
+        collection
+            .update_one(
+                doc! { "name": name },
+                doc! {
+                    "$setOnInsert": {
+                        "name": name,
+                        "permissions": permissions,
+                        "built_in": true,
+                        "created_at": DateTime::now(),
+                        "updated_at": DateTime::now(),
+                    }
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Resolve a role name to its permissions. Unknown roles grant nothing.
+pub async fn permissions_for_role(db: &RoleDb, role: &UserRole) -> Result<PermissionSet, String> {
+    Ok(PermissionSet::new(role_permissions(db, role.as_str()).await?))
+}
+
+/// The raw permission list of a role; empty for unknown roles
+pub async fn role_permissions(db: &RoleDb, name: &str) -> Result<Vec<String>, String> {
+    let collection = db.lock().await;
+    let found = collection
+        .find_one(doc! { "name": name }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(found.map(|r| r.permissions).unwrap_or_default())
+}
+
+pub async fn role_exists(db: &RoleDb, name: &str) -> Result<bool, String> {
+    let collection = db.lock().await;
+    let found = collection
+        .find_one(doc! { "name": name }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(found.is_some())
+}
+
+/// Permissions must exist and the actor must already hold every one of them,
+/// so `role:manage` can't be used to mint `*` or widen the caller's own role.
+fn validate_permissions(actor: &PermissionSet, permissions: &[String]) -> Result<(), String> {
+    if let Some(unknown) = permissions.iter().find(|p| !is_known_permission(p)) {
+        return Err(format!("Unknown permission: {}", unknown));
+    }
+    match actor.first_missing(permissions) {
+        Some(missing) => Err(format!("Cannot grant a permission you do not hold: {}", missing)),
+        None => Ok(()),
+    }
+}
+
+pub async fn list_roles(db: &RoleDb) -> Result<Vec<Role>, String> {
+    let collection = db.lock().await;
+    let cursor = collection
+        .find(None, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    cursor.try_collect().await.map_err(|e| e.to_string())
+}
+
+pub async fn create_role(
+    db: &RoleDb,
+    actor: &PermissionSet,
+    payload: CreateRole,
+) -> Result<Role, String> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Role name is required".to_string());
+    }
+    validate_permissions(actor, &payload.permissions)?;
+
+    let role = Role {
+        id: None,
+        name,
+        permissions: payload.permissions,
+        built_in: false,
+        created_at: Some(DateTime::now()),
+        updated_at: Some(DateTime::now()),
+    };
+
+    let collection = db.lock().await;
+    let result = collection
+        .insert_one(&role, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Role {
+        id: result.inserted_id.as_object_id(),
+        ..role
+    })
+}
+
+/// Replace a role's permissions. `Admin` is fixed to `*` so nobody can lock everyone out.
+pub async fn update_role_permissions(
+    db: &RoleDb,
+    actor: &PermissionSet,
+    name: &str,
+    permissions: Vec<String>,
+) -> Result<Role, String> {
+    if name == UserRole::ADMIN {
+        return Err("The Admin role cannot be modified".to_string());
+    }
+    validate_permissions(actor, &permissions)?;
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! { "name": name },
+            doc! { "$set": { "permissions": permissions, "updated_at": DateTime::now() } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Role not found".to_string())
+}
+
+/// Delete a custom role. Users still holding it are left with no permissions.
+pub async fn delete_role(db: &RoleDb, name: &str) -> Result<(), String> {
+    let collection = db.lock().await;
+    let result = collection
+        .delete_one(doc! { "name": name, "built_in": { "$ne": true } }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.deleted_count == 0 {
+        return Err("Role not found or built-in".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+    use crate::models::permission_model::{Permission, RoleManage, VehicleReadAny, VehicleReadOwn};
+
+    fn permissions(names: &[&str]) -> Vec<String> {
+        names.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn roles_only_get_permissions_the_actor_holds() {
+        let Some(database) = test_database().await else { return };
+        let roles: RoleDb = shared(&database, "roles");
+        seed_builtin_roles(&roles).await.unwrap();
+        let manager = PermissionSet::new(permissions(&[RoleManage::NAME, VehicleReadAny::NAME]));
+        let create = |granted: &[&str]| CreateRole {
+            name: "Support".to_string(),
+            permissions: permissions(granted),
+        };
+
+        assert!(create_role(&roles, &manager, create(&[WILDCARD])).await.is_err());
+        assert!(create_role(&roles, &manager, create(&[VehicleReadOwn::NAME, "vehicle:delete:any"]))
+            .await
+            .is_err());
+        let role = create_role(&roles, &manager, create(&[VehicleReadOwn::NAME])).await.unwrap();
+        assert_eq!(role.permissions, permissions(&[VehicleReadOwn::NAME]));
+
+        // Widening an existing role, built-in or not, is refused the same way
+        for name in ["Support", UserRole::USER] {
+            assert!(update_role_permissions(&roles, &manager, name, permissions(&[WILDCARD]))
+                .await
+                .is_err());
+            assert!(update_role_permissions(&roles, &manager, name, permissions(&[RoleManage::NAME, "user:role:assign"]))
+                .await
+                .is_err());
+        }
+        assert!(!role_permissions(&roles, UserRole::USER).await.unwrap().contains(&WILDCARD.to_string()));
+
+        let role = update_role_permissions(&roles, &manager, "Support", permissions(&[VehicleReadAny::NAME]))
+            .await
+            .unwrap();
+        assert_eq!(role.permissions, permissions(&[VehicleReadAny::NAME]));
+
+        database.drop(None).await.unwrap();
+    }
+}
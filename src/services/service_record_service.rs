@@ -0,0 +1,261 @@
+use futures_util::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::db::{ServiceRecordDb, VehicleDb};
+use crate::models::service_record_model::{
+    CategoryCost, CreateServiceRecord, ServiceCostTotals, ServiceRecord, UpdateServiceRecord,
+};
+use crate::services::vehicle_service::accessible_vehicle_id;
+use crate::storage::{discard, SharedFileStore};
+
+/// Storage key prefix for receipt uploads
+pub const RECEIPT_KEY_PREFIX: &str = "services";
+
+fn parse_record_id(record_id: &str) -> Result<ObjectId, String> {
+    ObjectId::parse_str(record_id).map_err(|_| "Invalid service record ID".to_string())
+}
+
+/// Add a service record to a vehicle. `None` if the vehicle isn't accessible.
+pub async fn create_service_record(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+    payload: CreateServiceRecord,
+    receipts: Vec<String>,
+) -> Result<Option<ServiceRecord>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let record = ServiceRecord {
+        id: Some(ObjectId::new()),
+        vehicle_id,
+        date: payload.date,
+        odometer: payload.odometer,
+        category: payload.category,
+        cost: payload.cost,
+        shop: payload.shop,
+        notes: payload.notes,
+        receipts,
+        created_at: Some(DateTime::now()),
+        updated_at: Some(DateTime::now()),
+    };
+
+    let collection = db.lock().await;
+    collection
+        .insert_one(&record, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(record))
+}
+
+/// All records of a vehicle, most recent first
+pub async fn list_service_records(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vec<ServiceRecord>>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+    let records = collection
+        .find(
+            doc! { "vehicle_id": vehicle_id },
+            FindOptions::builder()
+                .sort(doc! { "date": -1, "_id": -1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(records))
+}
+
+pub async fn get_service_record(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    record_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<ServiceRecord>, String> {
+    let record_id = parse_record_id(record_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let collection = db.lock().await;
+    collection
+        .find_one(doc! { "_id": record_id, "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Change the given fields; new receipts are added to the existing ones
+pub async fn update_service_record(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    record_id: &str,
+    owner: Option<ObjectId>,
+    payload: UpdateServiceRecord,
+    receipts: Vec<String>,
+) -> Result<Option<ServiceRecord>, String> {
+    let record_id = parse_record_id(record_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let mut set = doc! { "updated_at": DateTime::now() };
+    if let Some(date) = payload.date {
+        set.insert("date", date);
+    }
+    if let Some(odometer) = payload.odometer {
+        set.insert("odometer", odometer);
+    }
+    if let Some(category) = payload.category {
+        set.insert("category", bson::to_bson(&category).map_err(|e| e.to_string())?);
+    }
+    if let Some(cost) = payload.cost {
+        set.insert("cost", cost);
+    }
+    if let Some(shop) = payload.shop {
+        set.insert("shop", shop);
+    }
+    if let Some(notes) = payload.notes {
+        set.insert("notes", notes);
+    }
+
+    let mut update = doc! { "$set": set };
+    if !receipts.is_empty() {
+        update.insert("$push", doc! { "receipts": { "$each": receipts } });
+    }
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! { "_id": record_id, "vehicle_id": vehicle_id },
+            update,
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a record and its receipts. Returns `false` if nothing matched.
+pub async fn delete_service_record(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    store: &SharedFileStore,
+    vehicle_id: &str,
+    record_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<bool, String> {
+    let record_id = parse_record_id(record_id)?;
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(false);
+    };
+
+    let deleted = {
+        let collection = db.lock().await;
+        collection
+            .find_one_and_delete(doc! { "_id": record_id, "vehicle_id": vehicle_id }, None)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    let Some(record) = deleted else {
+        return Ok(false);
+    };
+    for key in record.receipts {
+        discard(store, &key).await;
+    }
+
+    Ok(true)
+}
+
+/// Total spend for a vehicle, overall and per category
+pub async fn service_cost_totals(
+    db: &ServiceRecordDb,
+    vehicles: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<ServiceCostTotals>, String> {
+    let Some(vehicle_id) = accessible_vehicle_id(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+
+    let pipeline = vec![
+        doc! { "$match": { "vehicle_id": vehicle_id } },
+        doc! {
+            "$group": {
+                "_id": "$category",
+                "total_cost": { "$sum": "$cost" },
+                "count": { "$sum": 1 },
+            }
+        },
+        doc! { "$sort": { "total_cost": -1 } },
+    ];
+
+    let collection = db.lock().await;
+    let groups: Vec<Document> = collection
+        .aggregate(pipeline, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut by_category = Vec::with_capacity(groups.len());
+    for group in groups {
+        let category = bson::from_bson(group.get("_id").cloned().unwrap_or_default())
+            .map_err(|e| e.to_string())?;
+        by_category.push(CategoryCost {
+            category,
+            total_cost: group.get_f64("total_cost").unwrap_or_default(),
+            count: group
+                .get_i32("count")
+                .map(i64::from)
+                .or_else(|_| group.get_i64("count"))
+                .unwrap_or_default(),
+        });
+    }
+
+    Ok(Some(ServiceCostTotals {
+        total_cost: by_category.iter().map(|c| c.total_cost).sum(),
+        record_count: by_category.iter().map(|c| c.count).sum(),
+        by_category,
+    }))
+}
+
+/// Remove every service record of a vehicle, returning the keys of their
+/// receipts for the caller to delete from the store
+pub async fn delete_vehicle_service_records(
+    db: &ServiceRecordDb,
+    vehicle_id: ObjectId,
+) -> Result<Vec<String>, String> {
+    let collection = db.lock().await;
+    let records: Vec<ServiceRecord> = collection
+        .find(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    collection
+        .delete_many(doc! { "vehicle_id": vehicle_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(records.into_iter().flat_map(|r| r.receipts).collect())
+}
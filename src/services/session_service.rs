@@ -0,0 +1,209 @@
+use std::env;
+
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::db::SessionDb;
+use crate::models::session_model::Session;
+use crate::utils::crypto::{random_token, sha256_hex};
+
+/// Lifetime of an access token, in minutes (`ACCESS_TOKEN_TTL_MINUTES`, default 15)
+pub fn access_token_ttl() -> chrono::Duration {
+    let minutes = env::var("ACCESS_TOKEN_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(15);
+    chrono::Duration::minutes(minutes)
+}
+
+/// Lifetime of a refresh token / session, in days (`REFRESH_TOKEN_TTL_DAYS`, default 30)
+pub fn refresh_token_ttl() -> chrono::Duration {
+    let days = env::var("REFRESH_TOKEN_TTL_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(30);
+    chrono::Duration::days(days)
+}
+
+fn session_expiry() -> DateTime {
+    DateTime::from_chrono(chrono::Utc::now() + refresh_token_ttl())
+}
+
+/// Start a new session for `user_id`.
+/// Returns the stored session and the clear-text refresh token (only its hash is persisted).
+pub async fn create_session(db: &SessionDb, user_id: ObjectId) -> Result<(Session, String), String> {
+    let refresh_token = random_token(32);
+
+    let session = Session {
+        id: Some(ObjectId::new()),
+        user_id,
+        refresh_token_hash: sha256_hex(&refresh_token),
+        previous_refresh_token_hash: None,
+        expires_at: session_expiry(),
+        revoked_at: None,
+        created_at: Some(DateTime::now()),
+        updated_at: Some(DateTime::now()),
+    };
+
+    let collection = db.lock().await;
+    collection
+        .insert_one(&session, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok((session, refresh_token))
+}
+
+/// Exchange a refresh token for a new one.
+/// The swap is done in a single filtered update so a token can only be used once.
+/// Presenting an already-rotated token revokes the whole session, since it means
+/// the token leaked.
+pub async fn rotate_session(db: &SessionDb, refresh_token: &str) -> Result<(Session, String), String> {
+    let presented_hash = sha256_hex(refresh_token);
+    let new_token = random_token(32);
+    let now = DateTime::now();
+
+    let collection = db.lock().await;
+
+    let rotated = collection
+        .find_one_and_update(
+            doc! {
+                "refresh_token_hash": &presented_hash,
+                "revoked_at": null,
+                "expires_at": { "$gt": now },
+            },
+            doc! {
+                "$set": {
+                    "refresh_token_hash": sha256_hex(&new_token),
+                    "previous_refresh_token_hash": &presented_hash,
+                    "expires_at": session_expiry(),
+                    "updated_at": now,
+                }
+            },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(session) = rotated {
+        return Ok((session, new_token));
+    }
+
+    // Reuse of a rotated token: kill the session it belonged to
+    collection
+        .update_one(
+            doc! { "previous_refresh_token_hash": &presented_hash, "revoked_at": null },
+            doc! { "$set": { "revoked_at": now, "updated_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Err("Invalid or expired refresh token".to_string())
+}
+
+/// Revoke a single session (logout)
+pub async fn revoke_session(db: &SessionDb, session_id: &str) -> Result<(), String> {
+    let obj_id = ObjectId::parse_str(session_id).map_err(|_| "Invalid session ID".to_string())?;
+    let now = DateTime::now();
+
+    let collection = db.lock().await;
+    collection
+        .update_one(
+            doc! { "_id": obj_id, "revoked_at": null },
+            doc! { "$set": { "revoked_at": now, "updated_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Revoke every live session of a user
+pub async fn revoke_user_sessions(db: &SessionDb, user_id: ObjectId) -> Result<(), String> {
+    let now = DateTime::now();
+
+    let collection = db.lock().await;
+    collection
+        .update_many(
+            doc! { "user_id": user_id, "revoked_at": null },
+            doc! { "$set": { "revoked_at": now, "updated_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Whether the session behind an access token's `jti` is still live
+pub async fn is_session_active(db: &SessionDb, session_id: &str) -> Result<bool, String> {
+    let obj_id = ObjectId::parse_str(session_id).map_err(|_| "Invalid session ID".to_string())?;
+
+    let collection = db.lock().await;
+    let session = collection
+        .find_one(
+            doc! {
+                "_id": obj_id,
+                "revoked_at": null,
+                "expires_at": { "$gt": DateTime::now() },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(session.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+
+    #[tokio::test]
+    async fn reused_refresh_token_fails_and_revokes_the_session() {
+        let Some(database) = test_database().await else { return };
+        let sessions: SessionDb = shared(&database, "sessions");
+        let (session, first) = create_session(&sessions, ObjectId::new()).await.unwrap();
+        let session_id = session.id.unwrap().to_hex();
+
+        let (rotated, second) = rotate_session(&sessions, &first).await.unwrap();
+        assert_eq!(rotated.id, session.id);
+        assert_ne!(first, second);
+        assert!(is_session_active(&sessions, &session_id).await.unwrap());
+
+        // The old token again: refused, and the whole session is gone
+        assert!(rotate_session(&sessions, &first).await.is_err());
+        assert!(!is_session_active(&sessions, &session_id).await.unwrap());
+        // Including for whoever held the current token
+        assert!(rotate_session(&sessions, &second).await.is_err());
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_the_session() {
+        let Some(database) = test_database().await else { return };
+        let sessions: SessionDb = shared(&database, "sessions");
+        let user_id = ObjectId::new();
+        let (session, refresh_token) = create_session(&sessions, user_id).await.unwrap();
+        let (other, _) = create_session(&sessions, user_id).await.unwrap();
+        let session_id = session.id.unwrap().to_hex();
+        let other_id = other.id.unwrap().to_hex();
+
+        revoke_session(&sessions, &session_id).await.unwrap();
+        assert!(!is_session_active(&sessions, &session_id).await.unwrap());
+        assert!(rotate_session(&sessions, &refresh_token).await.is_err());
+        assert!(is_session_active(&sessions, &other_id).await.unwrap());
+
+        // Logging out everywhere takes the rest
+        revoke_user_sessions(&sessions, user_id).await.unwrap();
+        assert!(!is_session_active(&sessions, &other_id).await.unwrap());
+
+        database.drop(None).await.unwrap();
+    }
+}
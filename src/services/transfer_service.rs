@@ -0,0 +1,462 @@
+use std::env;
+
+use futures_util::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::Collection;
+
+use crate::db::{UserDb, VehicleDb, VehicleTransferDb};
+use crate::mailer::{app_base_url, EmailMessage, SharedMailer};
+use crate::models::transfer_model::{TransferInbox, TransferStatus, VehicleTransfer};
+use crate::models::vehicle_model::{OwnershipRecord, Vehicle};
+use crate::services::vehicle_service::{get_vehicle, VehicleError};
+use crate::utils::validation::FieldError;
+
+/// Days a recipient has to answer (`TRANSFER_EXPIRY_DAYS`, default 7)
+fn transfer_ttl() -> chrono::Duration {
+    let days = env::var("TRANSFER_EXPIRY_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|d| *d > 0)
+        .unwrap_or(7);
+    chrono::Duration::days(days)
+}
+
+fn parse_transfer_id(transfer_id: &str) -> Result<ObjectId, String> {
+    ObjectId::parse_str(transfer_id).map_err(|_| "Invalid transfer ID".to_string())
+}
+
+fn email_error(message: &str) -> VehicleError {
+    VehicleError::Invalid(vec![FieldError {
+        field: "email".to_string(),
+        message: message.to_string(),
+    }])
+}
+
+/// Mark pending transfers past their deadline as expired
+async fn expire_stale(collection: &Collection<VehicleTransfer>) -> Result<(), String> {
+    collection
+        .update_many(
+            doc! { "status": "pending", "expires_at": { "$lte": DateTime::now() } },
+            doc! { "$set": { "status": "expired" } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Offer a vehicle to the user registered under `email`, and mail them about it.
+/// An address without an account gets the same pending offer (and no mail),
+/// so the response never tells the sender whether someone is registered.
+/// A vehicle can have one pending transfer at a time. `None` if the vehicle
+/// isn't accessible.
+pub async fn initiate_transfer(
+    db: &VehicleTransferDb,
+    vehicles: &VehicleDb,
+    users: &UserDb,
+    mailer: &SharedMailer,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+    email: &str,
+) -> Result<Option<VehicleTransfer>, VehicleError> {
+    let email = email.trim();
+    if email.is_empty() {
+        return Err(email_error("is required"));
+    }
+    let Some(vehicle) = get_vehicle(vehicles, vehicle_id, owner).await? else {
+        return Ok(None);
+    };
+    let vehicle_id = vehicle.id.ok_or("Vehicle has no ID")?;
+
+    let (recipient, current_owner) = {
+        let collection = users.lock().await;
+        let recipient = collection
+            .find_one(doc! { "email": email }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        let current_owner = collection
+            .find_one(doc! { "_id": vehicle.user_id }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        (recipient, current_owner)
+    };
+    // Only the owner's own address can be refused; they already know it is registered
+    if recipient.as_ref().is_some_and(|r| r.id == Some(vehicle.user_id)) {
+        return Err(email_error("already owns this vehicle"));
+    }
+
+    let now = chrono::Utc::now();
+    let transfer = VehicleTransfer {
+        id: Some(ObjectId::new()),
+        vehicle_id,
+        from_user_id: vehicle.user_id,
+        to_user_id: recipient.as_ref().and_then(|r| r.id),
+        to_email: email.to_string(),
+        status: TransferStatus::Pending,
+        created_at: DateTime::from_chrono(now),
+        expires_at: DateTime::from_chrono(now + transfer_ttl()),
+        responded_at: None,
+    };
+
+    {
+        let collection = db.lock().await;
+        expire_stale(&collection).await?;
+        let pending = collection
+            .find_one(doc! { "vehicle_id": vehicle_id, "status": "pending" }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        if pending.is_some() {
+            return Err("A transfer for this vehicle is already pending".into());
+        }
+        collection
+            .insert_one(&transfer, None)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let Some(recipient) = recipient else {
+        return Ok(Some(transfer));
+    };
+    let sender = current_owner.map(|u| u.name).unwrap_or_else(|| "Another user".to_string());
+    let sent = mailer
+        .send(EmailMessage {
+            to: recipient.email,
+            subject: "A vehicle is being transferred to you".to_string(),
+            body: format!(
+                "Hi {},\n\n{} wants to transfer their {} {} to you. Sign in to accept or reject it before {}.\n\n{}",
+                recipient.name,
+                sender,
+                vehicle.make,
+                vehicle.model,
+                transfer.expires_at.to_chrono().format("%Y-%m-%d"),
+                app_base_url()
+            ),
+        })
+        .await;
+    if let Err(e) = sent {
+        eprintln!("Failed to send transfer notice: {}", e);
+    }
+
+    Ok(Some(transfer))
+}
+
+/// Pending transfers the user has received or sent
+pub async fn pending_transfers(db: &VehicleTransferDb, user_id: ObjectId) -> Result<TransferInbox, String> {
+    let collection = db.lock().await;
+    expire_stale(&collection).await?;
+
+    let newest_first = || FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let incoming: Vec<VehicleTransfer> = collection
+        .find(doc! { "to_user_id": user_id, "status": "pending" }, newest_first())
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let outgoing: Vec<VehicleTransfer> = collection
+        .find(doc! { "from_user_id": user_id, "status": "pending" }, newest_first())
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(TransferInbox {
+        incoming: incoming.into_iter().map(Into::into).collect(),
+        outgoing: outgoing.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Move a pending transfer to `status`, provided `party` matches the caller
+async fn resolve_transfer(
+    db: &VehicleTransferDb,
+    transfer_id: &str,
+    party: &str,
+    user_id: ObjectId,
+    status: TransferStatus,
+) -> Result<Option<VehicleTransfer>, String> {
+    let transfer_id = parse_transfer_id(transfer_id)?;
+    let status = bson::to_bson(&status).map_err(|e| e.to_string())?;
+
+    let collection = db.lock().await;
+    expire_stale(&collection).await?;
+    collection
+        .find_one_and_update(
+            doc! { "_id": transfer_id, party: user_id, "status": "pending" },
+            doc! { "$set": { "status": status, "responded_at": DateTime::now() } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Take ownership. The transfer is claimed first, then the owner is swapped
+/// in one update that only matches while the sender still owns the vehicle,
+/// recording them in `ownership_history`. `None` if there is no pending
+/// transfer for this user.
+pub async fn accept_transfer(
+    db: &VehicleTransferDb,
+    vehicles: &VehicleDb,
+    transfer_id: &str,
+    user_id: ObjectId,
+) -> Result<Option<Vehicle>, String> {
+    let Some(transfer) =
+        resolve_transfer(db, transfer_id, "to_user_id", user_id, TransferStatus::Accepted).await?
+    else {
+        return Ok(None);
+    };
+    let transfer_id = transfer.id.ok_or("Transfer has no ID")?;
+    let new_owner = transfer.to_user_id.ok_or("Transfer has no recipient")?;
+
+    let previous_owner = OwnershipRecord {
+        user_id: transfer.from_user_id,
+        transferred_at: DateTime::now(),
+        transfer_id,
+    };
+    let previous_owner = bson::to_bson(&previous_owner).map_err(|e| e.to_string())?;
+
+    let updated = {
+        let collection = vehicles.lock().await;
+        collection
+            .find_one_and_update(
+                doc! {
+                    "_id": transfer.vehicle_id,
+                    "user_id": transfer.from_user_id,
+                    "deleted_at": null,
+                },
+                doc! {
+                    "$set": { "user_id": new_owner, "updated_at": DateTime::now() },
+                    "$push": { "ownership_history": previous_owner },
+                },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    if updated.is_none() {
+        // Deleted or handed over some other way since the offer was made
+        let collection = db.lock().await;
+        collection
+            .update_one(
+                doc! { "_id": transfer_id },
+                doc! { "$set": { "status": "cancelled" } },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        return Err("The vehicle is no longer available for transfer".to_string());
+    }
+
+    Ok(updated)
+}
+
+/// Decline a transfer offered to the user. `None` if there is none pending.
+pub async fn reject_transfer(
+    db: &VehicleTransferDb,
+    transfer_id: &str,
+    user_id: ObjectId,
+) -> Result<Option<VehicleTransfer>, String> {
+    resolve_transfer(db, transfer_id, "to_user_id", user_id, TransferStatus::Rejected).await
+}
+
+/// Withdraw a transfer the user started. `None` if there is none pending.
+pub async fn cancel_transfer(
+    db: &VehicleTransferDb,
+    transfer_id: &str,
+    user_id: ObjectId,
+) -> Result<Option<VehicleTransfer>, String> {
+    resolve_transfer(db, transfer_id, "from_user_id", user_id, TransferStatus::Cancelled).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use mongodb::bson::Document;
+
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+    use crate::mailer::InMemoryMailer;
+    use crate::models::transfer_model::TransferSummary;
+
+    struct Fixture {
+        transfers: VehicleTransferDb,
+        vehicles: VehicleDb,
+        users: UserDb,
+        mailer: Arc<InMemoryMailer>,
+        owner: ObjectId,
+        recipient: ObjectId,
+        vehicle: ObjectId,
+    }
+
+    impl Fixture {
+        async fn new(database: &mongodb::Database) -> Self {
+            let (owner, recipient, vehicle) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+            let users = database.collection::<Document>("users");
+            for (id, email) in [(owner, "owner@example.com"), (recipient, "buyer@example.com")] {
+                users
+                    .insert_one(doc! { "_id": id, "name": email, "email": email, "password": "x" }, None)
+                    .await
+                    .unwrap();
+            }
+            database
+                .collection::<Document>("vehicles")
+                .insert_one(doc! { "_id": vehicle, "user_id": owner, "make": "Make", "model": "Model" }, None)
+                .await
+                .unwrap();
+
+            Fixture {
+                transfers: shared(database, "vehicle_transfers"),
+                vehicles: shared(database, "vehicles"),
+                users: shared(database, "users"),
+                mailer: Arc::new(InMemoryMailer::default()),
+                owner,
+                recipient,
+                vehicle,
+            }
+        }
+
+        async fn offer(&self, email: &str) -> Result<Option<VehicleTransfer>, VehicleError> {
+            let mailer: SharedMailer = self.mailer.clone();
+            initiate_transfer(
+                &self.transfers,
+                &self.vehicles,
+                &self.users,
+                &mailer,
+                &self.vehicle.to_hex(),
+                Some(self.owner),
+                email,
+            )
+            .await
+        }
+
+        async fn vehicle(&self) -> Vehicle {
+            let collection = self.vehicles.lock().await;
+            collection.find_one(doc! { "_id": self.vehicle }, None).await.unwrap().unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_recipients_get_the_same_response() {
+        let Some(database) = test_database().await else { return };
+        let fixture = Fixture::new(&database).await;
+
+        let unknown = fixture.offer("nobody@example.com").await.unwrap().unwrap();
+        assert_eq!(unknown.to_user_id, None);
+        assert!(fixture.mailer.sent().is_empty());
+        cancel_transfer(&fixture.transfers, &unknown.id.unwrap().to_hex(), fixture.owner)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let known = fixture.offer("buyer@example.com").await.unwrap().unwrap();
+        assert_eq!(known.to_user_id, Some(fixture.recipient));
+        assert_eq!(fixture.mailer.sent().pop().unwrap().to, "buyer@example.com");
+
+        // Same fields, same status: nothing in the body tells the two apart
+        let keys = |transfer: VehicleTransfer| {
+            let value = serde_json::to_value(TransferSummary::from(transfer)).unwrap();
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            (keys, value["status"].clone())
+        };
+        assert_eq!(keys(unknown), keys(known));
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn accept_moves_ownership_once_and_records_history() {
+        let Some(database) = test_database().await else { return };
+        let fixture = Fixture::new(&database).await;
+        let transfer = fixture.offer("buyer@example.com").await.unwrap().unwrap();
+        let transfer_id = transfer.id.unwrap().to_hex();
+
+        // Only the recipient can accept
+        assert!(accept_transfer(&fixture.transfers, &fixture.vehicles, &transfer_id, fixture.owner)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Two racing accepts: exactly one wins
+        let (first, second) = tokio::join!(
+            accept_transfer(&fixture.transfers, &fixture.vehicles, &transfer_id, fixture.recipient),
+            accept_transfer(&fixture.transfers, &fixture.vehicles, &transfer_id, fixture.recipient),
+        );
+        let accepted: Vec<Vehicle> = [first.unwrap(), second.unwrap()].into_iter().flatten().collect();
+        assert_eq!(accepted.len(), 1);
+
+        let vehicle = fixture.vehicle().await;
+        assert_eq!(vehicle.user_id, fixture.recipient);
+        assert_eq!(vehicle.ownership_history.len(), 1);
+        assert_eq!(vehicle.ownership_history[0].user_id, fixture.owner);
+        assert_eq!(vehicle.ownership_history[0].transfer_id, transfer.id.unwrap());
+
+        // The new owner can pass it on; history grows oldest first
+        let next = VehicleTransfer {
+            id: Some(ObjectId::new()),
+            from_user_id: fixture.recipient,
+            to_user_id: Some(fixture.owner),
+            to_email: "owner@example.com".to_string(),
+            ..transfer
+        };
+        fixture.transfers.lock().await.insert_one(&next, None).await.unwrap();
+        accept_transfer(&fixture.transfers, &fixture.vehicles, &next.id.unwrap().to_hex(), fixture.owner)
+            .await
+            .unwrap()
+            .unwrap();
+        let history: Vec<ObjectId> = fixture.vehicle().await.ownership_history.iter().map(|r| r.user_id).collect();
+        assert_eq!(history, [fixture.owner, fixture.recipient]);
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_offers_can_no_longer_be_accepted() {
+        let Some(database) = test_database().await else { return };
+        let fixture = Fixture::new(&database).await;
+        let transfer = fixture.offer("buyer@example.com").await.unwrap().unwrap();
+        let transfer_id = transfer.id.unwrap();
+
+        fixture
+            .transfers
+            .lock()
+            .await
+            .update_one(
+                doc! { "_id": transfer_id },
+                doc! { "$set": { "expires_at": DateTime::from_chrono(chrono::Utc::now() - chrono::Duration::minutes(1)) } },
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(accept_transfer(&fixture.transfers, &fixture.vehicles, &transfer_id.to_hex(), fixture.recipient)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(fixture.vehicle().await.user_id, fixture.owner);
+
+        let inbox = pending_transfers(&fixture.transfers, fixture.recipient).await.unwrap();
+        assert!(inbox.incoming.is_empty());
+        let stored = fixture
+            .transfers
+            .lock()
+            .await
+            .find_one(doc! { "_id": transfer_id }, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransferStatus::Expired);
+
+        // The vehicle is free to be offered again
+        assert!(fixture.offer("buyer@example.com").await.unwrap().is_some());
+
+        database.drop(None).await.unwrap();
+    }
+}
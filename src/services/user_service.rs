@@ -1,24 +1,94 @@
-use crate::db::UserDb;
+use crate::db::{RoleAuditDb, RoleDb, SessionDb, UserDb, UserTokenDb};
+use crate::mailer::{app_base_url, EmailMessage, SharedMailer};
+use crate::middlewares::auth_middleware::Claims;
+use crate::models::permission_model::PermissionSet;
 use crate::models::user_model::{LoginUser, RegisterUser, User, UserRole};
+use crate::models::role_model::RoleAuditEntry;
+use crate::models::user_token_model::TokenPurpose;
+use crate::services::session_service::{
+    access_token_ttl, create_session, revoke_user_sessions, rotate_session,
+};
+use crate::services::lockout_service::{record_failed_login, reset_failed_logins, seconds_until_unlock};
+use crate::services::role_service::{role_exists, role_permissions};
+use crate::services::mfa_service::{decode_mfa_token, issue_mfa_token, verify_second_factor};
+use crate::services::user_token_service::{consume_user_token, issue_user_token};
+use crate::utils::jwt;
 use bcrypt::{hash, verify, DEFAULT_COST};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use mongodb::bson::{doc, oid::ObjectId, DateTime};
-use serde::{Deserialize, Serialize};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use serde::Serialize;
 use std::env;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub role:UserRole,
-    pub exp: usize,
-}
+/// Storage key prefix for profile images
+pub const PROFILE_IMAGE_KEY_PREFIX: &str = "profiles";
 
 #[derive(Serialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Access token lifetime in seconds
+    pub expires_in: i64,
     pub user: UserResponse,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum LoginError {
+    /// Too many failed attempts; clients should wait `retry_after` seconds
+    #[error("Account temporarily locked due to too many failed login attempts")]
+    Locked { retry_after: i64 },
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for LoginError {
+    fn from(message: String) -> Self {
+        LoginError::Failed(message)
+    }
+}
+
+impl From<&str> for LoginError {
+    fn from(message: &str) -> Self {
+        LoginError::Failed(message.to_string())
+    }
+}
+
+/// Why an account could not be created or its profile changed
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError {
+    #[error("Invalid email address")]
+    InvalidEmail,
+    #[error("An account with this email already exists")]
+    EmailTaken,
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for AccountError {
+    fn from(message: String) -> Self {
+        AccountError::Failed(message)
+    }
+}
+
+impl From<&str> for AccountError {
+    fn from(message: &str) -> Self {
+        AccountError::Failed(message.to_string())
+    }
+}
+
+/// Result of the password step of a login
+pub enum LoginOutcome {
+    Authenticated(LoginResponse),
+    /// 2FA is on: the client must call `/login/2fa` with this token and a code
+    MfaRequired { mfa_token: String, expires_in: i64 },
+}
+
+#[derive(Serialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
 #[derive(Serialize)]
 pub struct UserResponse {
     pub id: String,
@@ -29,9 +99,18 @@ pub struct UserResponse {
 
 pub async fn register_user(
     db: &UserDb,
+    tokens: &UserTokenDb,
+    mailer: &SharedMailer,
     user: RegisterUser,
     profile_image_path: Option<String>, 
-) -> Result<User, String> {
+) -> Result<User, AccountError> {
+    if !is_plausible_email(&user.email) {
+        return Err(AccountError::InvalidEmail);
+    }
+    if email_in_use(db, &user.email, None).await? {
+        return Err(AccountError::EmailTaken);
+    }
+
     let hashed = hash(&user.password, DEFAULT_COST).map_err(|e| e.to_string())?;
     let new_user = User {
         id: Some(ObjectId::new()),
@@ -39,7 +118,18 @@ pub async fn register_user(
         email: user.email,
         password: hashed,
         profile_image: profile_image_path,
-        role:user.role.unwrap_or(UserRole::User),
+        // Self-registration never grants elevated roles; see assign_role
+        role: UserRole::user(),
+        email_verified: false,
+        totp_enabled: false,
+        totp_secret: None,
+        totp_pending_secret: None,
+        totp_last_step: None,
+        recovery_code_hashes: vec![],
+        failed_login_attempts: 0,
+        locked_until: None,
+        oidc_issuer: None,
+        oidc_subject: None,
         created_at: Some(DateTime::now()),
     };
 
@@ -50,40 +140,242 @@ pub async fn register_user(
         .map_err(|e| e.to_string())?;
     drop(collection);
 
+    // The account exists either way; a failed mail can be retried via the resend endpoint
+    if let Err(e) = send_verification_email(tokens, mailer, &new_user).await {
+        eprintln!("send_verification_email error: {}", e);
+    }
+
     Ok(new_user)
 }
 
-pub async fn login_user(db: &UserDb, creds: LoginUser) -> Result<LoginResponse, String> {
+/// Whether another account (other than `except`) already uses `email`
+async fn email_in_use(db: &UserDb, email: &str, except: Option<&ObjectId>) -> Result<bool, String> {
+    let mut filter = doc! { "email": email };
+    if let Some(id) = except {
+        filter.insert("_id", doc! { "$ne": id });
+    }
+    let collection = db.lock().await;
+    let existing = collection
+        .find_one(filter, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(existing.is_some())
+}
+
+/// Cheap syntactic check; real validation is the verification mail itself
+fn is_plausible_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
+
+/// Whether `login_user` refuses unverified accounts (`REQUIRE_EMAIL_VERIFICATION`, default true)
+fn email_verification_required() -> bool {
+    env::var("REQUIRE_EMAIL_VERIFICATION")
+        .map(|v| !matches!(v.to_lowercase().as_str(), "false" | "0" | "no"))
+        .unwrap_or(true)
+}
+
+/// Lifetime of an email verification link (`EMAIL_VERIFICATION_TTL_HOURS`, default 48)
+fn email_verification_ttl() -> chrono::Duration {
+    let hours = env::var("EMAIL_VERIFICATION_TTL_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(48);
+    chrono::Duration::hours(hours)
+}
+
+async fn send_verification_email(
+    tokens: &UserTokenDb,
+    mailer: &SharedMailer,
+    user: &User,
+) -> Result<(), String> {
+    let user_id = user.id.ok_or("User has no ID")?;
+    let token = issue_user_token(
+        tokens,
+        user_id,
+        TokenPurpose::EmailVerification,
+        email_verification_ttl(),
+    )
+    .await?;
+
+    mailer
+        .send(EmailMessage {
+            to: user.email.clone(),
+            subject: "Confirm your email address".to_string(),
+            body: format!(
+                "Hi {},\n\nPlease confirm your email address by opening the link below.\n\n{}/api/v1/verify-email?token={}",
+                user.name,
+                app_base_url(),
+                token
+            ),
+        })
+        .await
+}
+
+/// Mark the account behind a verification token as verified
+pub async fn verify_email(db: &UserDb, tokens: &UserTokenDb, token: &str) -> Result<(), String> {
+    let record = consume_user_token(tokens, token, TokenPurpose::EmailVerification).await?;
+
+    let collection = db.lock().await;
+    let result = collection
+        .update_one(
+            doc! { "_id": record.user_id },
+            doc! { "$set": { "email_verified": true, "updated_at": DateTime::now() } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if result.matched_count == 0 {
+        return Err("User not found".to_string());
+    }
+
+    Ok(())
+}
+
+/// Send a fresh verification link. Unknown or already verified emails succeed silently.
+pub async fn resend_verification_email(
+    db: &UserDb,
+    tokens: &UserTokenDb,
+    mailer: &SharedMailer,
+    email: &str,
+) -> Result<(), String> {
     let collection = db.lock().await;
     let user = collection
-        .find_one(doc! {"email": &creds.email}, None)
+        .find_one(doc! { "email": email, "email_verified": false }, None)
         .await
-        .map_err(|e| e.to_string())?
-        .ok_or("Invalid email or password")?;
+        .map_err(|e| e.to_string())?;
     drop(collection);
 
-    if !verify(&creds.password, &user.password).map_err(|e| e.to_string())? {
-        return Err("Invalid email or password".to_string());
+    match user {
+        Some(user) => send_verification_email(tokens, mailer, &user).await,
+        None => Ok(()),
     }
+}
 
-    let secret = env::var("JWT_SECRET").unwrap_or("mysecret".to_string());
+/// Sign a short-lived access token bound to `session_id`
+pub fn issue_access_token(user_id: &ObjectId, role: &UserRole, session_id: &ObjectId) -> Result<String, String> {
     let exp = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
+        .checked_add_signed(access_token_ttl())
         .unwrap()
         .timestamp() as usize;
 
     let claims = Claims {
-        sub: user.id.unwrap().to_hex(),
-        role: user.role.clone(),
+        sub: user_id.to_hex(),
+        role: role.clone(),
         exp,
+        jti: session_id.to_hex(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
-    .map_err(|e| e.to_string())?;
+    jwt::sign(&claims)
+}
+
+/// Open a new session for `user` and return its access/refresh token pair
+pub async fn issue_tokens(sessions: &SessionDb, user: &User) -> Result<TokenPair, String> {
+    let user_id = user.id.ok_or("User has no ID")?;
+    let (session, refresh_token) = create_session(sessions, user_id).await?;
+    let token = issue_access_token(&user_id, &user.role, &session.id.unwrap())?;
+
+    Ok(TokenPair {
+        token,
+        refresh_token,
+        expires_in: access_token_ttl().num_seconds(),
+    })
+}
+
+/// Count a failed password or second-factor check and build the error to return
+async fn login_failure(db: &UserDb, user_id: ObjectId, message: &str) -> LoginError {
+    match record_failed_login(db, user_id).await {
+        Ok(Some(retry_after)) => LoginError::Locked { retry_after },
+        Ok(None) => LoginError::Failed(message.to_string()),
+        Err(e) => LoginError::Failed(e),
+    }
+}
+
+pub async fn login_user(
+    db: &UserDb,
+    sessions: &SessionDb,
+    creds: LoginUser,
+) -> Result<LoginOutcome, LoginError> {
+    let collection = db.lock().await;
+    let user = collection
+        .find_one(doc! {"email": &creds.email}, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invalid email or password")?;
+    drop(collection);
+    let user_id = user.id.ok_or("User has no ID")?;
+
+    // Locked accounts don't get their password checked at all
+    if let Some(retry_after) = seconds_until_unlock(&user) {
+        return Err(LoginError::Locked { retry_after });
+    }
+
+    if !verify(&creds.password, &user.password).map_err(|e| e.to_string())? {
+        return Err(login_failure(db, user_id, "Invalid email or password").await);
+    }
+
+    if email_verification_required() && !user.email_verified {
+        return Err("Email address not verified".into());
+    }
+
+    finish_login(db, sessions, &user).await
+}
+
+/// Last step shared by every first-factor login (password, OIDC): either hand
+/// out tokens or, with 2FA on, an "mfa pending" token for `/login/2fa`
+pub async fn finish_login(
+    db: &UserDb,
+    sessions: &SessionDb,
+    user: &User,
+) -> Result<LoginOutcome, LoginError> {
+    let user_id = user.id.ok_or("User has no ID")?;
+
+    if user.totp_enabled {
+        // Counter is only reset once the second factor also succeeds
+        let (mfa_token, expires_in) = issue_mfa_token(&user_id)?;
+        return Ok(LoginOutcome::MfaRequired { mfa_token, expires_in });
+    }
+
+    reset_failed_logins(db, user_id).await?;
+    Ok(LoginOutcome::Authenticated(build_login_response(sessions, user).await?))
+}
+
+/// Second login step for accounts with 2FA enabled
+pub async fn complete_mfa_login(
+    db: &UserDb,
+    sessions: &SessionDb,
+    mfa_token: &str,
+    code: &str,
+) -> Result<LoginResponse, LoginError> {
+    let user_id = decode_mfa_token(mfa_token)?;
+
+    let collection = db.lock().await;
+    let user = collection
+        .find_one(doc! { "_id": user_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+    drop(collection);
+
+    if let Some(retry_after) = seconds_until_unlock(&user) {
+        return Err(LoginError::Locked { retry_after });
+    }
+
+    if let Err(e) = verify_second_factor(db, &user, code).await {
+        return Err(login_failure(db, user_id, &e).await);
+    }
+
+    reset_failed_logins(db, user_id).await?;
+    Ok(build_login_response(sessions, &user).await?)
+}
+
+async fn build_login_response(sessions: &SessionDb, user: &User) -> Result<LoginResponse, String> {
+    let tokens = issue_tokens(sessions, user).await?;
 
     let user_response = UserResponse {
         id: user.id.unwrap().to_hex(),
@@ -93,32 +385,68 @@ pub async fn login_user(db: &UserDb, creds: LoginUser) -> Result<LoginResponse,
     };
 
     Ok(LoginResponse {
-        token,
+        token: tokens.token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
         user: user_response,
     })
 }
 
+/// Rotate a refresh token and mint a fresh access token for the same session.
+/// The role is re-read from the user document so promotions/demotions apply on refresh.
+pub async fn refresh_tokens(
+    db: &UserDb,
+    sessions: &SessionDb,
+    refresh_token: &str,
+) -> Result<TokenPair, String> {
+    let (session, new_refresh_token) = rotate_session(sessions, refresh_token).await?;
+
+    let collection = db.lock().await;
+    let user = collection
+        .find_one(doc! { "_id": session.user_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+    drop(collection);
+
+    let token = issue_access_token(&session.user_id, &user.role, &session.id.unwrap())?;
+
+    Ok(TokenPair {
+        token,
+        refresh_token: new_refresh_token,
+        expires_in: access_token_ttl().num_seconds(),
+    })
+}
+
 pub async fn update_user(
     db: &UserDb,
+    tokens: &UserTokenDb,
+    mailer: &SharedMailer,
     id: &str,
     payload: RegisterUser,
-    user_id: &str,
-) -> Result<User, String> {
-    // Ensure user can only update their own account (basic authorization)
-    if id != user_id {
-        return Err("Unauthorized: You can only update your own profile".to_string());
-    }
-
+) -> Result<User, AccountError> {
+    // Authorization (own profile vs user:update:any) is enforced by the handler
     let obj_id = ObjectId::parse_str(id).map_err(|_| "Invalid user ID".to_string())?;
-    let collection = db.lock().await;
 
     // Find existing user
-    let existing_user = collection
+    let existing_user = db
+        .lock()
+        .await
         .find_one(doc! { "_id": &obj_id }, None)
         .await
         .map_err(|e| e.to_string())?
         .ok_or("User not found")?;
 
+    let email_changed = payload.email != existing_user.email;
+    if email_changed {
+        if !is_plausible_email(&payload.email) {
+            return Err(AccountError::InvalidEmail);
+        }
+        if email_in_use(db, &payload.email, Some(&obj_id)).await? {
+            return Err(AccountError::EmailTaken);
+        }
+    }
+
     // Hash new password if changed
     let new_password = if payload.password != existing_user.password {
         hash(&payload.password, DEFAULT_COST).map_err(|e| e.to_string())?
@@ -126,15 +454,21 @@ pub async fn update_user(
         existing_user.password
     };
 
+    // A changed address has to be verified again
+    let email_verified = existing_user.email_verified && !email_changed;
+
     let updated_doc = doc! {
         "$set": {
             "name": &payload.name,
             "email": &payload.email,
             "password": &new_password,
+            "email_verified": email_verified,
             "updated_at": DateTime::now(),
         }
     };
 
+    let collection = db.lock().await;
+
     // Update user in DB
     collection
         .update_one(doc! { "_id": &obj_id }, updated_doc, None)
@@ -147,7 +481,573 @@ pub async fn update_user(
         .await
         .map_err(|e| e.to_string())?
         .ok_or("Failed to fetch updated user")?;
+    drop(collection);
+
+    // Same path as register_user: the change stands, a failed mail can be resent
+    if email_changed {
+        if let Err(e) = send_verification_email(tokens, mailer, &updated_user).await {
+            eprintln!("send_verification_email error: {}", e);
+        }
+    }
 
     Ok(updated_user)
 }
 
+/// Minimum accepted password length
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Lifetime of a password reset link (`PASSWORD_RESET_TTL_MINUTES`, default 60)
+fn password_reset_ttl() -> chrono::Duration {
+    let minutes = env::var("PASSWORD_RESET_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(60);
+    chrono::Duration::minutes(minutes)
+}
+
+/// Mail a password reset link if `email` belongs to an account.
+/// Unknown emails succeed silently so the endpoint can't be used to enumerate users.
+pub async fn request_password_reset(
+    db: &UserDb,
+    tokens: &UserTokenDb,
+    mailer: &SharedMailer,
+    email: &str,
+) -> Result<(), String> {
+    let collection = db.lock().await;
+    let user = collection
+        .find_one(doc! { "email": email }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(collection);
+
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let user_id = user.id.ok_or("User has no ID")?;
+
+    let ttl = password_reset_ttl();
+    let token = issue_user_token(tokens, user_id, TokenPurpose::PasswordReset, ttl).await?;
+
+    mailer
+        .send(EmailMessage {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nUse the link below to choose a new password. It expires in {} minutes.\n\n{}/api/v1/reset-password?token={}\n\nIf you didn't ask for this, you can ignore this email.",
+                user.name,
+                ttl.num_minutes(),
+                app_base_url(),
+                token
+            ),
+        })
+        .await
+}
+
+/// Set a new password using a reset token. The token is burned and every
+/// existing session of the user is revoked.
+pub async fn reset_password(
+    db: &UserDb,
+    tokens: &UserTokenDb,
+    sessions: &SessionDb,
+    token: &str,
+    new_password: &str,
+) -> Result<(), String> {
+    if new_password.len() < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+
+    let record = consume_user_token(tokens, token, TokenPurpose::PasswordReset).await?;
+    let hashed = hash(new_password, DEFAULT_COST).map_err(|e| e.to_string())?;
+
+    let collection = db.lock().await;
+    let result = collection
+        .update_one(
+            doc! { "_id": record.user_id },
+            doc! { "$set": { "password": hashed, "updated_at": DateTime::now() } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(collection);
+
+    if result.matched_count == 0 {
+        return Err("User not found".to_string());
+    }
+
+    revoke_user_sessions(sessions, record.user_id).await
+}
+
+async fn record_role_change(
+    audit: &RoleAuditDb,
+    actor_id: Option<ObjectId>,
+    target_user_id: ObjectId,
+    previous_role: Option<String>,
+    new_role: &str,
+) -> Result<(), String> {
+    let entry = RoleAuditEntry {
+        id: None,
+        actor_id,
+        target_user_id,
+        previous_role,
+        new_role: new_role.to_string(),
+        created_at: DateTime::now(),
+    };
+
+    let collection = audit.lock().await;
+    collection
+        .insert_one(&entry, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Who is changing a role: their user ID and the permissions `RequirePermission` resolved
+pub struct RoleActor<'a> {
+    pub user_id: &'a str,
+    pub permissions: &'a PermissionSet,
+}
+
+/// Change a user's role, audit it, and revoke the user's sessions so the
+/// new permissions apply immediately rather than when access tokens expire.
+/// The last remaining Admin cannot be demoted. Actors can't change their own
+/// role, and can only assign roles (or replace roles) whose permissions they
+/// already hold themselves.
+pub async fn assign_role(
+    db: &UserDb,
+    roles: &RoleDb,
+    audit: &RoleAuditDb,
+    sessions: &SessionDb,
+    actor: RoleActor<'_>,
+    target_id: &str,
+    new_role: &str,
+) -> Result<User, String> {
+    let actor_obj_id = ObjectId::parse_str(actor.user_id).map_err(|_| "Invalid user ID".to_string())?;
+    let target_obj_id = ObjectId::parse_str(target_id).map_err(|_| "Invalid user ID".to_string())?;
+
+    if actor_obj_id == target_obj_id {
+        return Err("You cannot change your own role".to_string());
+    }
+    if !role_exists(roles, new_role).await? {
+        return Err(format!("Unknown role: {}", new_role));
+    }
+    let granted = role_permissions(roles, new_role).await?;
+    if let Some(missing) = actor.permissions.first_missing(&granted) {
+        return Err(format!("Cannot assign a role with a permission you do not hold: {}", missing));
+    }
+
+    let collection = db.lock().await;
+    let target = collection
+        .find_one(doc! { "_id": target_obj_id }, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    if target.role.as_str() == new_role {
+        return Ok(target);
+    }
+
+    // Nor demote someone who holds more than the actor does
+    let current = role_permissions(roles, target.role.as_str()).await?;
+    if let Some(missing) = actor.permissions.first_missing(&current) {
+        return Err(format!("Cannot change the role of a user with a permission you do not hold: {}", missing));
+    }
+
+    if target.role.as_str() == UserRole::ADMIN {
+        let admins = collection
+            .count_documents(doc! { "role": UserRole::ADMIN }, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        if admins <= 1 {
+            return Err("Cannot demote the last Admin".to_string());
+        }
+    }
+
+    let updated = collection
+        .find_one_and_update(
+            doc! { "_id": target_obj_id, "role": target.role.as_str() },
+            doc! { "$set": { "role": new_role, "updated_at": DateTime::now() } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User was modified concurrently, retry")?;
+    drop(collection);
+
+    record_role_change(
+        audit,
+        Some(actor_obj_id),
+        target_obj_id,
+        Some(target.role.0),
+        new_role,
+    )
+    .await?;
+    revoke_user_sessions(sessions, target_obj_id).await?;
+
+    Ok(updated)
+}
+
+/// Create the first Admin from `BOOTSTRAP_ADMIN_EMAIL` / `BOOTSTRAP_ADMIN_PASSWORD`
+/// (and optional `BOOTSTRAP_ADMIN_NAME`). Does nothing once any Admin exists.
+/// If the email already belongs to a user, that user is promoted instead, but
+/// only when the address is verified and the password matches theirs, so an
+/// account someone registered under that email first can't become the admin.
+pub async fn bootstrap_admin(db: &UserDb, audit: &RoleAuditDb) -> Result<(), String> {
+    let Ok(email) = env::var("BOOTSTRAP_ADMIN_EMAIL") else {
+        return Ok(());
+    };
+
+    let collection = db.lock().await;
+    let admins = collection
+        .count_documents(doc! { "role": UserRole::ADMIN }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if admins > 0 {
+        return Ok(());
+    }
+    let password = env::var("BOOTSTRAP_ADMIN_PASSWORD")
+        .map_err(|_| "BOOTSTRAP_ADMIN_PASSWORD must be set".to_string())?;
+
+    let existing = collection
+        .find_one(doc! { "email": &email }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (user_id, previous_role) = match existing {
+        Some(user) => {
+            let user_id = user.id.ok_or("User has no ID")?;
+            if !user.email_verified {
+                return Err(format!("Refusing to promote {}: email address is not verified", email));
+            }
+            if !verify(&password, &user.password).unwrap_or(false) {
+                return Err(format!(
+                    "Refusing to promote {}: BOOTSTRAP_ADMIN_PASSWORD does not match the account's password",
+                    email
+                ));
+            }
+            collection
+                .update_one(
+                    doc! { "_id": user_id },
+                    doc! { "$set": { "role": UserRole::ADMIN, "updated_at": DateTime::now() } },
+                    None,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            (user_id, Some(user.role.0))
+        }
+        None => {
+            if password.len() < MIN_PASSWORD_LEN {
+                return Err(format!(
+                    "BOOTSTRAP_ADMIN_PASSWORD must be at least {} characters",
+                    MIN_PASSWORD_LEN
+                ));
+            }
+
+            let admin = User {
+                id: Some(ObjectId::new()),
+                name: env::var("BOOTSTRAP_ADMIN_NAME").unwrap_or("Administrator".to_string()),
+                email: email.clone(),
+                password: hash(&password, DEFAULT_COST).map_err(|e| e.to_string())?,
+                profile_image: None,
+                role: UserRole::admin(),
+                email_verified: true,
+                totp_enabled: false,
+                totp_secret: None,
+                totp_pending_secret: None,
+                totp_last_step: None,
+                recovery_code_hashes: vec![],
+                failed_login_attempts: 0,
+                locked_until: None,
+                oidc_issuer: None,
+                oidc_subject: None,
+                created_at: Some(DateTime::now()),
+            };
+            collection
+                .insert_one(&admin, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            (admin.id.unwrap(), None)
+        }
+    };
+    drop(collection);
+
+    record_role_change(audit, None, user_id, previous_role, UserRole::ADMIN).await?;
+    println!("Bootstrapped admin account {}", email);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use mongodb::bson::Document;
+
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+    use crate::mailer::InMemoryMailer;
+
+    /// The token from the link in the newest reset mail
+    fn mailed_token(mailer: &InMemoryMailer) -> String {
+        let message = mailer.sent().pop().expect("no mail sent");
+        let (_, rest) = message.body.split_once("reset-password?token=").expect("no reset link");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn bootstrap_admin_only_promotes_verified_accounts_with_the_password() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let audit: RoleAuditDb = shared(&database, "role_audit");
+        let raw = database.collection::<Document>("users");
+        let role_of = |email: &'static str| {
+            let users = users.clone();
+            async move {
+                let collection = users.lock().await;
+                collection.find_one(doc! { "email": email }, None).await.unwrap().unwrap().role
+            }
+        };
+
+        raw.insert_one(
+            doc! {
+                "name": "Squatter",
+                "email": "admin@example.com",
+                "password": hash("bootstrap-password", 4).unwrap(),
+                "email_verified": false,
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        env::set_var("BOOTSTRAP_ADMIN_EMAIL", "admin@example.com");
+        env::set_var("BOOTSTRAP_ADMIN_PASSWORD", "bootstrap-password");
+
+        // Registered first but never verified
+        assert!(bootstrap_admin(&users, &audit).await.is_err());
+        assert_eq!(role_of("admin@example.com").await, UserRole::user());
+
+        // Verified, but the operator's password is not theirs
+        raw.update_one(
+            doc! { "email": "admin@example.com" },
+            doc! { "$set": { "email_verified": true, "password": hash("someone-elses", 4).unwrap() } },
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(bootstrap_admin(&users, &audit).await.is_err());
+        assert_eq!(role_of("admin@example.com").await, UserRole::user());
+
+        raw.update_one(
+            doc! { "email": "admin@example.com" },
+            doc! { "$set": { "password": hash("bootstrap-password", 4).unwrap() } },
+            None,
+        )
+        .await
+        .unwrap();
+        bootstrap_admin(&users, &audit).await.unwrap();
+        assert_eq!(role_of("admin@example.com").await, UserRole::admin());
+
+        env::remove_var("BOOTSTRAP_ADMIN_EMAIL");
+        env::remove_var("BOOTSTRAP_ADMIN_PASSWORD");
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn password_reset_tokens_are_single_use_and_expire() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let tokens: UserTokenDb = shared(&database, "user_tokens");
+        let sessions: SessionDb = shared(&database, "sessions");
+        let mailer = Arc::new(InMemoryMailer::default());
+        let shared_mailer: SharedMailer = mailer.clone();
+
+        database
+            .collection::<Document>("users")
+            .insert_one(
+                doc! {
+                    "name": "Reset",
+                    "email": "reset@example.com",
+                    "password": hash("old-password", 4).unwrap(),
+                    "email_verified": true,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Unknown addresses succeed without sending anything
+        request_password_reset(&users, &tokens, &shared_mailer, "nobody@example.com")
+            .await
+            .unwrap();
+        assert!(mailer.sent().is_empty());
+
+        request_password_reset(&users, &tokens, &shared_mailer, "reset@example.com")
+            .await
+            .unwrap();
+        let message = mailer.sent().pop().unwrap();
+        assert_eq!(message.to, "reset@example.com");
+        assert!(message.body.contains("/api/v1/reset-password?token="));
+        let token = mailed_token(&mailer);
+
+        reset_password(&users, &tokens, &sessions, &token, "new-password")
+            .await
+            .unwrap();
+        let stored = users
+            .lock()
+            .await
+            .find_one(doc! { "email": "reset@example.com" }, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(verify("new-password", &stored.password).unwrap());
+
+        // The same token can't be used twice
+        assert!(reset_password(&users, &tokens, &sessions, &token, "other-password")
+            .await
+            .is_err());
+
+        // A fresh token stops working once it has expired
+        request_password_reset(&users, &tokens, &shared_mailer, "reset@example.com")
+            .await
+            .unwrap();
+        let token = mailed_token(&mailer);
+        tokens
+            .lock()
+            .await
+            .update_many(
+                doc! { "used_at": null },
+                doc! { "$set": { "expires_at": DateTime::from_chrono(chrono::Utc::now() - chrono::Duration::minutes(1)) } },
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(reset_password(&users, &tokens, &sessions, &token, "other-password")
+            .await
+            .is_err());
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn email_changes_are_validated_and_reverified() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let tokens: UserTokenDb = shared(&database, "user_tokens");
+        let mailer = Arc::new(InMemoryMailer::default());
+        let shared_mailer: SharedMailer = mailer.clone();
+        let payload = |email: &str| RegisterUser {
+            name: "Profile".to_string(),
+            email: email.to_string(),
+            password: "password".to_string(),
+        };
+
+        let user = register_user(&users, &tokens, &shared_mailer, payload("first@example.com"), None)
+            .await
+            .unwrap();
+        register_user(&users, &tokens, &shared_mailer, payload("taken@example.com"), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            register_user(&users, &tokens, &shared_mailer, payload("taken@example.com"), None).await,
+            Err(AccountError::EmailTaken)
+        ));
+
+        let id = user.id.unwrap().to_hex();
+        users
+            .lock()
+            .await
+            .update_one(doc! { "email": "first@example.com" }, doc! { "$set": { "email_verified": true } }, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            update_user(&users, &tokens, &shared_mailer, &id, payload("not-an-email")).await,
+            Err(AccountError::InvalidEmail)
+        ));
+        assert!(matches!(
+            update_user(&users, &tokens, &shared_mailer, &id, payload("taken@example.com")).await,
+            Err(AccountError::EmailTaken)
+        ));
+
+        // Keeping the address leaves it verified and sends nothing
+        let sent = mailer.sent().len();
+        let updated = update_user(&users, &tokens, &shared_mailer, &id, payload("first@example.com"))
+            .await
+            .unwrap();
+        assert!(updated.email_verified);
+        assert_eq!(mailer.sent().len(), sent);
+
+        let updated = update_user(&users, &tokens, &shared_mailer, &id, payload("second@example.com"))
+            .await
+            .unwrap();
+        assert!(!updated.email_verified);
+        assert_eq!(mailer.sent().pop().unwrap().to, "second@example.com");
+
+        database.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn assign_role_refuses_self_assignment_and_escalation() {
+        let Some(database) = test_database().await else { return };
+        let users: UserDb = shared(&database, "users");
+        let roles: RoleDb = shared(&database, "roles");
+        let audit: RoleAuditDb = shared(&database, "role_audit");
+        let sessions: SessionDb = shared(&database, "sessions");
+        crate::services::role_service::seed_builtin_roles(&roles).await.unwrap();
+        database
+            .collection::<Document>("roles")
+            .insert_one(doc! { "name": "Moderator", "permissions": ["user:role:assign", "vehicle:read:any"] }, None)
+            .await
+            .unwrap();
+
+        let raw = database.collection::<Document>("users");
+        let mut ids = vec![];
+        for (email, role) in [("mod@example.com", "Moderator"), ("user@example.com", "User"), ("admin@example.com", "Admin")] {
+            let id = ObjectId::new();
+            raw.insert_one(doc! { "_id": id, "name": email, "email": email, "password": "x", "role": role }, None)
+                .await
+                .unwrap();
+            ids.push(id.to_hex());
+        }
+        let (moderator, user, admin) = (&ids[0], &ids[1], &ids[2]);
+        let moderator_permissions = PermissionSet::new(vec![
+            "user:role:assign".to_string(),
+            "vehicle:read:any".to_string(),
+        ]);
+        let actor = || RoleActor {
+            user_id: moderator,
+            permissions: &moderator_permissions,
+        };
+
+        // Not even to a role the actor could otherwise hand out
+        assert!(assign_role(&users, &roles, &audit, &sessions, actor(), moderator, "Moderator")
+            .await
+            .unwrap_err()
+            .contains("your own role"));
+
+        // Admin carries `*`, which the moderator does not hold
+        assert!(assign_role(&users, &roles, &audit, &sessions, actor(), user, UserRole::ADMIN)
+            .await
+            .unwrap_err()
+            .contains("do not hold"));
+        // Nor can they demote someone who holds more than they do
+        assert!(assign_role(&users, &roles, &audit, &sessions, actor(), admin, "Moderator")
+            .await
+            .unwrap_err()
+            .contains("do not hold"));
+
+        let role_of = |id: &str| {
+            let users = users.clone();
+            let id = ObjectId::parse_str(id).unwrap();
+            async move { users.lock().await.find_one(doc! { "_id": id }, None).await.unwrap().unwrap().role }
+        };
+        assert_eq!(role_of(user).await, UserRole::user());
+        assert_eq!(role_of(admin).await, UserRole::admin());
+
+        database.drop(None).await.unwrap();
+    }
+}
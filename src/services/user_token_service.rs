@@ -0,0 +1,72 @@
+use mongodb::bson::{doc, oid::ObjectId, to_bson, DateTime};
+
+use crate::db::UserTokenDb;
+use crate::models::user_token_model::{TokenPurpose, UserToken};
+use crate::utils::crypto::{random_token, sha256_hex};
+
+/// Issue a new token for `purpose`, invalidating any earlier unused one.
+/// Returns the clear-text token to be mailed.
+pub async fn issue_user_token(
+    db: &UserTokenDb,
+    user_id: ObjectId,
+    purpose: TokenPurpose,
+    ttl: chrono::Duration,
+) -> Result<String, String> {
+    let token = random_token(32);
+    let purpose_bson = to_bson(&purpose).map_err(|e| e.to_string())?;
+    let now = DateTime::now();
+
+    let record = UserToken {
+        id: None,
+        user_id,
+        purpose,
+        token_hash: sha256_hex(&token),
+        expires_at: DateTime::from_chrono(chrono::Utc::now() + ttl),
+        used_at: None,
+        created_at: Some(now),
+    };
+
+    let collection = db.lock().await;
+    collection
+        .update_many(
+            doc! { "user_id": user_id, "purpose": purpose_bson, "used_at": null },
+            doc! { "$set": { "used_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    collection
+        .insert_one(&record, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(token)
+}
+
+/// Atomically mark a token as used and return it.
+/// Fails if the token is unknown, expired, already used or issued for another purpose.
+pub async fn consume_user_token(
+    db: &UserTokenDb,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<UserToken, String> {
+    let purpose_bson = to_bson(&purpose).map_err(|e| e.to_string())?;
+    let now = DateTime::now();
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! {
+                "token_hash": sha256_hex(token),
+                "purpose": purpose_bson,
+                "used_at": null,
+                "expires_at": { "$gt": now },
+            },
+            doc! { "$set": { "used_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invalid or expired token".to_string())
+}
@@ -1,27 +1,108 @@
-use mongodb::bson::{doc, oid::ObjectId, DateTime};
-use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, Bson, DateTime, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use std::env;
 
-use crate::db::VehicleDb;
-use crate::models::vehicle_model::{CreateVehicle, Vehicle};
+use crate::db::{
+    FuelLogDb, MaintenanceDueDb, MaintenanceRuleDb, ServiceRecordDb, VehicleDb, VehicleDocumentDb,
+};
+use crate::models::vehicle_model::{
+    CreateVehicle, UpdateVehicle, Vehicle, VehicleListQuery, VehiclePage,
+};
+use crate::services::{
+    document_service::delete_vehicle_documents, fuel_service::delete_vehicle_fuel_entries,
+    maintenance_service::delete_vehicle_maintenance,
+    service_record_service::delete_vehicle_service_records,
+};
+use crate::storage::{discard, SharedFileStore};
+use crate::utils::{validation::FieldError, vin};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+const SORTABLE_FIELDS: &[&str] = &["make", "model", "year", "created_at"];
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Storage key prefix for vehicle uploads
+pub const VEHICLE_KEY_PREFIX: &str = "vehicles";
+
+/// How long soft-deleted vehicles are kept before purging, in days
+/// (`VEHICLE_RETENTION_DAYS`, default 30)
+fn vehicle_retention() -> chrono::Duration {
+    let days = env::var("VEHICLE_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(30);
+    chrono::Duration::days(days)
+}
+
+/// How often the purge runs, in minutes (`VEHICLE_PURGE_INTERVAL_MINUTES`, default 60)
+fn purge_interval() -> std::time::Duration {
+    let minutes = env::var("VEHICLE_PURGE_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(60);
+    std::time::Duration::from_secs(minutes * 60)
+}
+
+/// Why a vehicle could not be created or updated
+#[derive(Debug, thiserror::Error)]
+pub enum VehicleError {
+    /// The payload failed validation; one entry per bad field
+    #[error("Validation failed")]
+    Invalid(Vec<FieldError>),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for VehicleError {
+    fn from(message: String) -> Self {
+        VehicleError::Failed(message)
+    }
+}
+
+impl From<&str> for VehicleError {
+    fn from(message: &str) -> Self {
+        VehicleError::Failed(message.to_string())
+    }
+}
 
 /// Create a new vehicle record
 pub async fn create_vehicle(
     db: &VehicleDb,
     user_id: String,
-    payload: CreateVehicle,
+    mut payload: CreateVehicle,
     file_paths: Option<Vec<String>>,
-) -> Result<Vehicle, String> {
+) -> Result<Vehicle, VehicleError> {
     let user_obj_id = ObjectId::parse_str(&user_id).map_err(|_| "Invalid user ID".to_string())?;
 
+    payload.vin = payload.vin.filter(|v| !v.trim().is_empty());
+    if let Some(decoded) = payload.vin.as_deref().and_then(|raw| vin::decode(raw).ok()) {
+        if payload.make.trim().is_empty() {
+            payload.make = decoded.manufacturer.unwrap_or_default();
+        }
+        if payload.year.is_none() {
+            payload.year = decoded.model_year;
+        }
+        payload.vin = Some(decoded.vin);
+    }
+    payload.validate().map_err(VehicleError::Invalid)?;
+
     let new_vehicle = Vehicle {
         id: None,
         user_id: user_obj_id,
-        make: payload.make,
-        model: payload.model,
+        make: payload.make.trim().to_string(),
+        model: payload.model.trim().to_string(),
         year: payload.year,
+        legacy_year: None,
+        vin: payload.vin,
         files: file_paths,
         created_at: Some(DateTime::now()),
         updated_at: Some(DateTime::now()),
+        deleted_at: None,
+        ownership_history: vec![],
     };
 
     let collection = db.lock().await;
@@ -29,7 +110,7 @@ pub async fn create_vehicle(
     let insert_result = collection
         .insert_one(&new_vehicle, None)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(vehicle_write_error)?;
 
     let inserted_id = insert_result
         .inserted_id
@@ -45,45 +126,645 @@ pub async fn create_vehicle(
     Ok(vehicle)
 }
 
-/// Update a vehicle (Admin only)
+/// Update a vehicle. `owner` restricts the update to that user's vehicles; the
+/// check is part of the update filter, so there is no window between checking
+/// ownership and writing. New `file_paths` replace the stored files, which are
+/// then deleted. Returns `None` when no matching vehicle exists.
 pub async fn update_vehicle(
     db: &VehicleDb,
+    store: &SharedFileStore,
     id: &str,
-    payload: CreateVehicle,
+    owner: Option<ObjectId>,
+    payload: UpdateVehicle,
     file_paths: Option<Vec<String>>,
-) -> Result<Vehicle, String> {
+) -> Result<Option<Vehicle>, VehicleError> {
     let obj_id = ObjectId::parse_str(id).map_err(|_| "Invalid vehicle ID".to_string())?;
+    payload.validate().map_err(VehicleError::Invalid)?;
 
     let mut update_doc = doc! {
         "updated_at": DateTime::now()
     };
 
-    if !payload.make.is_empty() {
-        update_doc.insert("make", payload.make);
+    if let Some(make) = payload.make {
+        update_doc.insert("make", make.trim());
+    }
+    if let Some(model) = payload.model {
+        update_doc.insert("model", model.trim());
     }
-    if !payload.model.is_empty() {
-        update_doc.insert("model", payload.model);
+    if let Some(year) = payload.year {
+        update_doc.insert("year", year);
     }
-    if !payload.year.is_empty() {
-        update_doc.insert("year", payload.year);
+    if let Some(raw) = payload.vin {
+        update_doc.insert("vin", vin::normalize(&raw)?);
     }
-    if let Some(paths) = file_paths {
+    if let Some(paths) = &file_paths {
         update_doc.insert("files", paths);
     }
 
+    let mut filter = doc! { "_id": obj_id, "deleted_at": null };
+    if let Some(owner) = owner {
+        filter.insert("user_id", owner);
+    }
+
     let collection = db.lock().await;
 
-    let updated = collection
+    let before = collection
         .find_one_and_update(
-            doc! { "_id": obj_id },
+            filter,
             doc! { "$set": update_doc },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::Before)
+                .build(),
+        )
+        .await
+        .map_err(vehicle_write_error)?;
+    let Some(before) = before else {
+        return Ok(None);
+    };
+    let updated = collection
+        .find_one(doc! { "_id": obj_id }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(collection);
+
+    if let Some(new_files) = &file_paths {
+        for old in before.files.unwrap_or_default() {
+            if !new_files.contains(&old) {
+                discard(store, &old).await;
+            }
+        }
+    }
+
+    Ok(updated)
+}
+
+/// One-off conversion of the text `year` used by older records to an integer.
+/// Values that don't parse are moved to `legacy_year` and `year` is cleared.
+/// Returns (converted, unconvertible).
+pub async fn migrate_string_years(db: &VehicleDb) -> Result<(u64, u64), String> {
+    let converted = doc! {
+        "$convert": {
+            "input": { "$trim": { "input": "$year" } },
+            "to": "int",
+            "onError": null,
+            "onNull": null,
+        }
+    };
+
+    let collection = db.lock().await;
+    let result = collection
+        .update_many(
+            doc! { "year": { "$type": "string" } },
+            vec![doc! {
+                "$set": {
+                    "year": converted.clone(),
+                    "legacy_year": {
+                        "$cond": [{ "$eq": [converted, null] }, "$year", "$$REMOVE"]
+                    },
+                }
+            }],
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let unconvertible = collection
+        .count_documents(doc! { "legacy_year": { "$exists": true }, "year": null }, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok((result.modified_count, unconvertible))
+}
+
+/// Fetch one vehicle. `owner` restricts the lookup to that user's vehicles,
+/// so other people's records look exactly like missing ones.
+pub async fn get_vehicle(
+    db: &VehicleDb,
+    id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<Vehicle>, String> {
+    let obj_id = ObjectId::parse_str(id).map_err(|_| "Invalid vehicle ID".to_string())?;
+
+    let mut filter = doc! { "_id": obj_id, "deleted_at": null };
+    if let Some(owner) = owner {
+        filter.insert("user_id", owner);
+    }
+
+    let collection = db.lock().await;
+    collection
+        .find_one(filter, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Resolve the parent of a nested resource (service records, fuel log, ...),
+/// applying the same `owner` scoping as vehicle reads. `None` when it doesn't
+/// exist, is deleted, or belongs to someone else.
+pub async fn accessible_vehicle_id(
+    db: &VehicleDb,
+    vehicle_id: &str,
+    owner: Option<ObjectId>,
+) -> Result<Option<ObjectId>, String> {
+    Ok(get_vehicle(db, vehicle_id, owner)
+        .await?
+        .and_then(|vehicle| vehicle.id))
+}
+
+/// List vehicles with filters and keyset pagination.
+/// Pages are ordered by the sort field with `_id` as tie-breaker, and the cursor
+/// carries both values of the last row, so inserts between requests never
+/// shift or repeat rows.
+pub async fn list_vehicles(
+    db: &VehicleDb,
+    owner: Option<ObjectId>,
+    query: VehicleListQuery,
+) -> Result<VehiclePage, String> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(format!("limit must be between 1 and {}", MAX_PAGE_SIZE));
+    }
+
+    let (sort_field, direction) = match query.sort.as_deref() {
+        None => ("_id", -1),
+        Some(sort) => {
+            let (field, direction) = match sort.strip_prefix('-') {
+                Some(field) => (field, -1),
+                None => (sort, 1),
+            };
+            let field = SORTABLE_FIELDS
+                .iter()
+                .find(|f| **f == field)
+                .ok_or(format!("Cannot sort by {}", field))?;
+            (*field, direction)
+        }
+    };
+
+    let mut filter = doc! { "deleted_at": null };
+    if let Some(owner) = owner {
+        filter.insert("user_id", owner);
+    }
+    if let Some(make) = query.make.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        filter.insert("make", exact_ignore_case(make));
+    }
+    if let Some(model) = query.model.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        filter.insert("model", exact_ignore_case(model));
+    }
+    let mut year_range = Document::new();
+    if let Some(min) = query.year_min {
+        year_range.insert("$gte", min);
+    }
+    if let Some(max) = query.year_max {
+        year_range.insert("$lte", max);
+    }
+    if !year_range.is_empty() {
+        filter.insert("year", year_range);
+    }
+
+    let collection = db.lock().await;
+
+    let total = if query.include_total {
+        Some(
+            collection
+                .count_documents(filter.clone(), None)
+                .await
+                .map_err(|e| e.to_string())?,
+        )
+    } else {
+        None
+    };
+
+    if let Some(cursor) = &query.cursor {
+        let (last_value, last_id) = decode_cursor(cursor)?;
+        let op = if direction == 1 { "$gt" } else { "$lt" };
+        if sort_field == "_id" {
+            filter.insert("_id", doc! { op: last_id });
+        } else {
+            let cursor_is_null = last_value == Bson::Null;
+            let mut after = vec![
+                doc! { sort_field: { op: last_value.clone() } },
+                doc! { sort_field: last_value, "_id": { op: last_id } },
+            ];
+            // Nulls (e.g. legacy `year: None`) sort first ascending and last
+            // descending, but `$gt`/`$lt` never match them, so say so explicitly
+            if direction == 1 && cursor_is_null {
+                after.push(doc! { sort_field: { "$ne": null } });
+            } else if direction == -1 && !cursor_is_null {
+                after.push(doc! { sort_field: null });
+            }
+            filter.insert("$or", after);
+        }
+    }
+
+    let mut sort = doc! { sort_field: direction };
+    if sort_field != "_id" {
+        sort.insert("_id", direction);
+    }
+    let options = FindOptions::builder()
+        .sort(sort)
+        .limit(limit + 1)
+        .build();
+
+    let mut vehicles: Vec<Vehicle> = collection
+        .find(filter, options)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let next_cursor = if vehicles.len() as i64 > limit {
+        vehicles.truncate(limit as usize);
+        vehicles
+            .last()
+            .map(|last| encode_cursor(last, sort_field))
+            .transpose()?
+    } else {
+        None
+    };
+
+    Ok(VehiclePage {
+        vehicles,
+        next_cursor,
+        total,
+    })
+}
+
+/// Soft-delete a vehicle. `owner` restricts it to that user's vehicles.
+/// Returns `false` when there was no matching live vehicle.
+pub async fn delete_vehicle(db: &VehicleDb, id: &str, owner: Option<ObjectId>) -> Result<bool, String> {
+    let obj_id = ObjectId::parse_str(id).map_err(|_| "Invalid vehicle ID".to_string())?;
+
+    let mut filter = doc! { "_id": obj_id, "deleted_at": null };
+    if let Some(owner) = owner {
+        filter.insert("user_id", owner);
+    }
+
+    let now = DateTime::now();
+    let collection = db.lock().await;
+    let result = collection
+        .update_one(
+            filter,
+            doc! { "$set": { "deleted_at": now, "updated_at": now } },
+            None,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(result.matched_count == 1)
+}
+
+/// Undo a soft-delete, as long as the record hasn't been purged yet
+pub async fn restore_vehicle(db: &VehicleDb, id: &str) -> Result<Option<Vehicle>, String> {
+    let obj_id = ObjectId::parse_str(id).map_err(|_| "Invalid vehicle ID".to_string())?;
+
+    let collection = db.lock().await;
+    collection
+        .find_one_and_update(
+            doc! { "_id": obj_id, "deleted_at": { "$ne": null } },
+            doc! {
+                "$unset": { "deleted_at": "" },
+                "$set": { "updated_at": DateTime::now() },
+            },
             FindOneAndUpdateOptions::builder()
                 .return_document(ReturnDocument::After)
                 .build(),
         )
         .await
+        .map_err(|e| e.to_string())
+}
+
+/// The collections whose records belong to a vehicle and go when it is purged
+#[derive(Clone)]
+pub struct VehicleChildDbs {
+    pub services: ServiceRecordDb,
+    pub fuel: FuelLogDb,
+    pub maintenance_rules: MaintenanceRuleDb,
+    pub maintenance_due: MaintenanceDueDb,
+    pub documents: VehicleDocumentDb,
+}
+
+/// Delete everything recorded against a vehicle, returning the keys of the
+/// files those records referenced
+async fn delete_vehicle_children(children: &VehicleChildDbs, vehicle_id: ObjectId) -> Result<Vec<String>, String> {
+    let mut keys = delete_vehicle_service_records(&children.services, vehicle_id).await?;
+    keys.extend(delete_vehicle_documents(&children.documents, vehicle_id).await?);
+    delete_vehicle_fuel_entries(&children.fuel, vehicle_id).await?;
+    delete_vehicle_maintenance(&children.maintenance_rules, &children.maintenance_due, vehicle_id).await?;
+    Ok(keys)
+}
+
+/// Hard-delete vehicles soft-deleted more than `retention` ago, along with
+/// their service records, fuel log, maintenance rules and documents, and every
+/// file those reference. Returns how many vehicles were removed.
+pub async fn purge_deleted_vehicles(
+    db: &VehicleDb,
+    children: &VehicleChildDbs,
+    store: &SharedFileStore,
+    retention: chrono::Duration,
+) -> Result<u64, String> {
+    let cutoff = DateTime::from_chrono(chrono::Utc::now() - retention);
+    let expired = doc! { "deleted_at": { "$lt": cutoff } };
+
+    let candidates: Vec<Vehicle> = {
+        let collection = db.lock().await;
+        collection
+            .find(expired.clone(), None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    let mut purged = 0;
+    for vehicle in candidates {
+        let Some(id) = vehicle.id else { continue };
+
+        // Re-check the cutoff so a vehicle restored in the meantime survives
+        let mut filter = expired.clone();
+        filter.insert("_id", id);
+        let result = {
+            let collection = db.lock().await;
+            collection
+                .delete_one(filter, None)
+                .await
+                .map_err(|e| e.to_string())?
+        };
+        if result.deleted_count == 0 {
+            continue;
+        }
+        purged += 1;
+
+        let mut keys = vehicle.files.unwrap_or_default();
+        keys.extend(delete_vehicle_children(children, id).await?);
+        for key in keys {
+            discard(store, &key).await;
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Run the purge on a fixed interval for the life of the process
+pub fn spawn_vehicle_purge(db: VehicleDb, children: VehicleChildDbs, store: SharedFileStore) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(purge_interval());
+        loop {
+            interval.tick().await;
+            match purge_deleted_vehicles(&db, &children, &store, vehicle_retention()).await {
+                Ok(0) => {}
+                Ok(count) => println!("Purged {} deleted vehicle(s)", count),
+                Err(e) => eprintln!("Vehicle purge failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Turn the unique-VIN violation into a readable message
+fn vehicle_write_error(err: mongodb::error::Error) -> String {
+    let duplicate = match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY,
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY,
+        _ => false,
+    };
+    if duplicate {
+        "A vehicle with this VIN already exists".to_string()
+    } else {
+        err.to_string()
+    }
+}
+
+/// Case-insensitive equality, with the user's input matched literally
+fn exact_ignore_case(value: &str) -> Document {
+    let escaped: String = value
+        .chars()
+        .flat_map(|c| {
+            let escape = "\\^$.|?*+()[]{}".contains(c);
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect();
+    doc! { "$regex": format!("^{}$", escaped), "$options": "i" }
+}
+
+fn encode_cursor(vehicle: &Vehicle, sort_field: &str) -> Result<String, String> {
+    let id = vehicle.id.ok_or("Vehicle has no ID")?;
+    let value = bson::to_document(vehicle)
         .map_err(|e| e.to_string())?
-        .ok_or("Vehicle not found")?;
+        .get(sort_field)
+        .cloned()
+        .unwrap_or(Bson::Null);
 
-    Ok(updated)
+    let mut bytes = Vec::new();
+    doc! { "v": value, "id": id }
+        .to_writer(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(Bson, ObjectId), String> {
+    let invalid = || "Invalid cursor".to_string();
+
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let cursor = Document::from_reader(bytes.as_slice()).map_err(|_| invalid())?;
+    let id = cursor.get_object_id("id").map_err(|_| invalid())?;
+    let value = cursor.get("v").cloned().ok_or_else(invalid)?;
+    Ok((value, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{shared, test_database};
+    use crate::storage::test_support::{put_bytes, temp_store};
+
+    #[tokio::test]
+    async fn update_deletes_the_files_it_replaces() {
+        let Some(database) = test_database().await else { return };
+        let (store, root) = temp_store();
+        let vehicles: VehicleDb = shared(&database, "vehicles");
+
+        for key in ["vehicles/old.jpg", "vehicles/kept.jpg", "vehicles/new.jpg"] {
+            put_bytes(store.as_ref(), key, b"contents").await;
+        }
+        let vehicle_id = ObjectId::new();
+        database
+            .collection::<Document>("vehicles")
+            .insert_one(
+                doc! {
+                    "_id": vehicle_id,
+                    "user_id": ObjectId::new(),
+                    "make": "Make",
+                    "model": "Model",
+                    "files": ["vehicles/old.jpg", "vehicles/kept.jpg"],
+                    "created_at": DateTime::now(),
+                    "updated_at": DateTime::now(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let new_files = vec!["vehicles/kept.jpg".to_string(), "vehicles/new.jpg".to_string()];
+        let updated = update_vehicle(
+            &vehicles,
+            &store,
+            &vehicle_id.to_hex(),
+            None,
+            UpdateVehicle::default(),
+            Some(new_files.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(updated.files, Some(new_files));
+        assert!(store.stat("vehicles/old.jpg").await.unwrap().is_none());
+        assert!(store.stat("vehicles/kept.jpg").await.unwrap().is_some());
+        assert!(store.stat("vehicles/new.jpg").await.unwrap().is_some());
+
+        database.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+
+    #[tokio::test]
+    async fn purge_removes_child_records_and_their_files() {
+        let Some(database) = test_database().await else { return };
+        let (store, root) = temp_store();
+        let vehicles: VehicleDb = shared(&database, "vehicles");
+        let children = VehicleChildDbs {
+            services: shared(&database, "service_records"),
+            fuel: shared(&database, "fuel_entries"),
+            maintenance_rules: shared(&database, "maintenance_rules"),
+            maintenance_due: shared(&database, "maintenance_due"),
+            documents: shared(&database, "vehicle_documents"),
+        };
+        let raw = |name: &str| database.collection::<Document>(name);
+
+        for key in ["vehicles/photo.jpg", "services/receipt.pdf", "documents/policy.pdf"] {
+            put_bytes(store.as_ref(), key, b"contents").await;
+        }
+
+        let vehicle_id = ObjectId::new();
+        let long_ago = DateTime::from_chrono(chrono::Utc::now() - chrono::Duration::days(60));
+        raw("vehicles")
+            .insert_one(
+                doc! {
+                    "_id": vehicle_id,
+                    "user_id": ObjectId::new(),
+                    "make": "Make",
+                    "model": "Model",
+                    "files": ["vehicles/photo.jpg"],
+                    "created_at": long_ago,
+                    "updated_at": long_ago,
+                    "deleted_at": long_ago,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        raw("service_records")
+            .insert_one(
+                doc! {
+                    "vehicle_id": vehicle_id,
+                    "date": long_ago,
+                    "odometer": 1000_i64,
+                    "category": "oil_change",
+                    "cost": 50.0,
+                    "receipts": ["services/receipt.pdf"],
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        raw("vehicle_documents")
+            .insert_one(
+                doc! { "vehicle_id": vehicle_id, "kind": "insurance", "file": "documents/policy.pdf" },
+                None,
+            )
+            .await
+            .unwrap();
+        raw("fuel_entries").insert_one(doc! { "vehicle_id": vehicle_id }, None).await.unwrap();
+        raw("maintenance_rules").insert_one(doc! { "vehicle_id": vehicle_id }, None).await.unwrap();
+        raw("maintenance_rules").insert_one(doc! { "vehicle_id": null }, None).await.unwrap();
+        raw("maintenance_due").insert_one(doc! { "vehicle_id": vehicle_id }, None).await.unwrap();
+
+        let purged = purge_deleted_vehicles(&vehicles, &children, &store, chrono::Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(purged, 1);
+
+        for name in ["vehicles", "service_records", "vehicle_documents", "fuel_entries", "maintenance_due"] {
+            assert_eq!(raw(name).count_documents(None, None).await.unwrap(), 0, "{}", name);
+        }
+        // The template, which belongs to no vehicle, stays
+        assert_eq!(raw("maintenance_rules").count_documents(None, None).await.unwrap(), 1);
+        for key in ["vehicles/photo.jpg", "services/receipt.pdf", "documents/policy.pdf"] {
+            assert!(store.stat(key).await.unwrap().is_none(), "{}", key);
+        }
+
+        database.drop(None).await.unwrap();
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+
+    #[tokio::test]
+    async fn year_pages_cross_the_null_year_block() {
+        let Some(database) = test_database().await else { return };
+        let vehicles: VehicleDb = shared(&database, "vehicles");
+        let raw = database.collection::<Document>("vehicles");
+        let owner = ObjectId::new();
+
+        // Legacy records have `year: null` or no year at all
+        let years = [Some(2005), None, Some(2001), None, Some(2005), Some(2010), None];
+        let mut inserted = vec![];
+        for (i, year) in years.into_iter().enumerate() {
+            let id = ObjectId::new();
+            let mut vehicle = doc! { "_id": id, "user_id": owner, "make": "Make", "model": "Model" };
+            match year {
+                Some(year) => {
+                    vehicle.insert("year", year);
+                }
+                None if i == 1 => {}
+                None => {
+                    vehicle.insert("year", Bson::Null);
+                }
+            }
+            raw.insert_one(vehicle, None).await.unwrap();
+            inserted.push((year, id));
+        }
+
+        for (sort, direction) in [("year", 1), ("-year", -1)] {
+            // Mongo orders nulls before numbers; descending reverses both
+            let mut expected = inserted.clone();
+            expected.sort_by_key(|(year, id)| (year.map_or(i64::MIN, i64::from), *id));
+            if direction == -1 {
+                expected.reverse();
+            }
+            let expected: Vec<ObjectId> = expected.into_iter().map(|(_, id)| id).collect();
+
+            let mut seen = vec![];
+            let mut cursor = None;
+            loop {
+                let query = VehicleListQuery {
+                    make: None,
+                    model: None,
+                    year_min: None,
+                    year_max: None,
+                    owner: None,
+                    sort: Some(sort.to_string()),
+                    cursor: cursor.take(),
+                    limit: Some(2),
+                    include_total: false,
+                };
+                let page = list_vehicles(&vehicles, Some(owner), query).await.unwrap();
+                seen.extend(page.vehicles.iter().map(|v| v.id.unwrap()));
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected, "sort={sort}");
+        }
+
+        database.drop(None).await.unwrap();
+    }
 }
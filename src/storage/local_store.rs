@@ -0,0 +1,225 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use axum::async_trait;
+use futures_util::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use super::{is_valid_key, ByteRange, ByteStream, FileStore, ObjectMeta, StoredObject};
+
+/// Files on the local disk, one per key under `root`. Only suitable for a
+/// single instance or a shared volume. A file's content type is kept next to
+/// it in a hidden `.<name>.type` file.
+pub struct LocalFileStore {
+    root: PathBuf,
+}
+
+impl LocalFileStore {
+    pub fn new(root: String) -> Self {
+        LocalFileStore { root: PathBuf::from(root) }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, String> {
+        if !is_valid_key(key) {
+            return Err("Invalid storage key".to_string());
+        }
+        Ok(self.root.join(key))
+    }
+
+    async fn meta(&self, key: &str, path: &Path) -> Result<Option<ObjectMeta>, String> {
+        let content_type = match tokio::fs::read_to_string(type_path(path)).await {
+            Ok(content_type) => Some(content_type.trim().to_string()),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let metadata = match tokio::fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let modified = metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from);
+
+        Ok(Some(ObjectMeta {
+            key: key.to_string(),
+            size: metadata.len(),
+            content_type,
+            // Changes whenever the file is rewritten
+            etag: modified.map(|m| {
+                format!("\"{:x}-{:x}\"", metadata.len(), m.timestamp_nanos_opt().unwrap_or_default())
+            }),
+            last_modified: modified,
+        }))
+    }
+}
+
+/// Where the content type of the file at `path` is recorded
+fn type_path(path: &Path) -> PathBuf {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    path.with_file_name(format!(".{}.type", name))
+}
+
+async fn remove_if_present(path: &Path) -> Result<(), String> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[async_trait]
+impl FileStore for LocalFileStore {
+    /// Written to a temporary file first, so readers never see half an upload
+    async fn put(
+        &self,
+        key: &str,
+        mut body: ByteStream<'_>,
+        content_type: Option<&str>,
+    ) -> Result<ObjectMeta, String> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let temp = path.with_file_name(format!(".{}.part", Uuid::new_v4()));
+        let types = type_path(&path);
+        let written = async {
+            let mut file = tokio::fs::File::create(&temp).await?;
+            while let Some(chunk) = body.next().await {
+                file.write_all(&chunk?).await?;
+            }
+            file.sync_all().await?;
+            // Recorded first, so the file never shows up without its type
+            match content_type {
+                Some(content_type) => tokio::fs::write(&types, content_type).await?,
+                None => remove_if_present(&types).await.map_err(std::io::Error::other)?,
+            }
+            tokio::fs::rename(&temp, &path).await
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&temp).await;
+            let _ = tokio::fs::remove_file(&types).await;
+            return Err(e.to_string());
+        }
+
+        self.meta(key, &path)
+            .await?
+            .ok_or("Stored file disappeared".to_string())
+    }
+
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, String> {
+        let path = self.path_for(key)?;
+        let Some(meta) = self.meta(key, &path).await? else {
+            return Ok(None);
+        };
+        let mut file = match tokio::fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let body = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                ReaderStream::new(file.take(range.end - range.start + 1)).boxed()
+            }
+            None => ReaderStream::new(file).boxed(),
+        };
+
+        Ok(Some(StoredObject { meta, body }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let path = self.path_for(key)?;
+        remove_if_present(&path).await?;
+        remove_if_present(&type_path(&path)).await
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<ObjectMeta>, String> {
+        let path = self.path_for(key)?;
+        self.meta(key, &path).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, String> {
+        let mut objects = vec![];
+        let mut pending = vec![(self.root.clone(), String::new())];
+
+        while let Some((dir, dir_key)) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+                let Ok(name) = entry.file_name().into_string() else { continue };
+                // In-flight temporary files
+                if name.starts_with('.') {
+                    continue;
+                }
+                let key = format!("{}{}", dir_key, name);
+                let file_type = entry.file_type().await.map_err(|e| e.to_string())?;
+
+                if file_type.is_dir() {
+                    let sub_key = format!("{}/", key);
+                    // Only descend where matching keys can be
+                    if sub_key.starts_with(prefix) || prefix.starts_with(&sub_key) {
+                        pending.push((entry.path(), sub_key));
+                    }
+                } else if file_type.is_file() && key.starts_with(prefix) {
+                    if let Some(meta) = self.meta(&key, &entry.path()).await? {
+                        objects.push(meta);
+                    }
+                }
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::{exercise_store, temp_store};
+
+    #[tokio::test]
+    async fn local_store_round_trip() {
+        let (store, root) = temp_store();
+        exercise_store(store.as_ref()).await;
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+
+    #[tokio::test]
+    async fn type_sidecars_and_temp_files_are_not_listed() {
+        let (store, root) = temp_store();
+        let body = futures_util::stream::once(async { Ok(axum::body::Bytes::from_static(b"%PDF-")) }).boxed();
+        store.put("documents/scan.pdf", body, Some("application/pdf")).await.unwrap();
+        tokio::fs::write(root.join("documents/.leftover.part"), b"half").await.unwrap();
+
+        let listed: Vec<String> = store.list("").await.unwrap().into_iter().map(|m| m.key).collect();
+        assert_eq!(listed, ["documents/scan.pdf"]);
+
+        store.delete("documents/scan.pdf").await.unwrap();
+        assert!(!type_path(&root.join("documents/scan.pdf")).exists());
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+
+    #[tokio::test]
+    async fn keys_cannot_leave_the_root() {
+        let (store, root) = temp_store();
+        for key in ["../escape.txt", "/etc/passwd", "a/../../b", ""] {
+            assert!(store.stat(key).await.is_err(), "{}", key);
+            assert!(store.delete(key).await.is_err(), "{}", key);
+        }
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+}
@@ -0,0 +1,196 @@
+use std::env;
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::body::Bytes;
+use futures_util::stream::BoxStream;
+use serde::Serialize;
+use uuid::Uuid;
+
+pub mod local_store;
+pub mod s3_store;
+
+pub use local_store::LocalFileStore;
+pub use s3_store::S3FileStore;
+
+/// What a store knows about one object
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectMeta {
+    pub key: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+    pub last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    pub etag: Option<String>,
+}
+
+/// File contents as they arrive, chunk by chunk
+pub type ByteStream<'a> = BoxStream<'a, Result<Bytes, std::io::Error>>;
+
+/// Inclusive byte offsets, as in an HTTP `Range` header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// An object's metadata and its contents, read lazily
+pub struct StoredObject {
+    pub meta: ObjectMeta,
+    pub body: ByteStream<'static>,
+}
+
+/// Where uploaded files live. Records store the object key, never a
+/// filesystem path, so every replica sees the same files.
+#[async_trait]
+pub trait FileStore: Send + Sync {
+    /// Store `body` under `key` without holding the whole file in memory. If
+    /// the stream fails, nothing is left behind under `key`.
+    async fn put(
+        &self,
+        key: &str,
+        body: ByteStream<'_>,
+        content_type: Option<&str>,
+    ) -> Result<ObjectMeta, String>;
+    /// `None` if there is no such object. With a `range`, only those bytes
+    /// are streamed; `meta` still describes the whole object.
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, String>;
+    /// Deleting a missing object is not an error
+    async fn delete(&self, key: &str) -> Result<(), String>;
+    async fn stat(&self, key: &str) -> Result<Option<ObjectMeta>, String>;
+    /// Every object whose key starts with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, String>;
+}
+
+pub type SharedFileStore = Arc<dyn FileStore>;
+
+/// Build the store selected by `STORAGE_BACKEND`:
+///  - `local` → files under `STORAGE_LOCAL_ROOT` (default `./uploads`)
+///  - `s3`    → an S3-compatible bucket configured via `S3_*` variables
+pub fn file_store_from_env() -> SharedFileStore {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or("local".to_string());
+
+    match backend.to_lowercase().as_str() {
+        "s3" => Arc::new(S3FileStore::from_env().expect("Invalid S3 configuration")),
+        _ => {
+            let root = env::var("STORAGE_LOCAL_ROOT").unwrap_or("./uploads".to_string());
+            Arc::new(LocalFileStore::new(root))
+        }
+    }
+}
+
+/// Small sanitizer for client-supplied file names
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_' || *c == '-')
+        .collect::<String>()
+}
+
+/// A fresh key under `prefix`, keeping a sanitized form of the client's file name
+pub fn new_key(prefix: &str, file_name: &str) -> String {
+    format!("{}/{}_{}", prefix, Uuid::new_v4(), sanitize_filename(file_name))
+}
+
+/// Keys are relative, `/`-separated, and never step outside the store
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 1024
+        && !key.starts_with('/')
+        && !key.chars().any(|c| c == '\\' || c.is_control())
+        && key.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+/// Delete an object, logging instead of failing: used to clean up after
+/// records that are already gone
+pub async fn discard(store: &SharedFileStore, key: &str) {
+    if let Err(e) = store.delete(key).await {
+        eprintln!("Failed to delete stored file {}: {}", key, e);
+    }
+}
+
+/// A local store in a fresh temporary directory, for tests
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+
+    pub fn temp_store() -> (SharedFileStore, std::path::PathBuf) {
+        let root = env::temp_dir().join(format!("async_rust_store_{}", Uuid::new_v4().simple()));
+        (Arc::new(LocalFileStore::new(root.to_string_lossy().into_owned())), root)
+    }
+
+    pub async fn put_bytes(store: &dyn FileStore, key: &str, bytes: &'static [u8]) {
+        let body = stream::once(async move { Ok(Bytes::from_static(bytes)) }).boxed();
+        store.put(key, body, None).await.unwrap();
+    }
+
+    async fn read_all(object: StoredObject) -> Vec<u8> {
+        object
+            .body
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await
+    }
+
+    /// Run every `FileStore` operation against `store`, inside a fresh prefix
+    /// that is empty again afterwards
+    pub async fn exercise_store(store: &dyn FileStore) {
+        let prefix = format!("test-{}", Uuid::new_v4().simple());
+        let small = format!("{}/a/hello.txt", prefix);
+        let large = format!("{}/b/large.bin", prefix);
+        let broken = format!("{}/a/broken.bin", prefix);
+
+        let body = stream::once(async { Ok(Bytes::from_static(b"hello, world")) }).boxed();
+        let meta = store.put(&small, body, Some("text/plain")).await.unwrap();
+        assert_eq!(meta.key, small);
+        assert_eq!(meta.size, 12);
+
+        let meta = store.stat(&small).await.unwrap().unwrap();
+        assert_eq!(meta.size, 12);
+        assert_eq!(meta.content_type.as_deref(), Some("text/plain"));
+        assert!(meta.etag.is_some());
+
+        let object = store.get(&small, None).await.unwrap().unwrap();
+        assert_eq!(object.meta.size, 12);
+        assert_eq!(read_all(object).await, b"hello, world");
+
+        // A range streams only those bytes; the metadata still covers the whole file
+        let object = store
+            .get(&small, Some(ByteRange { start: 7, end: 11 }))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(object.meta.size, 12);
+        assert_eq!(read_all(object).await, b"world");
+
+        // Larger than one S3 multipart part, sent in many chunks
+        let chunks = (0..9).map(|_| Ok(Bytes::from(vec![7u8; 1024 * 1024])));
+        let meta = store.put(&large, stream::iter(chunks).boxed(), None).await.unwrap();
+        assert_eq!(meta.size, 9 * 1024 * 1024);
+        assert_eq!(store.stat(&large).await.unwrap().unwrap().size, 9 * 1024 * 1024);
+
+        // A body that breaks off leaves nothing behind
+        let chunks = vec![Ok(Bytes::from_static(b"partial")), Err(std::io::Error::other("broken"))];
+        assert!(store.put(&broken, stream::iter(chunks).boxed(), None).await.is_err());
+        assert!(store.stat(&broken).await.unwrap().is_none());
+
+        let mut listed: Vec<String> = store.list(&prefix).await.unwrap().into_iter().map(|m| m.key).collect();
+        listed.sort();
+        assert_eq!(listed, [small.as_str(), large.as_str()]);
+        let listed: Vec<String> = store
+            .list(&format!("{}/a/", prefix))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.key)
+            .collect();
+        assert_eq!(listed, [small.as_str()]);
+
+        store.delete(&small).await.unwrap();
+        store.delete(&large).await.unwrap();
+        assert!(store.stat(&small).await.unwrap().is_none());
+        assert!(store.get(&small, None).await.unwrap().is_none());
+        // Deleting what is already gone is fine
+        store.delete(&small).await.unwrap();
+        assert!(store.list(&prefix).await.unwrap().is_empty());
+    }
+}
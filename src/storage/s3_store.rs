@@ -0,0 +1,532 @@
+use std::env;
+
+use axum::async_trait;
+use axum::body::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED, RANGE};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{is_valid_key, ByteRange, ByteStream, FileStore, ObjectMeta, StoredObject};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Uploads larger than this go up in parts of about this size, so at most one
+/// part is held in memory. S3 needs every part but the last to be >= 5 MiB.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Objects in an S3 bucket, or anything that speaks the same API (MinIO,
+/// R2, ...). Requests are signed with AWS Signature Version 4.
+pub struct S3FileStore {
+    client: reqwest::Client,
+    /// Scheme and host, e.g. `https://s3.eu-west-1.amazonaws.com` or `http://localhost:9000`
+    endpoint: reqwest::Url,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    /// `endpoint/bucket/key` instead of `bucket.endpoint/key`; MinIO needs this
+    path_style: bool,
+}
+
+impl S3FileStore {
+    /// Reads `S3_BUCKET`, `S3_ACCESS_KEY_ID`, `S3_SECRET_ACCESS_KEY`, and
+    /// optionally `S3_REGION` (default `us-east-1`), `S3_ENDPOINT` (default
+    /// the AWS endpoint for the region) and `S3_PATH_STYLE` (default `true`)
+    pub fn from_env() -> Result<Self, String> {
+        let required = |name: &str| env::var(name).map_err(|_| format!("{} must be set", name));
+
+        let region = env::var("S3_REGION").unwrap_or("us-east-1".to_string());
+        let endpoint = env::var("S3_ENDPOINT")
+            .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+        let endpoint = reqwest::Url::parse(&endpoint).map_err(|e| format!("Invalid S3_ENDPOINT: {}", e))?;
+        let path_style = env::var("S3_PATH_STYLE")
+            .map(|v| v != "false" && v != "0")
+            .unwrap_or(true);
+
+        Ok(S3FileStore {
+            client: reqwest::Client::new(),
+            endpoint,
+            bucket: required("S3_BUCKET")?,
+            region,
+            access_key_id: required("S3_ACCESS_KEY_ID")?,
+            secret_access_key: required("S3_SECRET_ACCESS_KEY")?,
+            path_style,
+        })
+    }
+
+    /// URL of `key` (or of the bucket itself when `key` is empty), with `query`
+    /// already in canonical order
+    fn url(&self, key: &str, query: &[(&str, &str)]) -> Result<reqwest::Url, String> {
+        let mut url = self.endpoint.clone();
+        let encoded_key = uri_encode(key, false);
+        let path = if self.path_style {
+            format!("/{}/{}", self.bucket, encoded_key)
+        } else {
+            let host = format!("{}.{}", self.bucket, url.host_str().unwrap_or_default());
+            url.set_host(Some(&host)).map_err(|e| e.to_string())?;
+            format!("/{}", encoded_key)
+        };
+        url.set_path(&path);
+
+        let query: Vec<String> = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", uri_encode(k, true), uri_encode(v, true)))
+            .collect();
+        url.set_query((!query.is_empty()).then(|| query.join("&")).as_deref());
+        Ok(url)
+    }
+
+    /// Build a signed request. `payload_hash` is the hex SHA-256 of the body.
+    fn signed(
+        &self,
+        method: Method,
+        url: reqwest::Url,
+        payload_hash: &str,
+    ) -> Result<reqwest::RequestBuilder, String> {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method.as_str(),
+            url.path(),
+            url.query().unwrap_or_default(),
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac(format!("AWS4{}", self.secret_access_key).as_bytes(), date.as_bytes())?;
+        let key = hmac(&key, self.region.as_bytes())?;
+        let key = hmac(&key, b"s3")?;
+        let key = hmac(&key, b"aws4_request")?;
+        let signature = hex::encode(hmac(&key, string_to_sign.as_bytes())?);
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        );
+
+        Ok(self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization))
+    }
+
+    fn object_url(&self, key: &str) -> Result<reqwest::Url, String> {
+        self.object_url_with(key, &[])
+    }
+
+    fn object_url_with(&self, key: &str, query: &[(&str, &str)]) -> Result<reqwest::Url, String> {
+        if !is_valid_key(key) {
+            return Err("Invalid storage key".to_string());
+        }
+        self.url(key, query)
+    }
+
+    /// Plain `PutObject`, for files that fit in one part
+    async fn put_single(&self, key: &str, bytes: Bytes, content_type: Option<&str>) -> Result<ObjectMeta, String> {
+        let url = self.object_url(key)?;
+        let payload_hash = hex::encode(Sha256::digest(&bytes));
+        let size = bytes.len() as u64;
+
+        let mut request = self.signed(Method::PUT, url, &payload_hash)?;
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        let response = request.body(bytes).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("upload", key, response).await);
+        }
+
+        Ok(ObjectMeta {
+            key: key.to_string(),
+            size,
+            content_type: content_type.map(str::to_string),
+            last_modified: Some(chrono::Utc::now()),
+            etag: meta_from_headers(key, response.headers()).etag,
+        })
+    }
+
+    async fn create_multipart_upload(&self, key: &str, content_type: Option<&str>) -> Result<String, String> {
+        let url = self.object_url_with(key, &[("uploads", "")])?;
+        let mut request = self.signed(Method::POST, url, EMPTY_PAYLOAD_SHA256)?;
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("upload start", key, response).await);
+        }
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        let started: InitiateMultipartUploadResult = quick_xml::de::from_str(&body).map_err(|e| e.to_string())?;
+        Ok(started.upload_id)
+    }
+
+    /// Upload one part and return its ETag
+    async fn upload_part(&self, key: &str, upload_id: &str, number: usize, bytes: Bytes) -> Result<String, String> {
+        let number = number.to_string();
+        let url = self.object_url_with(key, &[("partNumber", &number), ("uploadId", upload_id)])?;
+        let payload_hash = hex::encode(Sha256::digest(&bytes));
+        let response = self
+            .signed(Method::PUT, url, &payload_hash)?
+            .body(bytes)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("part upload", key, response).await);
+        }
+        meta_from_headers(key, response.headers())
+            .etag
+            .ok_or(format!("S3 returned no ETag for part {} of {}", number, key))
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        etags: &[String],
+    ) -> Result<Option<String>, String> {
+        let url = self.object_url_with(key, &[("uploadId", upload_id)])?;
+        let parts: String = etags
+            .iter()
+            .enumerate()
+            .map(|(i, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag))
+            .collect();
+        let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+        let payload_hash = hex::encode(Sha256::digest(body.as_bytes()));
+
+        let response = self
+            .signed(Method::POST, url, &payload_hash)?
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(failure("upload completion", key, response).await);
+        }
+        // S3 can report a failed completion in a 200 response
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        match quick_xml::de::from_str::<CompleteMultipartUploadResult>(&text) {
+            Ok(completed) => Ok(completed.e_tag),
+            Err(_) => Err(format!("S3 upload completion of {} failed: {}", key, text.trim())),
+        }
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) {
+        let aborted = async {
+            let url = self.object_url_with(key, &[("uploadId", upload_id)])?;
+            let response = self
+                .signed(Method::DELETE, url, EMPTY_PAYLOAD_SHA256)?
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+                return Err(failure("upload abort", key, response).await);
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = aborted {
+            eprintln!("Failed to abort multipart upload of {}: {}", key, e);
+        }
+    }
+
+    /// Stream `body` up part by part; the upload is aborted if anything fails
+    async fn put_multipart(
+        &self,
+        key: &str,
+        first: Bytes,
+        mut body: ByteStream<'_>,
+        content_type: Option<&str>,
+    ) -> Result<ObjectMeta, String> {
+        let upload_id = self.create_multipart_upload(key, content_type).await?;
+
+        let uploaded = async {
+            let mut etags = vec![];
+            let mut size = 0u64;
+            let mut part = first;
+            while !part.is_empty() {
+                size += part.len() as u64;
+                etags.push(self.upload_part(key, &upload_id, etags.len() + 1, part).await?);
+                part = read_part(&mut body).await?;
+            }
+            let etag = self.complete_multipart_upload(key, &upload_id, &etags).await?;
+            Ok::<_, String>((size, etag))
+        }
+        .await;
+
+        match uploaded {
+            Ok((size, etag)) => Ok(ObjectMeta {
+                key: key.to_string(),
+                size,
+                content_type: content_type.map(str::to_string),
+                last_modified: Some(chrono::Utc::now()),
+                etag,
+            }),
+            Err(e) => {
+                self.abort_multipart_upload(key, &upload_id).await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Up to `PART_SIZE` bytes from the stream; empty once it is exhausted
+async fn read_part(body: &mut ByteStream<'_>) -> Result<Bytes, String> {
+    let mut part = Vec::new();
+    while part.len() < PART_SIZE {
+        match body.next().await {
+            Some(chunk) => part.extend_from_slice(&chunk.map_err(|e| e.to_string())?),
+            None => break,
+        }
+    }
+    Ok(Bytes::from(part))
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut mac = HmacSha256::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+const EMPTY_PAYLOAD_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// S3's URI encoding: everything but unreserved characters is percent-encoded;
+/// `/` is kept in paths
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn meta_from_headers(key: &str, headers: &HeaderMap) -> ObjectMeta {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    ObjectMeta {
+        key: key.to_string(),
+        size: header(CONTENT_LENGTH).and_then(|v| v.parse().ok()).unwrap_or_default(),
+        content_type: header(CONTENT_TYPE).map(str::to_string),
+        last_modified: header(LAST_MODIFIED)
+            .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+            .map(|d| d.with_timezone(&chrono::Utc)),
+        etag: header(ETAG).map(str::to_string),
+    }
+}
+
+async fn failure(action: &str, key: &str, response: reqwest::Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    format!("S3 {} of {} failed with {}: {}", action, key, status, body.trim())
+}
+
+/// The parts of a `ListObjectsV2` response we use
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<ListedObject>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+    key: String,
+    size: u64,
+    last_modified: Option<String>,
+    e_tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompleteMultipartUploadResult {
+    e_tag: Option<String>,
+}
+
+#[async_trait]
+impl FileStore for S3FileStore {
+    async fn put(
+        &self,
+        key: &str,
+        mut body: ByteStream<'_>,
+        content_type: Option<&str>,
+    ) -> Result<ObjectMeta, String> {
+        if !is_valid_key(key) {
+            return Err("Invalid storage key".to_string());
+        }
+
+        let first = read_part(&mut body).await?;
+        if first.len() < PART_SIZE {
+            return self.put_single(key, first, content_type).await;
+        }
+        self.put_multipart(key, first, body, content_type).await
+    }
+
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> Result<Option<StoredObject>, String> {
+        let url = self.object_url(key)?;
+        let mut request = self.signed(Method::GET, url, EMPTY_PAYLOAD_SHA256)?;
+        if let Some(range) = range {
+            request = request.header(RANGE, format!("bytes={}-{}", range.start, range.end));
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(failure("download", key, response).await);
+        }
+
+        let mut meta = meta_from_headers(key, response.headers());
+        // A partial response's Content-Length is the part; the total follows the `/`
+        if let Some(total) = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit_once('/'))
+            .and_then(|(_, total)| total.parse().ok())
+        {
+            meta.size = total;
+        }
+        let body = response.bytes_stream().map_err(std::io::Error::other).boxed();
+        Ok(Some(StoredObject { meta, body }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        let url = self.object_url(key)?;
+        let response = self
+            .signed(Method::DELETE, url, EMPTY_PAYLOAD_SHA256)?
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(failure("delete", key, response).await);
+        }
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<ObjectMeta>, String> {
+        let url = self.object_url(key)?;
+        let response = self
+            .signed(Method::HEAD, url, EMPTY_PAYLOAD_SHA256)?
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(failure("stat", key, response).await);
+        }
+        Ok(Some(meta_from_headers(key, response.headers())))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, String> {
+        let mut objects = vec![];
+        let mut continuation: Option<String> = None;
+
+        loop {
+            // Canonical (sorted) order, as the signature requires
+            let mut query = vec![];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+            query.push(("list-type", "2"));
+            query.push(("prefix", prefix));
+
+            let url = self.url("", &query)?;
+            let response = self
+                .signed(Method::GET, url, EMPTY_PAYLOAD_SHA256)?
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(failure("listing", prefix, response).await);
+            }
+            let body = response.text().await.map_err(|e| e.to_string())?;
+            let page: ListBucketResult = quick_xml::de::from_str(&body).map_err(|e| e.to_string())?;
+
+            objects.extend(page.contents.into_iter().map(|o| ObjectMeta {
+                key: o.key,
+                size: o.size,
+                content_type: None,
+                last_modified: o
+                    .last_modified
+                    .and_then(|v| chrono::DateTime::parse_from_rfc3339(&v).ok())
+                    .map(|d| d.with_timezone(&chrono::Utc)),
+                etag: o.e_tag,
+            }));
+
+            match page.next_continuation_token {
+                Some(token) if page.is_truncated => continuation = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::exercise_store;
+
+    /// Runs against a real bucket, e.g. a local MinIO, only when `TEST_S3_ENDPOINT`,
+    /// `TEST_S3_BUCKET`, `TEST_S3_ACCESS_KEY_ID` and `TEST_S3_SECRET_ACCESS_KEY`
+    /// are set. The bucket must already exist.
+    #[tokio::test]
+    async fn s3_store_round_trip() {
+        let Ok(endpoint) = env::var("TEST_S3_ENDPOINT") else {
+            eprintln!("TEST_S3_ENDPOINT not set, skipping");
+            return;
+        };
+        let store = S3FileStore {
+            client: reqwest::Client::new(),
+            endpoint: reqwest::Url::parse(&endpoint).expect("Invalid TEST_S3_ENDPOINT"),
+            bucket: env::var("TEST_S3_BUCKET").expect("TEST_S3_BUCKET must be set"),
+            region: env::var("TEST_S3_REGION").unwrap_or("us-east-1".to_string()),
+            access_key_id: env::var("TEST_S3_ACCESS_KEY_ID").expect("TEST_S3_ACCESS_KEY_ID must be set"),
+            secret_access_key: env::var("TEST_S3_SECRET_ACCESS_KEY")
+                .expect("TEST_S3_SECRET_ACCESS_KEY must be set"),
+            path_style: true,
+        };
+
+        exercise_store(&store).await;
+    }
+}
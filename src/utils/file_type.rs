@@ -0,0 +1,131 @@
+//! File type detection from leading "magic" bytes, so an upload is judged by
+//! its contents rather than the name or type the client claims.
+
+/// How many leading bytes `sniff` needs to recognise every known type
+pub const SNIFF_LEN: usize = 16;
+
+pub struct FileType {
+    pub mime: &'static str,
+    /// Accepted file name extensions, lowercase; the first is the canonical one
+    pub extensions: &'static [&'static str],
+}
+
+const JPEG: FileType = FileType {
+    mime: "image/jpeg",
+    extensions: &["jpg", "jpeg", "jpe", "jfif", "pjpeg", "pjp"],
+};
+const PNG: FileType = FileType { mime: "image/png", extensions: &["png"] };
+const GIF: FileType = FileType { mime: "image/gif", extensions: &["gif"] };
+const WEBP: FileType = FileType { mime: "image/webp", extensions: &["webp"] };
+const HEIC: FileType = FileType { mime: "image/heic", extensions: &["heic", "heif"] };
+const PDF: FileType = FileType { mime: "application/pdf", extensions: &["pdf"] };
+
+/// The type `head` (the first bytes of a file) starts like, if any we accept
+pub fn sniff(head: &[u8]) -> Option<&'static FileType> {
+    if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(&JPEG)
+    } else if head.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(&PNG)
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        Some(&GIF)
+    } else if head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == b"WEBP" {
+        Some(&WEBP)
+    } else if head.len() >= 12
+        && &head[4..8] == b"ftyp"
+        && matches!(&head[8..12], b"heic" | b"heix" | b"heim" | b"heis" | b"mif1" | b"msf1")
+    {
+        Some(&HEIC)
+    } else if head.starts_with(b"%PDF-") {
+        Some(&PDF)
+    } else {
+        None
+    }
+}
+
+/// The extension of `file_name`, lowercased; `None` when it has none
+pub fn extension(file_name: &str) -> Option<String> {
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    (!stem.is_empty() && !ext.is_empty()).then(|| ext.to_lowercase())
+}
+
+/// A name without an extension gets the type's canonical one; a name whose
+/// extension belongs to another type is refused with `None`
+pub fn name_for(file_type: &FileType, file_name: &str) -> Option<String> {
+    match extension(file_name) {
+        Some(ext) if file_type.extensions.contains(&ext.as_str()) => Some(file_name.to_string()),
+        Some(_) => None,
+        None => Some(format!("{}.{}", file_name, file_type.extensions[0])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mime(head: &[u8]) -> Option<&'static str> {
+        sniff(head).map(|t| t.mime)
+    }
+
+    #[test]
+    fn recognises_each_magic_number() {
+        assert_eq!(mime(&[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F']), Some("image/jpeg"));
+        assert_eq!(mime(&[0xFF, 0xD8, 0xFF, 0xE1]), Some("image/jpeg"));
+        assert_eq!(mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(mime(b"GIF87a\x01\0\x01\0"), Some("image/gif"));
+        assert_eq!(mime(b"GIF89a\x01\0\x01\0"), Some("image/gif"));
+        assert_eq!(mime(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+        for brand in [&b"heic"[..], b"heix", b"heim", b"heis", b"mif1", b"msf1"] {
+            let head = [&b"\0\0\0\x18ftyp"[..], brand, b"\0\0\0\0"].concat();
+            assert_eq!(mime(&head), Some("image/heic"));
+        }
+        assert_eq!(mime(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3"), Some("application/pdf"));
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_contents() {
+        // A Windows executable, whatever it is called
+        assert_eq!(mime(b"MZ\x90\0\x03\0\0\0\x04\0\0\0\xFF\xFF\0\0"), None);
+        // A RIFF container that isn't WebP, and other ftyp brands
+        assert_eq!(mime(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(mime(b"\0\0\0\x18ftypmp42\0\0\0\0"), None);
+        assert_eq!(mime(b"RIFF"), None);
+        assert_eq!(mime(&[0xFF, 0xD8]), None);
+        assert_eq!(mime(b""), None);
+    }
+
+    #[test]
+    fn extension_is_lowercased_and_needs_a_stem() {
+        assert_eq!(extension("photo.JPG").as_deref(), Some("jpg"));
+        assert_eq!(extension("archive.tar.gz").as_deref(), Some("gz"));
+        assert_eq!(extension("photo"), None);
+        assert_eq!(extension(".hidden"), None);
+        assert_eq!(extension("photo."), None);
+    }
+
+    #[test]
+    fn renamed_executable_is_refused() {
+        let exe = b"MZ\x90\0\x03\0\0\0\x04\0\0\0\xFF\xFF\0\0";
+        assert!(sniff(exe).is_none());
+
+        // And a real JPEG can't be passed off under another type's extension
+        let jpeg = sniff(&[0xFF, 0xD8, 0xFF, 0xE0]).unwrap();
+        assert_eq!(name_for(jpeg, "setup.exe"), None);
+        assert_eq!(name_for(jpeg, "scan.pdf"), None);
+    }
+
+    #[test]
+    fn name_without_extension_gets_the_canonical_one() {
+        let jpeg = sniff(&[0xFF, 0xD8, 0xFF, 0xE0]).unwrap();
+        let pdf = sniff(b"%PDF-1.4").unwrap();
+        assert_eq!(name_for(jpeg, "photo").as_deref(), Some("photo.jpg"));
+        assert_eq!(name_for(pdf, "policy").as_deref(), Some("policy.pdf"));
+    }
+
+    #[test]
+    fn every_jpeg_extension_is_accepted() {
+        let jpeg = sniff(&[0xFF, 0xD8, 0xFF, 0xE0]).unwrap();
+        for name in ["a.jpg", "a.jpeg", "a.JPE", "a.jfif", "a.pjpeg", "a.pjp"] {
+            assert_eq!(name_for(jpeg, name).as_deref(), Some(name));
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod crypto;
+pub mod file_type;
+pub mod jwt;
+pub mod signed_url;
+pub mod totp;
+pub mod validation;
+pub mod vin;
@@ -0,0 +1,97 @@
+//! HMAC signatures for time-limited file links.
+//!
+//! `FILE_URL_SECRET` holds the signing secret (at least 32 bytes). Every
+//! replica must share it; changing it invalidates all links handed out so far.
+
+use std::env;
+use std::sync::OnceLock;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::models::file_model::Disposition;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MIN_SECRET_LEN: usize = 32;
+
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Load the secret. Called once at startup; a missing or short secret is fatal.
+pub fn init_from_env() -> Result<(), String> {
+    let secret = env::var("FILE_URL_SECRET").map_err(|_| "FILE_URL_SECRET must be set".to_string())?;
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(format!("FILE_URL_SECRET must be at least {} bytes", MIN_SECRET_LEN));
+    }
+    SECRET
+        .set(secret.into_bytes())
+        .map_err(|_| "File URL secret already initialised".to_string())
+}
+
+fn mac(key: &str, expires: i64, disposition: Option<Disposition>) -> HmacSha256 {
+    let secret = SECRET.get().expect("File URL secret not initialised");
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    let disposition = disposition.map(|d| d.as_str()).unwrap_or_default();
+    mac.update(format!("{}\n{}\n{}", key, expires, disposition).as_bytes());
+    mac
+}
+
+/// Signature over one key, its expiry and its disposition, so none of them can
+/// be swapped on an issued link
+pub fn sign(key: &str, expires: i64, disposition: Option<Disposition>) -> String {
+    URL_SAFE_NO_PAD.encode(mac(key, expires, disposition).finalize().into_bytes())
+}
+
+/// Constant-time check of a signature produced by `sign`
+pub fn verify(key: &str, expires: i64, disposition: Option<Disposition>, signature: &str) -> bool {
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    mac(key, expires, disposition).verify_slice(&signature).is_ok()
+}
+
+/// Use a fixed secret, for tests that sign or check links
+#[cfg(test)]
+pub fn init_for_tests() {
+    SECRET.get_or_init(|| b"test-secret-that-is-at-least-32-bytes".to_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "vehicles/3f2a_photo.jpg";
+    const EXPIRES: i64 = 1_900_000_000;
+
+    #[test]
+    fn signature_verifies_for_what_was_signed() {
+        init_for_tests();
+        for disposition in [None, Some(Disposition::Inline), Some(Disposition::Attachment)] {
+            let signature = sign(KEY, EXPIRES, disposition);
+            assert!(verify(KEY, EXPIRES, disposition, &signature));
+        }
+    }
+
+    #[test]
+    fn changing_any_signed_part_fails() {
+        init_for_tests();
+        let signature = sign(KEY, EXPIRES, Some(Disposition::Inline));
+
+        assert!(!verify("vehicles/3f2a_other.jpg", EXPIRES, Some(Disposition::Inline), &signature));
+        assert!(!verify(KEY, EXPIRES + 1, Some(Disposition::Inline), &signature));
+        assert!(!verify(KEY, EXPIRES, Some(Disposition::Attachment), &signature));
+        assert!(!verify(KEY, EXPIRES, None, &signature));
+    }
+
+    #[test]
+    fn malformed_signatures_fail() {
+        init_for_tests();
+        let signature = sign(KEY, EXPIRES, None);
+
+        assert!(!verify(KEY, EXPIRES, None, ""));
+        assert!(!verify(KEY, EXPIRES, None, "not base64!"));
+        assert!(!verify(KEY, EXPIRES, None, &signature[..signature.len() - 2]));
+        assert!(!verify(KEY, EXPIRES, None, &format!("{}AA", signature)));
+    }
+}
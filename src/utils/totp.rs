@@ -0,0 +1,183 @@
+//! RFC 6238 time-based one-time passwords (SHA-1, 6 digits, 30 s steps),
+//! the variant every authenticator app supports.
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Accepted clock drift, in steps, on either side of now
+const SKEW_STEPS: i64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Generate a new 160-bit shared secret, base32 encoded
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// `otpauth://` URI for QR codes
+pub fn provisioning_uri(secret: &str, issuer: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        url_encode(issuer),
+        url_encode(account),
+        secret,
+        url_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+/// Check `code` against `secret` at `now` (unix seconds).
+/// Returns the matching time step so callers can reject replays of the same code.
+pub fn verify(secret: &str, code: &str, now: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let key = base32_decode(secret)?;
+    let current = now / STEP_SECONDS;
+
+    (current - SKEW_STEPS..=current + SKEW_STEPS).find(|step| hotp(&key, *step as u64) == code)
+}
+
+/// The code for `secret` at `now`, as an authenticator app would show it
+#[cfg(test)]
+pub fn code_at(secret: &str, now: i64) -> Option<String> {
+    Some(hotp(&base32_decode(secret)?, (now / STEP_SECONDS) as u64))
+}
+
+fn hotp(key: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[19] & 0x0f) as usize;
+    let binary = ((digest[offset] as u32 & 0x7f) << 24)
+        | ((digest[offset + 1] as u32) << 16)
+        | ((digest[offset + 2] as u32) << 8)
+        | (digest[offset + 3] as u32);
+
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RFC 4226 / RFC 6238 SHA-1 test key, "12345678901234567890"
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn base32_round_trips_the_rfc_key() {
+        assert_eq!(base32_encode(RFC_KEY), RFC_SECRET);
+        assert_eq!(base32_decode(RFC_SECRET).unwrap(), RFC_KEY);
+        assert_eq!(base32_decode(&RFC_SECRET.to_lowercase()).unwrap(), RFC_KEY);
+        assert_eq!(base32_decode("MZXW6YQ=").unwrap(), b"foob");
+        assert!(base32_decode("not base32!").is_none());
+
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_decode(&secret).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226_appendix_d() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314",
+            "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(RFC_KEY, counter as u64), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238_appendix_b() {
+        // The RFC lists 8-digit codes; 6-digit ones are their last six digits
+        let expected = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in expected {
+            assert_eq!(code_at(RFC_SECRET, time).unwrap(), code, "time {}", time);
+            assert_eq!(verify(RFC_SECRET, code, time), Some(time / STEP_SECONDS));
+        }
+    }
+
+    #[test]
+    fn verify_allows_one_step_of_drift() {
+        let now = 1111111111;
+        let code = code_at(RFC_SECRET, now).unwrap();
+
+        assert_eq!(verify(RFC_SECRET, &code, now + STEP_SECONDS), Some(now / STEP_SECONDS));
+        assert_eq!(verify(RFC_SECRET, &code, now - STEP_SECONDS), Some(now / STEP_SECONDS));
+        assert_eq!(verify(RFC_SECRET, &code, now + 2 * STEP_SECONDS), None);
+        assert_eq!(verify(RFC_SECRET, &format!(" {} ", code), now), Some(now / STEP_SECONDS));
+    }
+
+    #[test]
+    fn verify_rejects_malformed_codes() {
+        assert_eq!(verify(RFC_SECRET, "28708", 59), None);
+        assert_eq!(verify(RFC_SECRET, "2870820", 59), None);
+        assert_eq!(verify(RFC_SECRET, "28708a", 59), None);
+        assert_eq!(verify("not base32!", "287082", 59), None);
+    }
+}
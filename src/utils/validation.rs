@@ -0,0 +1,82 @@
+//! Field-level validation errors, reported all at once so clients can mark
+//! every bad input in a form instead of fixing them one round-trip at a time.
+
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Collects errors while a payload is checked field by field
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Record `message` against `field` unless `ok` holds
+    pub fn check(&mut self, ok: bool, field: &str, message: &str) {
+        if !ok {
+            self.add(field, message);
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// Accepts a plain `YYYY-MM-DD` (midnight UTC) or a full RFC 3339 timestamp
+pub fn parse_date(text: &str) -> Option<chrono::DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    chrono::DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// The raw value of a parsed field; `None` (with an error if `required`) when missing or blank
+pub fn required_text<'a>(v: &mut Validator, field: &str, text: Option<&'a str>, required: bool) -> Option<&'a str> {
+    match text.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(text) => Some(text),
+        None => {
+            if required || text.is_some() {
+                v.add(field, "is required");
+            }
+            None
+        }
+    }
+}
+
+/// Blank optional text is treated as absent
+pub fn optional_text(v: &mut Validator, field: &str, text: Option<String>, max_len: usize) -> Option<String> {
+    let text = text?.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() > max_len {
+        v.add(field, format!("must be at most {} characters", max_len));
+        return None;
+    }
+    Some(text)
+}
@@ -0,0 +1,199 @@
+//! ISO 3779 vehicle identification numbers: validation and offline decoding.
+//!
+//! Manufacturer (WMI) and country tables come from `data/vin_wmi.json`, compiled
+//! into the binary so decoding never needs the network.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chrono::Datelike;
+use serde::Deserialize;
+
+use crate::models::vehicle_model::VinDecoded;
+
+const VIN_LENGTH: usize = 17;
+const CHECK_DIGIT_POSITION: usize = 8;
+const WEIGHTS: [u32; VIN_LENGTH] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+/// Model-year codes in order, starting at 1980; the cycle repeats every 30 years
+const YEAR_CODES: &[u8; 30] = b"ABCDEFGHJKLMNPRSTVWXY123456789";
+const FIRST_YEAR: i32 = 1980;
+
+#[derive(Deserialize)]
+struct WmiTable {
+    countries: HashMap<String, String>,
+    manufacturers: HashMap<String, String>,
+}
+
+fn wmi_table() -> &'static WmiTable {
+    static TABLE: OnceLock<WmiTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        serde_json::from_str(include_str!("../../data/vin_wmi.json"))
+            .expect("Bundled VIN data file is invalid")
+    })
+}
+
+/// Value of a VIN character in the check-digit sum; `None` for characters a VIN may not contain
+fn transliterate(c: u8) -> Option<u32> {
+    match c {
+        b'0'..=b'9' => Some((c - b'0') as u32),
+        b'A' | b'J' => Some(1),
+        b'B' | b'K' | b'S' => Some(2),
+        b'C' | b'L' | b'T' => Some(3),
+        b'D' | b'M' | b'U' => Some(4),
+        b'E' | b'N' | b'V' => Some(5),
+        b'F' | b'W' => Some(6),
+        b'G' | b'P' | b'X' => Some(7),
+        b'H' | b'Y' => Some(8),
+        b'R' | b'Z' => Some(9),
+        _ => None,
+    }
+}
+
+/// Upper-case and validate a VIN: 17 characters, no I/O/Q, correct check digit
+pub fn normalize(vin: &str) -> Result<String, String> {
+    let vin = vin.trim().to_ascii_uppercase();
+    let bytes = vin.as_bytes();
+
+    if bytes.len() != VIN_LENGTH {
+        return Err(format!("VIN must be {} characters", VIN_LENGTH));
+    }
+
+    let mut sum = 0;
+    for (i, &c) in bytes.iter().enumerate() {
+        let value = transliterate(c).ok_or(format!("VIN contains invalid character '{}'", c as char))?;
+        sum += value * WEIGHTS[i];
+    }
+
+    let expected = match sum % 11 {
+        10 => b'X',
+        digit => b'0' + digit as u8,
+    };
+    if bytes[CHECK_DIGIT_POSITION] != expected {
+        return Err("VIN check digit does not match".to_string());
+    }
+
+    Ok(vin)
+}
+
+/// Model year from position 10. The code alone is ambiguous across 30-year
+/// cycles; as in North America, a letter in position 7 means 2010 or later.
+fn model_year(bytes: &[u8]) -> Option<i32> {
+    let index = YEAR_CODES.iter().position(|&c| c == bytes[9])? as i32;
+    let mut year = FIRST_YEAR + index;
+
+    if bytes[6].is_ascii_alphabetic() {
+        let latest = chrono::Utc::now().year() + 1;
+        year += 30;
+        while year + 30 <= latest {
+            year += 30;
+        }
+    }
+
+    Some(year)
+}
+
+/// Validate and decode a VIN using the bundled tables
+pub fn decode(vin: &str) -> Result<VinDecoded, String> {
+    let vin = normalize(vin)?;
+    let bytes = vin.as_bytes();
+    let table = wmi_table();
+
+    let wmi = vin[..3].to_string();
+    Ok(VinDecoded {
+        manufacturer: table.manufacturers.get(&wmi).cloned(),
+        country: table.countries.get(&vin[..1]).cloned(),
+        model_year: model_year(bytes),
+        plant_code: vin[10..11].to_string(),
+        serial_number: vin[11..].to_string(),
+        wmi,
+        vin,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `vin` with position 9 replaced by the check digit it should have
+    fn with_check_digit(vin: &str) -> String {
+        let mut bytes = vin.as_bytes().to_vec();
+        let sum: u32 = bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| transliterate(c).unwrap() * WEIGHTS[i])
+            .sum();
+        bytes[CHECK_DIGIT_POSITION] = match sum % 11 {
+            10 => b'X',
+            digit => b'0' + digit as u8,
+        };
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn accepts_known_good_vins() {
+        for vin in ["1M8GDM9AXKP042788", "1HGCM82633A004352", "11111111111111111"] {
+            assert_eq!(normalize(vin), Ok(vin.to_string()));
+        }
+        // Trimmed and upper-cased first
+        assert_eq!(normalize(" 1m8gdm9axkp042788 "), Ok("1M8GDM9AXKP042788".to_string()));
+    }
+
+    #[test]
+    fn rejects_wrong_check_digit_and_length() {
+        assert_eq!(
+            normalize("1M8GDM9A1KP042788"),
+            Err("VIN check digit does not match".to_string())
+        );
+        assert_eq!(
+            normalize("1HGCM82643A004352"),
+            Err("VIN check digit does not match".to_string())
+        );
+        assert!(normalize("1M8GDM9AXKP04278").is_err());
+        assert!(normalize("1M8GDM9AXKP0427888").is_err());
+    }
+
+    #[test]
+    fn rejects_i_o_and_q() {
+        for bad in ['I', 'O', 'Q'] {
+            let vin = format!("1M8GDM9AXKP04278{}", bad);
+            assert_eq!(
+                normalize(&vin),
+                Err(format!("VIN contains invalid character '{}'", bad))
+            );
+        }
+    }
+
+    #[test]
+    fn transliterates_letters_per_iso_3779() {
+        let values: Vec<u32> = b"ABCDEFGHJKLMNPRSTUVWXYZ".iter().map(|&c| transliterate(c).unwrap()).collect();
+        assert_eq!(values, [1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 7, 9, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(transliterate(b'-'), None);
+    }
+
+    #[test]
+    fn model_year_code_is_read_in_the_right_cycle() {
+        // Digit in position 7: the 1980-2009 cycle
+        let older = with_check_digit("1HGCM826X3A004352");
+        assert_eq!(decode(&older).unwrap().model_year, Some(2003));
+        let older = with_check_digit("1M8GDM9AXAP042788");
+        assert_eq!(decode(&older).unwrap().model_year, Some(1980));
+
+        // Same year code, letter in position 7: 2010 onwards
+        let newer = with_check_digit("1M8GDMAAXAP042788");
+        assert_eq!(decode(&newer).unwrap().model_year, Some(2010));
+        let newer = with_check_digit("1HGCM8A6X3A004352");
+        assert_eq!(decode(&newer).unwrap().model_year, Some(2033));
+    }
+
+    #[test]
+    fn decodes_manufacturer_country_and_serial() {
+        let decoded = decode("1HGCM82633A004352").unwrap();
+        assert_eq!(decoded.wmi, "1HG");
+        assert_eq!(decoded.manufacturer.as_deref(), Some("Honda"));
+        assert_eq!(decoded.country.as_deref(), Some("United States"));
+        assert_eq!(decoded.model_year, Some(2003));
+        assert_eq!(decoded.plant_code, "A");
+        assert_eq!(decoded.serial_number, "004352");
+    }
+}